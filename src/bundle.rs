@@ -0,0 +1,129 @@
+use std::{
+    collections::BTreeSet,
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    kind::Kind,
+    rank::{RankMode, rank},
+    search::{Corpus, Term},
+    vault::{DocId, Vault},
+};
+
+#[derive(Debug, Error)]
+pub enum BundleError {
+    #[error("failed to write the bundle `{path}` because {reason}")]
+    WriteFailed { path: PathBuf, reason: String },
+    #[error("failed to read the bundle `{path}` because {reason}")]
+    ReadFailed { path: PathBuf, reason: String },
+    #[error(
+        "the bundle `{path}` is in version {version}, but only version {} is supported",
+        Bundle::VERSION
+    )]
+    UnsupportedVersion { path: PathBuf, version: u32 },
+}
+
+/// A note of a bundle
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BundleDocument {
+    /// The path of the note, relative to the root of the vault
+    pub path: PathBuf,
+    pub title: String,
+    pub kind: Kind,
+    pub tags: BTreeSet<String>,
+    pub metadata: serde_json::Value,
+    pub rank: f32,
+    /// The notes of the bundle this one links to, as indices into its documents
+    pub links: Vec<usize>,
+}
+
+/// A self-contained snapshot of the index of a vault: the metadata of its notes, the link graph,
+/// their ranks, and the statistics of the search corpus. It can be read without the notes
+/// themselves, e.g. on another machine. It's stored as gzipped JSON.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Bundle {
+    pub version: u32,
+    pub documents: Vec<BundleDocument>,
+    pub vocabulary: Vec<Term>,
+}
+
+impl Bundle {
+    pub const VERSION: u32 = 2;
+
+    /// Take a snapshot of the visible notes of the vault
+    pub fn new(vault: &Vault, num_iter: usize, tol: f32) -> Self {
+        let root = vault.path().canonicalize().unwrap_or_else(|_| vault.path());
+        let ids = vault.ids();
+        let ranks = rank(vault, &ids, RankMode::Global, num_iter, tol);
+        // Links to hidden notes are left out, since they aren't part of the bundle
+        let index = |target: &DocId| ids.binary_search(target).ok();
+
+        let documents = ids
+            .iter()
+            .zip(ranks)
+            .map(|(&id, rank)| {
+                let doc = vault.document(id);
+                let path = doc.path().path();
+                BundleDocument {
+                    path: path.strip_prefix(&root).unwrap_or(&path).to_path_buf(),
+                    title: doc.title(),
+                    kind: doc.kind(),
+                    tags: doc.tags(),
+                    metadata: serde_json::to_value(doc.metadata()).unwrap_or_default(),
+                    rank,
+                    links: vault.outbound(id).iter().filter_map(index).collect(),
+                }
+            })
+            .collect();
+
+        // Recompute the statistics without the hidden notes, so that none of their words leak
+        let corpus = Corpus::new(
+            ids.iter()
+                .map(|&id| vault.document(id).stripped().unwrap())
+                .collect(),
+            vault.config().tokenizer.get(),
+        );
+        Bundle {
+            version: Self::VERSION,
+            documents,
+            vocabulary: corpus.vocabulary(),
+        }
+    }
+
+    pub fn export(&self, path: &Path) -> Result<(), BundleError> {
+        let write_failed = |reason: String| BundleError::WriteFailed {
+            path: path.to_path_buf(),
+            reason,
+        };
+        let file = File::create(path).map_err(|e| write_failed(e.to_string()))?;
+        let mut encoder = GzEncoder::new(BufWriter::new(file), Compression::default());
+        serde_json::to_writer(&mut encoder, self).map_err(|e| write_failed(e.to_string()))?;
+        encoder
+            .finish()
+            .and_then(|mut writer| writer.flush())
+            .map_err(|e| write_failed(e.to_string()))
+    }
+
+    pub fn import(path: &Path) -> Result<Self, BundleError> {
+        let read_failed = |reason: String| BundleError::ReadFailed {
+            path: path.to_path_buf(),
+            reason,
+        };
+        let file = File::open(path).map_err(|e| read_failed(e.to_string()))?;
+        let bundle: Bundle = serde_json::from_reader(GzDecoder::new(BufReader::new(file)))
+            .map_err(|e| read_failed(e.to_string()))?;
+        if bundle.version != Self::VERSION {
+            return Err(BundleError::UnsupportedVersion {
+                path: path.to_path_buf(),
+                version: bundle.version,
+            });
+        }
+        Ok(bundle)
+    }
+}
@@ -0,0 +1,537 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fmt::Display,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use owo_colors::OwoColorize;
+use percent_encoding::percent_decode_str;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use serde::Serialize;
+
+use url::Url;
+
+use crate::{
+    config::{LinkPolicy, Schema},
+    document::{Document, ValueType},
+    fmt,
+    path::MarkdownPath,
+    pos::Pos,
+    vault::Vault,
+};
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl FromStr for Severity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "warning" => Ok(Severity::Warning),
+            "error" => Ok(Severity::Error),
+            _ => Err(format!(
+                "unknown severity `{s}`; expected `warning` or `error`"
+            )),
+        }
+    }
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "{}", "warning".yellow()),
+            Severity::Error => write!(f, "{}", "error".bright_red()),
+        }
+    }
+}
+
+/// The checks a note can be put through
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "kebab-case")]
+pub enum Check {
+    BrokenLink,
+    BrokenAnchor,
+    MissingTitle,
+    UnpublishedLink,
+    AbsoluteLink,
+    ForbiddenDomain,
+    MissingKey,
+    WrongType,
+    DuplicateTitle,
+    Unreadable,
+    NonCanonicalLink,
+    UnusedAttachment,
+    InvalidBoost,
+}
+
+impl Check {
+    const ALL: [Check; 13] = [
+        Check::BrokenLink,
+        Check::BrokenAnchor,
+        Check::MissingTitle,
+        Check::UnpublishedLink,
+        Check::AbsoluteLink,
+        Check::ForbiddenDomain,
+        Check::MissingKey,
+        Check::WrongType,
+        Check::DuplicateTitle,
+        Check::Unreadable,
+        Check::NonCanonicalLink,
+        Check::UnusedAttachment,
+        Check::InvalidBoost,
+    ];
+}
+
+impl FromStr for Check {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Check::ALL
+            .into_iter()
+            .find(|check| check.to_string() == s)
+            .ok_or_else(|| format!("unknown check `{s}`"))
+    }
+}
+
+impl Display for Check {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let check = match self {
+            Check::BrokenLink => "broken-link",
+            Check::BrokenAnchor => "broken-anchor",
+            Check::MissingTitle => "missing-title",
+            Check::UnpublishedLink => "unpublished-link",
+            Check::AbsoluteLink => "absolute-link",
+            Check::ForbiddenDomain => "forbidden-domain",
+            Check::MissingKey => "missing-key",
+            Check::WrongType => "wrong-type",
+            Check::DuplicateTitle => "duplicate-title",
+            Check::Unreadable => "unreadable",
+            Check::NonCanonicalLink => "non-canonical-link",
+            Check::UnusedAttachment => "unused-attachment",
+            Check::InvalidBoost => "invalid-boost",
+        };
+        write!(f, "{check}")
+    }
+}
+
+/// A problem found in a note, or in another file of the vault
+#[derive(Debug, Clone, Serialize)]
+pub struct Issue {
+    pub path: PathBuf,
+    pub pos: Option<Pos>,
+    pub check: Check,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Issue {
+    /// Where the issue is, as `path:line:column`
+    pub fn location(&self) -> String {
+        let path = self.path.to_string_lossy().to_string();
+        match self.pos {
+            Some(pos) => format!("{path}:{pos}"),
+            None => path,
+        }
+    }
+}
+
+/// Find the links of a document which point to nothing: a note or file which doesn't exist, or
+/// a heading which isn't in the target note.
+pub fn broken_links(vault: &Vault, document: &Document) -> Vec<Issue> {
+    let base_path = document.path().dir();
+    document
+        .links()
+        .into_iter()
+        .filter(|link| !link.is_external())
+        .filter_map(|link| {
+            let issue = |check, message| Issue {
+                path: document.path().path(),
+                pos: Some(link.pos),
+                check,
+                severity: Severity::Error,
+                message,
+            };
+
+            let target = if link.target().is_empty() {
+                // A link to an anchor of the document itself
+                Some(document)
+            } else if let Some(path) = link.to_markdown_path(base_path.clone()) {
+                vault.get_document(&path)
+            } else {
+                // Links to attachments only need to point to an existing file
+                let decoded = percent_decode_str(link.target()).decode_utf8_lossy();
+                if base_path.join(decoded.as_ref()).exists() {
+                    return None;
+                }
+                return Some(issue(
+                    Check::BrokenLink,
+                    format!("`{}` does not exist", link.url),
+                ));
+            };
+
+            match (target, link.anchor()) {
+                (None, _) => Some(issue(
+                    Check::BrokenLink,
+                    format!("`{}` is not a note in the vault", link.url),
+                )),
+                (Some(target), Some(anchor))
+                    if !target.headings().iter().any(|h| h.slug() == anchor) =>
+                {
+                    Some(issue(
+                        Check::BrokenAnchor,
+                        format!("`{}` has no heading `#{anchor}`", link.target()),
+                    ))
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+pub fn missing_title(document: &Document) -> Option<Issue> {
+    document
+        .get_metadata(&"title".to_string())
+        .is_none()
+        .then(|| Issue {
+            path: document.path().path(),
+            pos: None,
+            check: Check::MissingTitle,
+            severity: Severity::Warning,
+            message: "the note has no `title` attribute".into(),
+        })
+}
+
+/// Find the links of a document pointing to notes which aren't in the `published` set
+pub fn unpublished_links(
+    vault: &Vault,
+    document: &Document,
+    published: &HashSet<MarkdownPath>,
+) -> Vec<Issue> {
+    let base_path = document.path().dir();
+    document
+        .links()
+        .into_iter()
+        .filter_map(|link| {
+            let target = link.to_markdown_path(base_path.clone())?;
+            // Links to notes outside of the vault are reported as broken instead
+            vault.get_document(&target)?;
+            (!published.contains(&target)).then(|| Issue {
+                path: document.path().path(),
+                pos: Some(link.pos),
+                check: Check::UnpublishedLink,
+                severity: Severity::Error,
+                message: format!("`{}` is not published", link.url),
+            })
+        })
+        .collect()
+}
+
+/// Find the links of a document which break the link policy of the vault
+pub fn link_policy(document: &Document, policy: &LinkPolicy) -> Vec<Issue> {
+    document
+        .links()
+        .into_iter()
+        .filter_map(|link| {
+            let issue = |check, message| Issue {
+                path: document.path().path(),
+                pos: Some(link.pos),
+                check,
+                severity: Severity::Error,
+                message,
+            };
+            match Url::parse(&link.url) {
+                Ok(url) if url.scheme() == "file" && policy.relative_only => Some(issue(
+                    Check::AbsoluteLink,
+                    format!("`{}` is an absolute path; use a relative link", link.url),
+                )),
+                Ok(url) => {
+                    let host = url.host_str()?;
+                    let allowed = policy.allowed_domains.as_ref()?;
+                    let is_allowed = allowed.iter().any(|domain| {
+                        host == domain
+                            || host
+                                .strip_suffix(domain.as_str())
+                                .is_some_and(|sub| sub.ends_with('.'))
+                    });
+                    (!is_allowed).then(|| {
+                        issue(
+                            Check::ForbiddenDomain,
+                            format!("`{host}` is not an allowed domain"),
+                        )
+                    })
+                }
+                Err(_) if policy.relative_only && link.target().starts_with('/') => Some(issue(
+                    Check::AbsoluteLink,
+                    format!("`{}` is an absolute path; use a relative link", link.url),
+                )),
+                Err(_) => None,
+            }
+        })
+        .collect()
+}
+
+/// Find the links of every given note which point to a note or file which doesn't exist, leaving
+/// out links to missing headings
+pub fn dangling_links(vault: &Vault, documents: &[&Document]) -> Vec<Issue> {
+    let mut issues: Vec<Issue> = documents
+        .into_par_iter()
+        .flat_map(|doc| broken_links(vault, doc))
+        .filter(|issue| issue.check == Check::BrokenLink)
+        .collect();
+    issues.sort_by(|a, b| (&a.path, a.pos).cmp(&(&b.path, b.pos)));
+    issues
+}
+
+/// Check every given note for broken links and links breaking the link policy of the vault
+pub fn verify_links(vault: &Vault, documents: &[&Document], policy: &LinkPolicy) -> Vec<Issue> {
+    let mut issues: Vec<Issue> = documents
+        .into_par_iter()
+        .flat_map(|doc| {
+            let mut issues = broken_links(vault, doc);
+            issues.extend(link_policy(doc, policy));
+            issues
+        })
+        .collect();
+    issues.sort_by(|a, b| (&a.path, a.pos).cmp(&(&b.path, b.pos)));
+    issues
+}
+
+/// Find the frontmatter keys of a document which are missing or of the wrong type, according to
+/// the schema of the vault
+pub fn schema(document: &Document, schema: &Schema) -> Vec<Issue> {
+    let issue = |pos, check, message| Issue {
+        path: document.path().path(),
+        pos,
+        check,
+        severity: Severity::Error,
+        message,
+    };
+    let missing = schema
+        .required
+        .iter()
+        .filter(|key| document.get_metadata(key).is_none())
+        .map(|key| {
+            issue(
+                None,
+                Check::MissingKey,
+                format!("the note has no `{key}` attribute"),
+            )
+        });
+    let wrong_type = schema.types.iter().filter_map(|(key, expected)| {
+        let value = document.get_metadata(key)?;
+        let actual = ValueType::of(value);
+        (actual != Some(*expected)).then(|| {
+            let actual = actual.map_or_else(|| "empty".to_string(), |ty| format!("a {ty}"));
+            issue(
+                document.metadata_pos(key).ok().flatten(),
+                Check::WrongType,
+                format!("`{key}` should be a {expected}, but is {actual}"),
+            )
+        })
+    });
+    missing.chain(wrong_type).collect()
+}
+
+/// Report a `boost` attribute which isn't a positive, finite number, and so is ignored
+pub fn boost(document: &Document) -> Option<Issue> {
+    let key = "boost".to_string();
+    let value = document.get_metadata(&key)?;
+    Document::parse_boost(value).is_none().then(|| Issue {
+        path: document.path().path(),
+        pos: document.metadata_pos(&key).ok().flatten(),
+        check: Check::InvalidBoost,
+        severity: Severity::Warning,
+        message: format!("`boost` should be a positive number, but is `{value}`"),
+    })
+}
+
+/// Check every given note against the schema of the vault
+pub fn verify_schema(documents: &[&Document], schema: &Schema) -> Vec<Issue> {
+    let mut issues: Vec<Issue> = documents
+        .into_par_iter()
+        .flat_map(|doc| self::schema(doc, schema))
+        .collect();
+    issues.sort_by(|a, b| (&a.path, a.pos).cmp(&(&b.path, b.pos)));
+    issues
+}
+
+/// Validate the notes which are about to be published, i.e. the given `documents`, which are
+/// expected to only link to each other. They go through the checks of `n doctor`, with the
+/// severities configured for it, and must have a title.
+pub fn publish(vault: &Vault, documents: &[&Document]) -> Vec<Issue> {
+    let published: HashSet<MarkdownPath> = documents.iter().map(|doc| doc.path()).collect();
+    let mut issues: Vec<Issue> = documents
+        .into_par_iter()
+        .flat_map(|doc| {
+            let mut issues = note(vault, doc);
+            issues.extend(missing_title(doc));
+            issues.extend(unpublished_links(vault, doc, &published));
+            issues
+        })
+        .collect();
+    issues.extend(duplicate_titles(documents));
+    with_severities(issues, &vault.config().doctor)
+}
+
+/// Find the notes sharing their title with other notes, which can't be told apart when linked to
+/// by title
+pub fn duplicate_titles(documents: &[&Document]) -> Vec<Issue> {
+    let mut titles: BTreeMap<String, Vec<&Document>> = BTreeMap::new();
+    documents
+        .iter()
+        .for_each(|doc| titles.entry(doc.title()).or_default().push(doc));
+    titles
+        .into_iter()
+        .filter(|(_, notes)| notes.len() > 1)
+        .flat_map(|(title, notes)| {
+            let paths: Vec<PathBuf> = notes.iter().map(|doc| doc.path().path()).collect();
+            paths.clone().into_iter().map(move |path| {
+                let others: Vec<String> = paths
+                    .iter()
+                    .filter(|other| **other != path)
+                    .map(|other| format!("`{}`", other.to_string_lossy()))
+                    .collect();
+                Issue {
+                    path,
+                    pos: None,
+                    check: Check::DuplicateTitle,
+                    severity: Severity::Warning,
+                    message: format!("`{title}` is also the title of {}", others.join(", ")),
+                }
+            })
+        })
+        .collect()
+}
+
+/// Find the links of a document which `n fmt` would rewrite into a shorter form
+pub fn non_canonical_links(vault: &Vault, document: &Document) -> Vec<Issue> {
+    let Ok(contents) = document.contents() else {
+        return Vec::new();
+    };
+    fmt::non_canonical_links(vault, document, &contents)
+        .into_iter()
+        .map(|(link, edit)| Issue {
+            path: document.path().path(),
+            pos: Some(link.pos),
+            check: Check::NonCanonicalLink,
+            severity: Severity::Warning,
+            message: format!(
+                "`{}` can be written `{}`; run `n fmt`",
+                link.target(),
+                edit.replacement
+            ),
+        })
+        .collect()
+}
+
+/// Find the files of the vault which couldn't be read as notes, or which no note links to or
+/// embeds, drafts included, given every file of the vault
+pub fn stray_files(vault: &Vault, files: &[PathBuf]) -> Vec<Issue> {
+    let canonical = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let (notes, attachments): (Vec<&PathBuf>, Vec<&PathBuf>) = files
+        .iter()
+        .partition(|path| path.extension().is_some_and(|ext| ext == "md"));
+
+    let mut issues = Vec::new();
+    let mut referenced = BTreeSet::new();
+    for path in notes {
+        let document = MarkdownPath::new(vault.path(), path.clone())
+            .ok()
+            .and_then(|path| vault.get_document(&path));
+        let Some(document) = document else {
+            // The vault leaves out the notes it can't parse, so parse them again for the reason
+            let reason = Document::new(vault.path(), path.clone()).err().map_or_else(
+                || "it is not part of the vault".to_string(),
+                |e| e.to_string(),
+            );
+            issues.push(Issue {
+                path: path.clone(),
+                pos: None,
+                check: Check::Unreadable,
+                severity: Severity::Error,
+                message: format!("the note cannot be read: {reason}"),
+            });
+            continue;
+        };
+        let base_path = document.path().dir();
+        referenced.extend(
+            document
+                .references()
+                .unwrap_or_default()
+                .iter()
+                .filter(|link| !link.is_external() && !link.target().is_empty())
+                .map(|link| {
+                    let decoded = percent_decode_str(link.target()).decode_utf8_lossy();
+                    canonical(&base_path.join(decoded.as_ref()))
+                }),
+        );
+    }
+
+    issues.extend(
+        attachments
+            .into_iter()
+            .filter(|path| !referenced.contains(&canonical(path)))
+            .map(|path| Issue {
+                path: path.clone(),
+                pos: None,
+                check: Check::UnusedAttachment,
+                severity: Severity::Warning,
+                message: "no note links to or embeds the file".into(),
+            }),
+    );
+    issues
+}
+
+/// Run the checks of a single note: its links, against the vault and its link policy, and its
+/// frontmatter, against the schema of the vault
+pub fn note(vault: &Vault, document: &Document) -> Vec<Issue> {
+    let config = vault.config();
+    let mut issues = broken_links(vault, document);
+    issues.extend(link_policy(document, &config.links));
+    issues.extend(non_canonical_links(vault, document));
+    issues.extend(schema(document, &config.schema));
+    issues.extend(boost(document));
+    issues
+}
+
+/// Override the severity of the issues of some checks, where `None` drops the issues of a check
+/// altogether, and sort the issues by location
+fn with_severities(
+    issues: Vec<Issue>,
+    severities: &BTreeMap<Check, Option<Severity>>,
+) -> Vec<Issue> {
+    let mut issues: Vec<Issue> = issues
+        .into_iter()
+        .filter_map(|mut issue| {
+            if let Some(&severity) = severities.get(&issue.check) {
+                issue.severity = severity?;
+            }
+            Some(issue)
+        })
+        .collect();
+    issues.sort_by(|a, b| (&a.path, a.pos).cmp(&(&b.path, b.pos)));
+    issues
+}
+
+/// Run every check of the vault over the given notes, and over the other files of the vault,
+/// with the severity of each check overridden by `severities`, where `None` turns a check off
+pub fn doctor(
+    vault: &Vault,
+    documents: &[&Document],
+    files: &[PathBuf],
+    severities: &BTreeMap<Check, Option<Severity>>,
+) -> Vec<Issue> {
+    let mut issues: Vec<Issue> = documents
+        .into_par_iter()
+        .flat_map(|doc| note(vault, doc))
+        .collect();
+    issues.extend(duplicate_titles(documents));
+    issues.extend(stray_files(vault, files));
+    with_severities(issues, severities)
+}
@@ -1,16 +1,224 @@
-use std::{fs, path::PathBuf};
+use std::{collections::VecDeque, fs, path::PathBuf, time::Duration};
 
-use crate::template::Template;
+use crate::{
+    date::Date,
+    graph::Colouring,
+    kind::{InvalidKind, Kind},
+    rank::RankMode,
+    related::Method,
+    template::Template,
+};
+
+/// Which part of a note `inspect` prints
+#[derive(Debug, Default)]
+pub enum View {
+    /// Its path, metadata, headings and links
+    #[default]
+    Summary,
+    /// Its raw body, without the frontmatter
+    Content,
+    /// Its plain text, as indexed by the search
+    Stripped,
+    /// The raw source of the section under the given heading
+    Section(String),
+}
+
+/// How lists of notes are printed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Table,
+    /// One path per line, for piping into other tools
+    Path,
+    /// One title per line
+    Title,
+    /// A Graphviz digraph, for `graph`
+    Dot,
+}
+
+/// How `word-frequency` groups notes to find their most specific terms
+#[derive(Debug, Clone, Copy)]
+pub enum Grouping {
+    Tag,
+    Folder,
+}
 
 #[derive(Debug)]
 pub enum Subcommand {
-    Inspect(Option<PathBuf>),
+    Inspect {
+        path: Option<PathBuf>,
+        view: View,
+    },
     Links(PathBuf),
     Backlinks(PathBuf),
     Query(String),
-    Search(String),
+    /// Evaluate several queries at once, as read from a file
+    QueryMany(Vec<String>),
+    Search {
+        query: String,
+        /// Search each section of the notes separately
+        sections: bool,
+    },
     List,
-    New { template: Template, path: String },
+    /// Print the cached title and aliases of every note, or the paths of the notes called the
+    /// given name
+    TitleIndex(Option<String>),
+    /// Dump the search index of the vault as JSON
+    IndexDump,
+    Status,
+    /// List the task list items of the notes matching a query, or of every note
+    Tasks {
+        query: Option<String>,
+        open: bool,
+        done: bool,
+    },
+    /// List the batches of files in the trash
+    TrashList,
+    /// Put back the files of a batch of the trash, the latest one by default
+    TrashRestore(Option<String>),
+    /// List the most recently modified notes
+    Recent {
+        limit: usize,
+    },
+    /// Print notes drawn at random, among those matching a query if one is given
+    Random {
+        query: Option<String>,
+        count: usize,
+    },
+    /// Print the path of the daily note of a date, today by default, creating it if needed
+    Journal(Option<Date>),
+    /// Report metrics about the notes and the links between them
+    Stats {
+        /// Append a snapshot of the metrics to the history of the vault
+        record: bool,
+        /// Show the recorded history instead of the current metrics
+        history: bool,
+    },
+    Templates,
+    BundleExport(PathBuf),
+    BundleImport(PathBuf),
+    WordFrequency {
+        /// List the top terms of each group instead of the whole vocabulary
+        by: Option<Grouping>,
+    },
+    New {
+        template: Template,
+        path: String,
+    },
+    /// Move a note into a folder, or to a new path, rewriting its links and the links to it
+    Move {
+        path: PathBuf,
+        to: PathBuf,
+        dry_run: bool,
+    },
+    /// Rename a note after a new title, rewriting the links to it
+    Rename {
+        path: PathBuf,
+        title: String,
+        /// Only print the planned edits
+        dry_run: bool,
+    },
+    RenameHeading {
+        path: PathBuf,
+        old: String,
+        new: String,
+        dry_run: bool,
+    },
+    /// List the tags of the vault with the number of notes carrying them, or the notes carrying
+    /// the given tag or one of its nested tags. With `fix`, rewrite the tags of every note into
+    /// their canonical form instead.
+    Tags {
+        name: Option<String>,
+        fix: bool,
+    },
+    RenameTag {
+        old: String,
+        new: String,
+        merge: bool,
+    },
+    /// Normalise the given note, or every note. With `check`, only report which notes would
+    /// change.
+    Fmt {
+        path: Option<PathBuf>,
+        check: bool,
+    },
+    /// Append or refresh a `## Backlinks` section in the given note, or in every note
+    InsertBacklinks(Option<PathBuf>),
+    /// Print the outline of the headings of the given note. With `insert`, write it as a table of
+    /// contents under the `<!-- toc -->` line of the note instead.
+    Toc {
+        path: PathBuf,
+        insert: bool,
+    },
+    /// Go through the notes of the inbox one at a time, prompting for what to do with each
+    Inbox,
+    /// List the notes related to the given one through their links
+    Related {
+        path: PathBuf,
+        method: Method,
+    },
+    PublishCheck(String),
+    /// Check the frontmatter of the notes against the schema of the vault
+    SchemaCheck,
+    /// Run every check over the notes and the other files of the vault
+    Doctor,
+    /// List the links of the given note, or of every note, pointing to nothing
+    BrokenLinks(Option<PathBuf>),
+    VerifyLinks {
+        /// The only domains external links may point to, overriding the configuration
+        allowlist: Option<Vec<String>>,
+    },
+    /// Link the dates mentioned in a note, or in every note, to their daily note
+    LinkDates(Option<PathBuf>),
+    Tree {
+        /// Nest notes under their `parent` rather than only under their folder
+        hierarchy: bool,
+    },
+    /// Serve the vault over HTTP, along with a web viewer if `ui` is set
+    Serve {
+        host: String,
+        port: u16,
+        ui: bool,
+    },
+    /// List the notes without backlinks and without links to other notes. With `inbound` or
+    /// `outbound`, only the given direction counts.
+    Orphans {
+        inbound: bool,
+        outbound: bool,
+    },
+    /// Watch the vault for notes which change, running a command for each of them. With a query,
+    /// only the notes matching it count.
+    Watch {
+        interval: Duration,
+        exec: Option<String>,
+        query: Option<String>,
+    },
+    /// Print the link graph of the notes
+    Graph {
+        colour: Option<Colouring>,
+    },
+}
+
+impl Subcommand {
+    /// Whether the subcommand writes to the notes of the vault
+    pub fn mutates(&self) -> bool {
+        matches!(
+            self,
+            Subcommand::New { .. }
+                | Subcommand::RenameHeading { dry_run: false, .. }
+                | Subcommand::Rename { dry_run: false, .. }
+                | Subcommand::Move { dry_run: false, .. }
+                | Subcommand::RenameTag { .. }
+                | Subcommand::LinkDates(_)
+                | Subcommand::Inbox
+                | Subcommand::InsertBacklinks(_)
+                | Subcommand::Toc { insert: true, .. }
+                | Subcommand::Fmt { check: false, .. }
+                | Subcommand::Stats { record: true, .. }
+                | Subcommand::TrashRestore(_)
+                | Subcommand::Tags { fix: true, .. }
+        )
+    }
 }
 
 /// Parsed ommand-line arguments
@@ -20,6 +228,883 @@ pub struct Args {
     /// Whether to output the results as json
     pub json: bool,
     pub vault_dir: PathBuf,
+    /// Which kinds of notes to include in the results
+    pub kinds: Vec<Kind>,
+    /// Whether to include notes marked as `draft` or `private` in the results
+    pub include_drafts: bool,
+    pub format: Format,
+    /// Refuse to run subcommands which modify the vault, and don't write any cache to it
+    pub read_only: bool,
+    /// How notes are ranked when listing them and searching
+    pub rank_mode: RankMode,
+    /// Whether to report how long each phase of the subcommand took, on stderr
+    pub timings: bool,
+}
+
+/// An option as typed on the command line
+struct Opt {
+    long: &'static str,
+    short: Option<char>,
+    /// The placeholder of its value, or `None` for a flag
+    value: Option<&'static str>,
+    /// Its value when it isn't given, as shown in help
+    default: Option<&'static str>,
+    help: &'static str,
+}
+
+/// A subcommand as typed on the command line, with what it accepts. Help, usages and the
+/// validation of options are all derived from these.
+struct Spec {
+    name: &'static str,
+    aliases: &'static [&'static str],
+    /// Its positional arguments, as shown in its usage
+    arguments: &'static str,
+    /// What it does, in a line
+    about: &'static str,
+    /// The options only this subcommand accepts
+    options: &'static [Opt],
+    examples: &'static [&'static str],
+}
+
+const SUBCOMMANDS: &[Spec] = &[
+    Spec {
+        name: "inspect",
+        aliases: &[],
+        arguments: "[PATH]",
+        about: "Print a note, or the whole vault if no note is given",
+        options: &[
+            Opt {
+                long: "content",
+                short: None,
+                value: None,
+                default: None,
+                help: "Print the raw body, without the frontmatter",
+            },
+            Opt {
+                long: "stripped",
+                short: None,
+                value: None,
+                default: None,
+                help: "Print the plain text, as indexed by the search",
+            },
+            Opt {
+                long: "section",
+                short: None,
+                value: Some("HEADING"),
+                default: None,
+                help: "Print the raw source of the section under the heading",
+            },
+        ],
+        examples: &[
+            "n inspect notes/rust.md --section=Details",
+            "n --json inspect",
+        ],
+    },
+    Spec {
+        name: "links",
+        aliases: &[],
+        arguments: "PATH",
+        about: "List the links of a note",
+        options: &[],
+        examples: &["n links notes/rust.md"],
+    },
+    Spec {
+        name: "backlinks",
+        aliases: &[],
+        arguments: "PATH",
+        about: "List the notes linking to a note",
+        options: &[],
+        examples: &["n backlinks notes/rust.md"],
+    },
+    Spec {
+        name: "query",
+        aliases: &[],
+        arguments: "QUERY",
+        about: "List the notes matching a query",
+        options: &[Opt {
+            long: "queries-file",
+            short: None,
+            value: Some("FILE"),
+            default: None,
+            help: "Evaluate the queries of a file, one per line, in a single pass instead of QUERY",
+        }],
+        examples: &[
+            "n query '(contains tags rust)'",
+            "n query '(and (orphan) (not (leaf)))'",
+            "n query '(and (orphan) (before modified 2023-01-01))'",
+            "n --json query --queries-file=dashboard.txt",
+        ],
+    },
+    Spec {
+        name: "search",
+        aliases: &[],
+        arguments: "QUERY",
+        about: "Search the notes, ranked by relevance and by their links",
+        options: &[Opt {
+            long: "sections",
+            short: None,
+            value: None,
+            default: None,
+            help: "Search each section of the notes separately",
+        }],
+        examples: &["n search 'borrow checker'", "n search lifetimes --sections"],
+    },
+    Spec {
+        name: "list",
+        aliases: &["ls"],
+        arguments: "",
+        about: "List the notes, the most linked to first",
+        options: &[],
+        examples: &["n list --format=path"],
+    },
+    Spec {
+        name: "new",
+        aliases: &[],
+        arguments: "PATH",
+        about: "Create a note from a template",
+        options: &[
+            Opt {
+                long: "template-file",
+                short: Some('t'),
+                value: Some("FILE"),
+                default: None,
+                help: "The template to fill in, which is required",
+            },
+            Opt {
+                long: "variables",
+                short: Some('v'),
+                value: Some("KEY:VALUE,..."),
+                default: None,
+                help: "The values of the variables of the template",
+            },
+        ],
+        examples: &["n new -t templates/daily.md -v title:Today,date:2024-05-01 daily/2024-05-01"],
+    },
+    Spec {
+        name: "title-index",
+        aliases: &[],
+        arguments: "[NAME]",
+        about: "Print the cached title and aliases of every note, or the notes called NAME",
+        options: &[],
+        examples: &[
+            "n title-index",
+            "$EDITOR \"$(n title-index 'Weekly review')\"",
+        ],
+    },
+    Spec {
+        name: "index",
+        aliases: &[],
+        arguments: "dump",
+        about: "Dump the search index of the vault as JSON",
+        options: &[],
+        examples: &["n index dump | jq .df"],
+    },
+    Spec {
+        name: "mv",
+        aliases: &["move"],
+        arguments: "PATH FOLDER/|NEW-PATH",
+        about: "Move a note, rewriting its links and the links to it",
+        options: &[Opt {
+            long: "dry-run",
+            short: None,
+            value: None,
+            default: None,
+            help: "Only print the planned edits",
+        }],
+        examples: &["n mv inbox/idea.md projects/"],
+    },
+    Spec {
+        name: "rename",
+        aliases: &[],
+        arguments: "PATH TITLE",
+        about: "Rename a note after a new title, rewriting the links to it",
+        options: &[Opt {
+            long: "dry-run",
+            short: None,
+            value: None,
+            default: None,
+            help: "Only print the planned edits",
+        }],
+        examples: &["n rename notes/rust.md 'Rust language' --dry-run"],
+    },
+    Spec {
+        name: "rename-heading",
+        aliases: &[],
+        arguments: "PATH OLD NEW",
+        about: "Rename a heading of a note, and the links to it",
+        options: &[Opt {
+            long: "dry-run",
+            short: None,
+            value: None,
+            default: None,
+            help: "Only print the planned edits",
+        }],
+        examples: &["n rename-heading notes/rust.md Details 'More details'"],
+    },
+    Spec {
+        name: "tag",
+        aliases: &[],
+        arguments: "rename|merge OLD NEW",
+        about: "Rename a tag in every note, or merge it into another one",
+        options: &[],
+        examples: &["n tag rename rust rustlang", "n tag merge todo tasks"],
+    },
+    Spec {
+        name: "tags",
+        aliases: &[],
+        arguments: "[TAG]",
+        about: "List the tags with their number of notes, or the notes carrying a tag",
+        options: &[Opt {
+            long: "fix",
+            short: None,
+            value: None,
+            default: None,
+            help: "Rewrite the tags of every note into their canonical form, as configured",
+        }],
+        examples: &["n tags", "n tags project/n", "n tags --fix"],
+    },
+    Spec {
+        name: "journal",
+        aliases: &[],
+        arguments: "",
+        about: "Print the path of a daily note, creating it from the daily template if needed",
+        options: &[Opt {
+            long: "date",
+            short: None,
+            value: Some("YYYY-MM-DD"),
+            default: Some("today, in UTC"),
+            help: "The day of the note",
+        }],
+        examples: &["$EDITOR \"$(n journal)\"", "n journal --date=2024-05-01"],
+    },
+    Spec {
+        name: "random",
+        aliases: &[],
+        arguments: "",
+        about: "Print notes drawn at random, for a serendipitous review",
+        options: &[
+            Opt {
+                long: "query",
+                short: None,
+                value: Some("QUERY"),
+                default: None,
+                help: "Only draw among the notes matching the query",
+            },
+            Opt {
+                long: "count",
+                short: None,
+                value: Some("N"),
+                default: Some("1"),
+                help: "How many distinct notes to draw",
+            },
+        ],
+        examples: &["n random --query '(contains tags review)' --count=3"],
+    },
+    Spec {
+        name: "recent",
+        aliases: &[],
+        arguments: "",
+        about: "List the most recently modified notes, by their `modified` attribute or their file",
+        options: &[Opt {
+            long: "limit",
+            short: None,
+            value: Some("N"),
+            default: Some("10"),
+            help: "How many notes to list",
+        }],
+        examples: &["n recent --limit=20", "n recent -f path | fzf"],
+    },
+    Spec {
+        name: "related",
+        aliases: &[],
+        arguments: "PATH",
+        about: "List the notes related to a note through their links",
+        options: &[Opt {
+            long: "method",
+            short: None,
+            value: Some("METHOD"),
+            default: Some("cocitation"),
+            help: "cocitation: linked to by the same notes, coupling: linking to the same notes",
+        }],
+        examples: &["n related notes/rust.md --method=coupling"],
+    },
+    Spec {
+        name: "inbox",
+        aliases: &[],
+        arguments: "",
+        about: "Triage the notes of the inbox one at a time",
+        options: &[],
+        examples: &[],
+    },
+    Spec {
+        name: "fmt",
+        aliases: &[],
+        arguments: "[PATH]",
+        about: "Normalise the frontmatter, blank lines and links of notes",
+        options: &[Opt {
+            long: "check",
+            short: None,
+            value: None,
+            default: None,
+            help: "Only report which notes would change",
+        }],
+        examples: &["n fmt --check"],
+    },
+    Spec {
+        name: "insert-backlinks",
+        aliases: &[],
+        arguments: "[PATH]",
+        about: "Add or refresh a Backlinks section in notes",
+        options: &[],
+        examples: &[],
+    },
+    Spec {
+        name: "doctor",
+        aliases: &[],
+        arguments: "",
+        about: "Check the links, titles and frontmatter of the notes, and the other files of the vault",
+        options: &[],
+        examples: &["n doctor", "n doctor --json"],
+    },
+    Spec {
+        name: "toc",
+        aliases: &[],
+        arguments: "PATH",
+        about: "Print the outline of the headings of a note",
+        options: &[Opt {
+            long: "insert",
+            short: None,
+            value: None,
+            default: None,
+            help: "Write a table of contents under the `<!-- toc -->` line of the note",
+        }],
+        examples: &["n toc notes/rust.md", "n toc --insert notes/rust.md"],
+    },
+    Spec {
+        name: "publish-check",
+        aliases: &[],
+        arguments: "[QUERY]",
+        about: "Check the notes matching the query, `(contains publish true)` by default, before publishing them",
+        options: &[],
+        examples: &[],
+    },
+    Spec {
+        name: "schema-check",
+        aliases: &[],
+        arguments: "",
+        about: "Check the frontmatter of the notes against the schema of the vault",
+        options: &[],
+        examples: &[],
+    },
+    Spec {
+        name: "tree",
+        aliases: &[],
+        arguments: "",
+        about: "Print the notes as a tree of folders",
+        options: &[Opt {
+            long: "hierarchy",
+            short: None,
+            value: None,
+            default: None,
+            help: "Nest notes under their `parent` as well",
+        }],
+        examples: &["n tree --hierarchy"],
+    },
+    Spec {
+        name: "orphans",
+        aliases: &[],
+        arguments: "",
+        about: "List the notes without backlinks and without links to other notes",
+        options: &[
+            Opt {
+                long: "no-inbound",
+                short: None,
+                value: None,
+                default: None,
+                help: "Only list the notes without backlinks",
+            },
+            Opt {
+                long: "no-outbound",
+                short: None,
+                value: None,
+                default: None,
+                help: "Only list the notes without links to other notes",
+            },
+        ],
+        examples: &["n orphans --no-inbound"],
+    },
+    Spec {
+        name: "graph",
+        aliases: &[],
+        arguments: "",
+        about: "Print the link graph of the notes as a Graphviz digraph",
+        options: &[Opt {
+            long: "colour",
+            short: None,
+            value: Some("rank|tag"),
+            default: None,
+            help: "Colour the notes by rank or by their first tag",
+        }],
+        examples: &["n graph --colour=rank | dot -Tsvg > vault.svg"],
+    },
+    Spec {
+        name: "serve",
+        aliases: &[],
+        arguments: "",
+        about: "Serve the vault over HTTP as a JSON API",
+        options: &[
+            Opt {
+                long: "host",
+                short: None,
+                value: Some("HOST"),
+                default: Some("127.0.0.1"),
+                help: "The address to listen on",
+            },
+            Opt {
+                long: "port",
+                short: None,
+                value: Some("PORT"),
+                default: Some("8080"),
+                help: "The port to listen on",
+            },
+            Opt {
+                long: "ui",
+                short: None,
+                value: None,
+                default: None,
+                help: "Serve a web viewer at `/` as well",
+            },
+        ],
+        examples: &["n serve --ui --host=0.0.0.0"],
+    },
+    Spec {
+        name: "watch",
+        aliases: &[],
+        arguments: "",
+        about: "Watch the notes, running a command for each of them which changes",
+        options: &[
+            Opt {
+                long: "exec",
+                short: None,
+                value: Some("COMMAND"),
+                default: None,
+                help: "The command to run, with `{}` replaced by the path of the note",
+            },
+            Opt {
+                long: "interval",
+                short: None,
+                value: Some("SECONDS"),
+                default: Some("1"),
+                help: "How often to check for changes",
+            },
+            Opt {
+                long: "query",
+                short: None,
+                value: Some("QUERY"),
+                default: None,
+                help: "Only follow the notes matching the query, printing them all whenever notes change unless there is a command",
+            },
+        ],
+        examples: &[
+            "n watch --exec 'pandoc {} -o {}.html'",
+            "n watch --query '(contains tags todo)'",
+            "n watch --query '(contains tags publish)' --exec 'pandoc {} -o {}.html'",
+        ],
+    },
+    Spec {
+        name: "status",
+        aliases: &[],
+        arguments: "",
+        about: "Report statistics about the vault and the health of its index",
+        options: &[],
+        examples: &[],
+    },
+    Spec {
+        name: "stats",
+        aliases: &[],
+        arguments: "",
+        about: "Report word counts, links, orphans and tags across the notes",
+        options: &[
+            Opt {
+                long: "record",
+                short: None,
+                value: None,
+                default: None,
+                help: "Append the number of notes, words and links to `.n/stats.ndjson`",
+            },
+            Opt {
+                long: "history",
+                short: None,
+                value: None,
+                default: None,
+                help: "Show how the vault grew across the recorded snapshots",
+            },
+        ],
+        examples: &[
+            "n stats --kind=all",
+            "n stats --record",
+            "n stats --history",
+        ],
+    },
+    Spec {
+        name: "tasks",
+        aliases: &[],
+        arguments: "",
+        about: "List the `- [ ]` tasks of the notes, with their note, line and heading",
+        options: &[
+            Opt {
+                long: "open",
+                short: None,
+                value: None,
+                default: None,
+                help: "List the open tasks, which is the default unless --done is given",
+            },
+            Opt {
+                long: "done",
+                short: None,
+                value: None,
+                default: None,
+                help: "List the tasks which are done",
+            },
+            Opt {
+                long: "query",
+                short: None,
+                value: Some("QUERY"),
+                default: None,
+                help: "Only list the tasks of the notes matching the query",
+            },
+        ],
+        examples: &[
+            "n tasks",
+            "n tasks --open --done --query '(contains tags project)'",
+            "n tasks -f path | vim -q /dev/stdin",
+        ],
+    },
+    Spec {
+        name: "trash",
+        aliases: &[],
+        arguments: "[list|restore [ID]]",
+        about: "List the files edited or moved by commands, or put a batch of them back",
+        options: &[],
+        examples: &[
+            "n trash",
+            "n trash restore",
+            "n trash restore 1714550400000",
+        ],
+    },
+    Spec {
+        name: "templates",
+        aliases: &[],
+        arguments: "",
+        about: "List the templates with the variables they use",
+        options: &[],
+        examples: &[],
+    },
+    Spec {
+        name: "word-frequency",
+        aliases: &[],
+        arguments: "",
+        about: "List the most common terms, or the most specific terms of each group",
+        options: &[Opt {
+            long: "by",
+            short: None,
+            value: Some("tag|folder"),
+            default: None,
+            help: "Group the notes by tag or by folder",
+        }],
+        examples: &["n word-frequency --by=tag"],
+    },
+    Spec {
+        name: "bundle",
+        aliases: &[],
+        arguments: "export|import FILE",
+        about: "Export the visible notes as a gzipped JSON bundle, or read one back",
+        options: &[],
+        examples: &["n bundle export vault.json.gz"],
+    },
+    Spec {
+        name: "link-dates",
+        aliases: &[],
+        arguments: "[PATH]",
+        about: "Link the dates mentioned in notes to their daily note",
+        options: &[],
+        examples: &[],
+    },
+    Spec {
+        name: "broken-links",
+        aliases: &[],
+        arguments: "[PATH]",
+        about: "List the links pointing to notes or files which don't exist",
+        options: &[],
+        examples: &[],
+    },
+    Spec {
+        name: "verify-links",
+        aliases: &[],
+        arguments: "",
+        about: "Check the links of every note against the link policy of the vault",
+        options: &[Opt {
+            long: "external-allowlist",
+            short: None,
+            value: Some("DOMAIN,..."),
+            default: None,
+            help: "The only domains external links may point to, overriding the configuration",
+        }],
+        examples: &["n verify-links --external-allowlist=example.com"],
+    },
+];
+
+/// The options every subcommand accepts
+const GLOBAL_OPTIONS: &[Opt] = &[
+    Opt {
+        long: "json",
+        short: Some('j'),
+        value: None,
+        default: None,
+        help: "Print the results as JSON",
+    },
+    Opt {
+        long: "vault-dir",
+        short: Some('d'),
+        value: Some("DIR"),
+        default: Some("."),
+        help: "The root of the vault",
+    },
+    Opt {
+        long: "kind",
+        short: Some('k'),
+        value: Some("KIND,..."),
+        default: Some("regular"),
+        help: "Which kinds of notes to include, or `all`",
+    },
+    Opt {
+        long: "include-drafts",
+        short: None,
+        value: None,
+        default: None,
+        help: "Include the notes marked as `draft` or `private`",
+    },
+    Opt {
+        long: "format",
+        short: Some('f'),
+        value: Some("FORMAT"),
+        default: Some("table"),
+        help: "How lists of notes are printed: table, path, title, or dot for `graph`",
+    },
+    Opt {
+        long: "read-only",
+        short: None,
+        value: None,
+        default: None,
+        help: "Refuse to modify the vault or to write its caches",
+    },
+    Opt {
+        long: "timings",
+        short: None,
+        value: None,
+        default: None,
+        help: "Report how long each phase took, on stderr",
+    },
+    Opt {
+        long: "rank-mode",
+        short: None,
+        value: Some("MODE"),
+        default: Some("global"),
+        help: "global, or fresh to favour recently modified notes",
+    },
+    Opt {
+        long: "half-life",
+        short: None,
+        value: Some("DAYS"),
+        default: Some("30"),
+        help: "How many days it takes a note to lose half its freshness",
+    },
+    Opt {
+        long: "help",
+        short: Some('h'),
+        value: None,
+        default: None,
+        help: "Print this help, or the help of a subcommand",
+    },
+];
+
+impl Opt {
+    /// Whether the option is named `long` on the command line
+    fn is(&self, long: &str) -> bool {
+        self.long == long
+    }
+
+    /// How the option is written in a usage, e.g. `[-f|--format=FORMAT]`
+    fn usage(&self) -> String {
+        let short = self.short.map(|c| format!("-{c}|")).unwrap_or_default();
+        let value = self.value.map(|v| format!("={v}")).unwrap_or_default();
+        format!("[{short}--{}{value}]", self.long)
+    }
+
+    /// How the option is written in help, e.g. `-f, --format=FORMAT`
+    fn signature(&self) -> String {
+        let short = self.short.map_or("    ".to_string(), |c| format!("-{c}, "));
+        let value = self.value.map(|v| format!("={v}")).unwrap_or_default();
+        format!("{short}--{}{value}", self.long)
+    }
+
+    /// List options in help, aligning their descriptions
+    fn table(options: &[Opt]) -> String {
+        let width = options
+            .iter()
+            .map(|opt| opt.signature().len())
+            .max()
+            .unwrap_or(0);
+        options
+            .iter()
+            .map(|opt| {
+                let default = opt
+                    .default
+                    .map(|d| format!(" [default: {d}]"))
+                    .unwrap_or_default();
+                format!("  {:width$}  {}{default}\n", opt.signature(), opt.help)
+            })
+            .collect()
+    }
+}
+
+impl Spec {
+    fn find(name: &str) -> Option<&'static Spec> {
+        SUBCOMMANDS
+            .iter()
+            .find(|spec| spec.name == name || spec.aliases.contains(&name))
+    }
+
+    fn usage(&self) -> String {
+        let options: Vec<String> = self.options.iter().map(Opt::usage).collect();
+        format!("n {} {} {}", self.name, self.arguments, options.join(" "))
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn help(&self) -> String {
+        let mut help = format!("{}\n\nUsage: {}\n", self.about, self.usage());
+        if !self.aliases.is_empty() {
+            help += &format!("Aliases: {}\n", self.aliases.join(", "));
+        }
+        if !self.options.is_empty() {
+            help += &format!("\nOptions:\n{}", Opt::table(self.options));
+        }
+        if !self.examples.is_empty() {
+            help += "\nExamples:\n";
+            for example in self.examples {
+                help += &format!("  {example}\n");
+            }
+        }
+        help + "\nThe global options of `n help` are accepted as well."
+    }
+
+    /// The help of `n` itself, listing every subcommand
+    fn general_help() -> String {
+        let width = SUBCOMMANDS
+            .iter()
+            .map(|spec| spec.name.len())
+            .max()
+            .unwrap_or(0);
+        let subcommands: String = SUBCOMMANDS
+            .iter()
+            .map(|spec| format!("  {:width$}  {}\n", spec.name, spec.about))
+            .collect();
+        format!(
+            "Usage: n [OPTIONS] SUBCOMMAND [ARGS]\n\nSubcommands:\n{subcommands}\nOptions:\n{}\nRun `n help SUBCOMMAND` for the options and examples of a subcommand.",
+            Opt::table(GLOBAL_OPTIONS)
+        )
+    }
+}
+
+/// The help of the given subcommand, or of `n` itself
+fn help(target: Option<&str>) -> Result<String, lexopt::Error> {
+    match target {
+        None | Some("subcommands") => Ok(Spec::general_help()),
+        Some(name) => Ok(Spec::find(name)
+            .ok_or_else(|| unknown_subcommand(name))?
+            .help()),
+    }
+}
+
+fn unknown_subcommand(name: &str) -> lexopt::Error {
+    match suggest(name, SUBCOMMANDS.iter().map(|spec| spec.name)) {
+        Some(suggestion) => {
+            format!("unknown subcommand `{name}`; did you mean `{suggestion}`?").into()
+        }
+        None => format!("unknown subcommand `{name}`; run `n help` to list them").into(),
+    }
+}
+
+/// Check that the option with the given long name is accepted where it was given, i.e. that it is
+/// global or belongs to the subcommand given before it
+fn check_option(spec: Option<&Spec>, long: &str) -> Result<(), lexopt::Error> {
+    let all_options = || {
+        GLOBAL_OPTIONS
+            .iter()
+            .chain(SUBCOMMANDS.iter().flat_map(|spec| spec.options))
+    };
+    let owned = SUBCOMMANDS
+        .iter()
+        .any(|spec| spec.options.iter().any(|opt| opt.is(long)));
+    match spec {
+        _ if GLOBAL_OPTIONS.iter().any(|opt| opt.is(long)) => Ok(()),
+        Some(spec) if spec.options.iter().any(|opt| opt.is(long)) => Ok(()),
+        None if owned => Err(format!("`--{long}` must come after its subcommand").into()),
+        Some(spec) if owned => Err(format!(
+            "`{}` has no option `--{long}`; usage: {}",
+            spec.name,
+            spec.usage()
+        )
+        .into()),
+        _ => {
+            let candidates: Vec<&str> = match spec {
+                Some(spec) => spec
+                    .options
+                    .iter()
+                    .chain(GLOBAL_OPTIONS)
+                    .map(|opt| opt.long)
+                    .collect(),
+                None => all_options().map(|opt| opt.long).collect(),
+            };
+            Err(match suggest(long, candidates) {
+                Some(suggestion) => {
+                    format!("unknown option `--{long}`; did you mean `--{suggestion}`?")
+                }
+                None => format!("unknown option `--{long}`; run `n help` to list the options"),
+            }
+            .into())
+        }
+    }
+}
+
+/// The candidate closest to a mistyped name, if it is close enough to be a likely typo
+fn suggest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    candidates
+        .into_iter()
+        .map(|candidate| (distance(name, candidate), candidate))
+        .filter(|&(distance, candidate)| distance <= (candidate.len() / 3).max(1))
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+/// The Levenshtein distance between two strings, counted in characters
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = (diagonal + usize::from(ca != cb))
+                .min(above + 1)
+                .min(row[j] + 1);
+            diagonal = above;
+        }
+    }
+    row[b.len()]
 }
 
 impl Args {
@@ -27,20 +1112,80 @@ impl Args {
     pub fn parse() -> Result<Args, lexopt::Error> {
         use lexopt::prelude::*;
 
-        let mut subcommand = None;
-        let mut argument = None;
+        let mut spec: Option<&Spec> = None;
+        let mut arguments = VecDeque::new();
         let mut parser = lexopt::Parser::from_env();
         let mut json = false;
         let mut vault_dir = std::env::current_dir().unwrap();
         let mut variables = None;
         let mut template_file = None;
+        let mut kinds = vec![Kind::Regular];
+        let mut hierarchy = false;
+        let mut sections = false;
+        let mut check = false;
+        let mut dry_run = false;
+        let mut record = false;
+        let mut date = None;
+        let mut query = None;
+        let mut queries_file = None;
+        let mut count = 1;
+        let mut limit = 10;
+        let mut open = false;
+        let mut fix = false;
+        let mut insert = false;
+        let mut done = false;
+        let mut history = false;
+        let mut read_only = false;
+        let mut allowlist = None;
+        let mut include_drafts = false;
+        let mut timings = false;
+        let mut fresh = false;
+        let mut half_life = 30.0;
+        let mut by = None;
+        let mut method = Method::default();
+        let mut colour = None;
+        let mut no_inbound = false;
+        let mut no_outbound = false;
+        let mut host = "127.0.0.1".to_string();
+        let mut port = 8080;
+        let mut ui = false;
+        let mut exec = None;
+        let mut interval = Duration::from_secs(1);
+        let mut format = Format::default();
+        let mut view = View::default();
         while let Some(arg) = parser.next()? {
+            // Options are checked against the specs before being parsed
+            let option = match arg {
+                Short(c) => Some(
+                    GLOBAL_OPTIONS
+                        .iter()
+                        .chain(SUBCOMMANDS.iter().flat_map(|spec| spec.options))
+                        .find(|opt| opt.short == Some(c))
+                        .ok_or_else(|| {
+                            format!("unknown option `-{c}`; run `n help` to list the options")
+                        })?
+                        .long,
+                ),
+                Long(name) => Some(name),
+                Value(_) => None,
+            };
+            if let Some(option) = option {
+                check_option(spec, option)?;
+            }
+
             match arg {
-                Value(val) if subcommand.is_none() => {
-                    subcommand = Some(val.clone().string()?);
+                Value(val) if spec.is_none() => {
+                    let name = val.string()?;
+                    if name == "help" {
+                        // `n help SUBCOMMAND` is `n --help SUBCOMMAND`
+                        let target = parser.value().ok().and_then(|val| val.string().ok());
+                        println!("{}", help(target.as_deref())?);
+                        std::process::exit(0);
+                    }
+                    spec = Some(Spec::find(&name).ok_or_else(|| unknown_subcommand(&name))?);
                 }
                 Value(val) => {
-                    argument = Some(val.string()?);
+                    arguments.push_back(val.string()?);
                 }
                 Short('j') | Long("json") => {
                     json = true;
@@ -52,53 +1197,363 @@ impl Args {
                 Short('v') | Long("variables") => {
                     variables = Some(parser.value()?.parse::<String>()?.to_string());
                 }
+                Long("content") => {
+                    view = View::Content;
+                }
+                Long("stripped") => {
+                    view = View::Stripped;
+                }
+                Long("section") => {
+                    view = View::Section(parser.value()?.parse::<String>()?);
+                }
+                Long("include-drafts") => {
+                    include_drafts = true;
+                }
+                Short('f') | Long("format") => {
+                    format = parser.value()?.parse_with(|format| match format {
+                        "table" => Ok(Format::Table),
+                        "path" => Ok(Format::Path),
+                        "title" => Ok(Format::Title),
+                        "dot" => Ok(Format::Dot),
+                        _ => Err(format!("unknown format `{format}`")),
+                    })?;
+                }
+                Long("by") => {
+                    by = Some(parser.value()?.parse_with(|by| match by {
+                        "tag" => Ok(Grouping::Tag),
+                        "folder" => Ok(Grouping::Folder),
+                        _ => Err(format!("cannot group by `{by}`")),
+                    })?);
+                }
+                Long("method") => {
+                    method = parser.value()?.parse_with(|method| match method {
+                        "cocitation" => Ok(Method::Cocitation),
+                        "coupling" => Ok(Method::Coupling),
+                        _ => Err(format!("unknown method `{method}`")),
+                    })?;
+                }
+                Long("colour") => {
+                    colour = Some(parser.value()?.parse_with(|colour| match colour {
+                        "rank" => Ok(Colouring::Rank),
+                        "tag" => Ok(Colouring::Tag),
+                        _ => Err(format!("cannot colour by `{colour}`")),
+                    })?);
+                }
+                Long("host") => {
+                    host = parser.value()?.parse::<String>()?;
+                }
+                Long("port") => {
+                    port = parser.value()?.parse()?;
+                }
+                Long("no-inbound") => {
+                    no_inbound = true;
+                }
+                Long("no-outbound") => {
+                    no_outbound = true;
+                }
+                Long("exec") => {
+                    exec = Some(parser.value()?.parse::<String>()?);
+                }
+                Long("interval") => {
+                    interval = parser.value()?.parse_with(|seconds: &str| {
+                        match seconds.parse::<f64>() {
+                            Ok(seconds) if seconds > 0.0 => Ok(Duration::from_secs_f64(seconds)),
+                            _ => Err(format!("`{seconds}` is not a positive number of seconds")),
+                        }
+                    })?;
+                }
+                Long("ui") => {
+                    ui = true;
+                }
+                Long("external-allowlist") => {
+                    let domains = parser.value()?.parse::<String>()?;
+                    allowlist = Some(domains.split(',').map(|d| d.trim().to_string()).collect());
+                }
+                Long("rank-mode") => {
+                    fresh = parser.value()?.parse_with(|mode| match mode {
+                        "global" => Ok(false),
+                        "fresh" => Ok(true),
+                        _ => Err(format!("unknown rank mode `{mode}`")),
+                    })?;
+                }
+                Long("half-life") => {
+                    half_life = parser
+                        .value()?
+                        .parse_with(|days: &str| match days.parse() {
+                            Ok(days) if days > 0.0 => Ok(days),
+                            _ => Err(format!("`{days}` is not a positive number of days")),
+                        })?;
+                }
+                Long("timings") => {
+                    timings = true;
+                }
+                Long("read-only") => {
+                    read_only = true;
+                }
+                Long("queries-file") => {
+                    queries_file = Some(parser.value()?.string()?);
+                }
+                Long("query") => {
+                    query = Some(parser.value()?.string()?);
+                }
+                Long("count") => {
+                    count = parser
+                        .value()?
+                        .parse_with(|count: &str| match count.parse() {
+                            Ok(count) if count > 0 => Ok(count),
+                            _ => Err(format!("`{count}` is not a positive number of notes")),
+                        })?;
+                }
+                Long("insert") => {
+                    insert = true;
+                }
+                Long("fix") => {
+                    fix = true;
+                }
+                Long("open") => {
+                    open = true;
+                }
+                Long("done") => {
+                    done = true;
+                }
+                Long("limit") => {
+                    limit = parser
+                        .value()?
+                        .parse_with(|limit: &str| match limit.parse() {
+                            Ok(limit) if limit > 0 => Ok(limit),
+                            _ => Err(format!("`{limit}` is not a positive number of notes")),
+                        })?;
+                }
+                Long("date") => {
+                    date = Some(parser.value()?.parse()?);
+                }
+                Long("record") => {
+                    record = true;
+                }
+                Long("history") => {
+                    history = true;
+                }
+                Long("dry-run") => {
+                    dry_run = true;
+                }
+                Long("check") => {
+                    check = true;
+                }
+                Long("sections") => {
+                    sections = true;
+                }
+                Long("hierarchy") => {
+                    hierarchy = true;
+                }
+                Short('k') | Long("kind") => {
+                    kinds = parser.value()?.parse_with(Self::parse_kinds)?;
+                }
                 Short('t') | Long("template-file") => {
                     template_file = Some(parser.value()?.parse::<String>()?.to_string());
                 }
                 Short('h') | Long("help") => {
-                    let target: Option<String> = parser
-                        .value()
-                        .ok()
-                        .map_or_else(|| None, |res| res.parse::<String>().ok());
-                    let help_text = match target {
-                        Some(val) if val == "subcommands" => {
-                            "Available subcommmands are: inspect, links, backlinks"
-                        }
-                        _ => {
-                            "Usage: n [-j|--json] [-d|--vault-dir=DIR] SUBCOMMAND PATH\n\nTo see the available subcommands, run zk --help subcommands."
-                        }
+                    // `n SUBCOMMAND --help` and `n --help SUBCOMMAND` both show its usage
+                    let target = match spec {
+                        Some(spec) => Some(spec.name.to_string()),
+                        None => parser
+                            .value()
+                            .ok()
+                            .and_then(|res| res.parse::<String>().ok()),
                     };
-                    println!("{help_text}");
+                    println!("{}", help(target.as_deref())?);
                     std::process::exit(0);
                 }
                 _ => return Err(arg.unexpected()),
             }
         }
-        let subcommand = match subcommand.ok_or("missing subcommand")? {
-            val if val == "inspect" => {
-                Subcommand::Inspect(argument.map_or_else(|| None, |val| Some(PathBuf::from(val))))
-            }
-            val if val == "query" => Subcommand::Query(argument.ok_or("missing argument")?),
-            val if val == "search" => Subcommand::Search(argument.ok_or("missing argument")?),
-            val if (val == "list") || (val == "ls") => Subcommand::List,
-            val if val == "backlinks" => {
-                Subcommand::Backlinks(argument.ok_or("missing argument")?.into())
+
+        let spec = spec.ok_or("missing subcommand; run `n help` to list them")?;
+        let missing = |name: &str| -> lexopt::Error {
+            format!("missing {name}; usage: {}", spec.usage()).into()
+        };
+        let mut argument = || arguments.pop_front();
+        let subcommand = match spec.name {
+            "inspect" => Subcommand::Inspect {
+                path: argument().map(PathBuf::from),
+                view,
+            },
+            "query" => match queries_file {
+                Some(file) => {
+                    let queries = fs::read_to_string(&file)
+                        .map_err(|e| format!("failed to read the queries `{file}` because {e}"))?;
+                    // Blank lines and comments let the queries be grouped and explained
+                    let queries = queries
+                        .lines()
+                        .map(str::trim)
+                        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                        .map(str::to_string)
+                        .collect();
+                    Subcommand::QueryMany(queries)
+                }
+                None => Subcommand::Query(argument().ok_or_else(|| missing("QUERY"))?),
+            },
+            "search" => Subcommand::Search {
+                query: argument().ok_or_else(|| missing("QUERY"))?,
+                sections,
+            },
+            "list" => Subcommand::List,
+            "title-index" => Subcommand::TitleIndex(argument()),
+            "index" => match argument().ok_or_else(|| missing("dump"))?.as_str() {
+                "dump" => Subcommand::IndexDump,
+                action => return Err(format!("unknown index action `{action}`").into()),
+            },
+            "status" => Subcommand::Status,
+            "stats" => Subcommand::Stats { record, history },
+            "journal" => Subcommand::Journal(date),
+            "random" => Subcommand::Random { query, count },
+            "recent" => Subcommand::Recent { limit },
+            // Open tasks are listed unless asked otherwise
+            "tasks" => Subcommand::Tasks {
+                query,
+                open: open || !done,
+                done,
+            },
+            "trash" => match argument().as_deref() {
+                None | Some("list") => Subcommand::TrashList,
+                Some("restore") => Subcommand::TrashRestore(argument()),
+                Some(action) => return Err(format!("unknown trash action `{action}`").into()),
+            },
+            "templates" => Subcommand::Templates,
+            "bundle" => {
+                let action = argument().ok_or_else(|| missing("export or import"))?;
+                let path = PathBuf::from(argument().ok_or_else(|| missing("FILE"))?);
+                match action.as_str() {
+                    "export" => Subcommand::BundleExport(path),
+                    "import" => Subcommand::BundleImport(path),
+                    action => return Err(format!("unknown bundle action `{action}`").into()),
+                }
             }
-            val if val == "links" => Subcommand::Links(argument.ok_or("missing argument")?.into()),
-            val if val == "new" => {
-                let template =
-                    fs::read_to_string::<String>(template_file.ok_or("missing argument")?).unwrap();
-                let path = argument.ok_or("missing argument")?.into();
+            "word-frequency" => Subcommand::WordFrequency { by },
+            "backlinks" => Subcommand::Backlinks(argument().ok_or_else(|| missing("PATH"))?.into()),
+            "links" => Subcommand::Links(argument().ok_or_else(|| missing("PATH"))?.into()),
+            "new" => {
+                let file = template_file.ok_or_else(|| missing("--template-file"))?;
+                let template = fs::read_to_string(&file)
+                    .map_err(|e| format!("failed to read the template `{file}` because {e}"))?;
+                let path = argument().ok_or_else(|| missing("PATH"))?;
                 let template = Template::new(template, variables);
                 Subcommand::New { template, path }
             }
-            _ => todo!(),
+            "mv" => Subcommand::Move {
+                path: argument().ok_or_else(|| missing("PATH"))?.into(),
+                to: argument()
+                    .ok_or_else(|| missing("FOLDER or NEW-PATH"))?
+                    .into(),
+                dry_run,
+            },
+            "rename" => Subcommand::Rename {
+                path: argument().ok_or_else(|| missing("PATH"))?.into(),
+                title: argument().ok_or_else(|| missing("TITLE"))?,
+                dry_run,
+            },
+            "rename-heading" => Subcommand::RenameHeading {
+                path: argument().ok_or_else(|| missing("PATH"))?.into(),
+                old: argument().ok_or_else(|| missing("OLD"))?,
+                new: argument().ok_or_else(|| missing("NEW"))?,
+                dry_run,
+            },
+            "tag" => {
+                let merge = match argument()
+                    .ok_or_else(|| missing("rename or merge"))?
+                    .as_str()
+                {
+                    "rename" => false,
+                    "merge" => true,
+                    action => return Err(format!("unknown tag action `{action}`").into()),
+                };
+                Subcommand::RenameTag {
+                    old: argument().ok_or_else(|| missing("OLD"))?,
+                    new: argument().ok_or_else(|| missing("NEW"))?,
+                    merge,
+                }
+            }
+            "tags" => {
+                let name = argument().map(|tag| tag.trim_start_matches('#').to_string());
+                if fix && name.is_some() {
+                    return Err("`--fix` rewrites every tag, so it takes no tag".into());
+                }
+                Subcommand::Tags { name, fix }
+            }
+            "publish-check" => Subcommand::PublishCheck(
+                argument().unwrap_or_else(|| "(contains publish true)".to_string()),
+            ),
+            "inbox" => Subcommand::Inbox,
+            "insert-backlinks" => Subcommand::InsertBacklinks(argument().map(PathBuf::from)),
+            "toc" => Subcommand::Toc {
+                path: argument().ok_or_else(|| missing("PATH"))?.into(),
+                insert,
+            },
+            "fmt" => Subcommand::Fmt {
+                path: argument().map(PathBuf::from),
+                check,
+            },
+            "related" => Subcommand::Related {
+                path: argument().ok_or_else(|| missing("PATH"))?.into(),
+                method,
+            },
+            "schema-check" => Subcommand::SchemaCheck,
+            "doctor" => Subcommand::Doctor,
+            "broken-links" => Subcommand::BrokenLinks(argument().map(PathBuf::from)),
+            "verify-links" => Subcommand::VerifyLinks { allowlist },
+            "link-dates" => Subcommand::LinkDates(argument().map(PathBuf::from)),
+            "tree" => Subcommand::Tree { hierarchy },
+            "graph" => Subcommand::Graph { colour },
+            "orphans" => Subcommand::Orphans {
+                inbound: no_inbound,
+                outbound: no_outbound,
+            },
+            "serve" => Subcommand::Serve { host, port, ui },
+            "watch" => Subcommand::Watch {
+                interval,
+                exec,
+                query,
+            },
+            name => unreachable!("the subcommand `{name}` has a spec but isn't parsed"),
         };
+        if let Some(extra) = arguments.pop_front() {
+            return Err(format!("unexpected argument `{extra}`; usage: {}", spec.usage()).into());
+        }
+        // Only graphs are printed as DOT, and graphs can't be printed as a list of notes
+        match (&subcommand, format) {
+            (Subcommand::Graph { .. }, Format::Path | Format::Title) => {
+                return Err("`graph` can only be printed with `--format=dot`".into());
+            }
+            (Subcommand::Graph { .. }, _) | (_, Format::Table | Format::Path | Format::Title) => {}
+            (_, Format::Dot) => {
+                return Err("`--format=dot` only applies to `graph`".into());
+            }
+        }
+        if read_only && subcommand.mutates() {
+            return Err("the vault is opened read-only, so it cannot be modified".into());
+        }
 
         Ok(Args {
             subcommand,
             json,
             vault_dir,
+            kinds,
+            include_drafts,
+            format,
+            read_only,
+            rank_mode: if fresh {
+                RankMode::Fresh { half_life }
+            } else {
+                RankMode::Global
+            },
+            timings,
         })
     }
+
+    /// Parse a comma-separated list of kinds, where `all` stands for every kind
+    fn parse_kinds(kinds: &str) -> Result<Vec<Kind>, InvalidKind> {
+        if kinds.trim() == "all" {
+            return Ok(Kind::ALL.to_vec());
+        }
+        kinds.split(',').map(str::parse).collect()
+    }
 }
@@ -0,0 +1,350 @@
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use thiserror::Error;
+use yaml_rust2::{Yaml, YamlLoader};
+
+use crate::{
+    check::{Check, Severity},
+    date::{self, DateError, DateFormat},
+    document::ValueType,
+    kind::{InvalidKind, Kind},
+    tag::TagRules,
+    tokenize::Tokenizer,
+};
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("failed to read the configuration file `{path}` because {reason}")]
+    FailedToRead { path: PathBuf, reason: String },
+    #[error("the configuration file `{path}` cannot be parsed because {reason}")]
+    ParseFailed { path: PathBuf, reason: String },
+    #[error(transparent)]
+    InvalidKind(#[from] InvalidKind),
+    #[error(transparent)]
+    InvalidDateFormat(#[from] DateError),
+}
+
+/// Vault-level configuration, read from `.n/config.yaml` inside the vault directory. Every field
+/// has a sensible default, so the file is entirely optional.
+///
+/// ```yaml
+/// kinds:
+///   template: [templates]
+///   daily: [daily, journal]
+///   archive: archive
+/// dates: ["%Y-%m-%d", "%d/%m/%Y"]
+/// links:
+///   relative-only: true
+///   allowed-domains: [example.com]
+/// schema:
+///   required: [title]
+///   types:
+///     tags: list
+///     publish: boolean
+/// format:
+///   key-order: [title, date, tags]
+/// search:
+///   tokenizer: unicode
+/// journal:
+///   path: journal/%Y/%m/%Y-%m-%d.md
+///   template: daily
+/// trash:
+///   retention-days: 30
+/// doctor:
+///   duplicate-title: error
+///   unused-attachment: off
+/// tags:
+///   case-fold: true
+///   word-separator: "-"
+///   hierarchy-separators: [":", "."]
+/// ```
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// The folders, relative to the root of the vault, whose notes are of the given kind
+    pub kinds: BTreeMap<Kind, Vec<PathBuf>>,
+    /// The formats in which dates are recognised in the body of notes
+    pub dates: Vec<DateFormat>,
+    pub links: LinkPolicy,
+    pub schema: Schema,
+    /// The order `n fmt` puts the keys of the frontmatter in. Other keys come after them.
+    pub key_order: Vec<String>,
+    /// How the text of notes and queries is split into terms for the search
+    pub tokenizer: Tokenizer,
+    pub journal: Journal,
+    /// How long the files trashed by an operation are kept before being purged
+    pub trash_retention: Duration,
+    /// How tags are canonicalised when notes are parsed, and by `n tags --fix`
+    pub tags: TagRules,
+    /// The severity `n doctor` reports the issues of some checks with, where `None` turns the
+    /// check off
+    pub doctor: BTreeMap<Check, Option<Severity>>,
+}
+
+/// Where `n journal` puts daily notes, and what it fills them with
+#[derive(Debug, Clone)]
+pub struct Journal {
+    /// The path of daily notes relative to the root of the vault, where `%Y`, `%m` and `%d` stand
+    /// for the year, month and day of the note
+    pub path: String,
+    /// The template daily notes are rendered from. If none is configured, the `daily` template is
+    /// used when there is one.
+    pub template: Option<String>,
+}
+
+impl Default for Journal {
+    fn default() -> Self {
+        Self {
+            path: "journal/%Y-%m-%d.md".into(),
+            template: None,
+        }
+    }
+}
+
+/// Which forms of links are allowed in the vault, e.g. because others break once published
+#[derive(Debug, Clone, Default)]
+pub struct LinkPolicy {
+    /// Whether links to local files must be relative, rather than absolute paths
+    pub relative_only: bool,
+    /// The only domains external links may point to, along with their subdomains. Any domain is
+    /// allowed if this is `None`.
+    pub allowed_domains: Option<Vec<String>>,
+}
+
+/// What the frontmatter of every note must look like
+#[derive(Debug, Clone, Default)]
+pub struct Schema {
+    /// The keys every note must have
+    pub required: Vec<String>,
+    /// The type of the values of some keys, whenever they are present
+    pub types: BTreeMap<String, ValueType>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let kinds = BTreeMap::from([
+            (Kind::Template, vec!["templates".into()]),
+            (Kind::Daily, vec!["daily".into(), "journal".into()]),
+            (Kind::Archive, vec!["archive".into()]),
+        ]);
+        Self {
+            kinds,
+            dates: vec![date::ISO.clone()],
+            links: LinkPolicy::default(),
+            schema: Schema::default(),
+            key_order: vec!["title".into(), "date".into(), "tags".into()],
+            tokenizer: Tokenizer::default(),
+            journal: Journal::default(),
+            trash_retention: Duration::from_secs(30 * 86_400),
+            tags: TagRules::default(),
+            doctor: BTreeMap::new(),
+        }
+    }
+}
+
+impl Config {
+    /// The directory holding everything `n` keeps inside a vault
+    pub const DIR: &str = ".n";
+    pub const FILE: &str = "config.yaml";
+
+    /// Load the configuration of the vault at `base_path`, falling back to the defaults if it has
+    /// none.
+    pub fn load(base_path: &Path) -> Result<Self, ConfigError> {
+        let path = base_path.join(Self::DIR).join(Self::FILE);
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => {
+                return Err(ConfigError::FailedToRead {
+                    path,
+                    reason: e.to_string(),
+                });
+            }
+        };
+
+        let parsed =
+            YamlLoader::load_from_str(&contents).map_err(|e| ConfigError::ParseFailed {
+                path: path.clone(),
+                reason: e.to_string(),
+            })?;
+        let Some(root) = parsed.first() else {
+            return Ok(Self::default());
+        };
+
+        let mut config = Self::default();
+        if let Some(kinds) = root["kinds"].as_hash() {
+            for (kind, folders) in kinds {
+                let kind: Kind = kind
+                    .as_str()
+                    .ok_or_else(|| ConfigError::ParseFailed {
+                        path: path.clone(),
+                        reason: "the keys of `kinds` must be strings".into(),
+                    })?
+                    .parse()?;
+                config.kinds.insert(kind, Self::strings(folders));
+            }
+        }
+
+        if !root["dates"].is_badvalue() {
+            config.dates = Self::strings::<String>(&root["dates"])
+                .iter()
+                .map(|format| DateFormat::new(format))
+                .collect::<Result<_, _>>()?;
+        }
+
+        let links = &root["links"];
+        if let Some(relative_only) = links["relative-only"].as_bool() {
+            config.links.relative_only = relative_only;
+        }
+        if !links["allowed-domains"].is_badvalue() {
+            config.links.allowed_domains = Some(Self::strings(&links["allowed-domains"]));
+        }
+
+        let schema = &root["schema"];
+        config.schema.required = Self::strings(&schema["required"]);
+        if let Some(types) = schema["types"].as_hash() {
+            for (key, ty) in types {
+                let parse_failed = |reason: &str| ConfigError::ParseFailed {
+                    path: path.clone(),
+                    reason: reason.into(),
+                };
+                let key = key
+                    .as_str()
+                    .ok_or_else(|| parse_failed("the keys of `schema.types` must be strings"))?;
+                let ty = ty
+                    .as_str()
+                    .ok_or_else(|| parse_failed("the values of `schema.types` must be strings"))?
+                    .parse()
+                    .map_err(|reason: String| parse_failed(&reason))?;
+                config.schema.types.insert(key.to_string(), ty);
+            }
+        }
+
+        if !root["format"]["key-order"].is_badvalue() {
+            config.key_order = Self::strings(&root["format"]["key-order"]);
+        }
+
+        if let Some(tokenizer) = root["search"]["tokenizer"].as_str() {
+            config.tokenizer = tokenizer
+                .parse()
+                .map_err(|reason| ConfigError::ParseFailed {
+                    path: path.clone(),
+                    reason,
+                })?;
+        }
+
+        let journal = &root["journal"];
+        if let Some(pattern) = journal["path"].as_str() {
+            // The date must be part of the path, or every day would share the same note
+            if let Some(field) = ["%Y", "%m", "%d"]
+                .into_iter()
+                .find(|field| !pattern.contains(field))
+            {
+                return Err(ConfigError::ParseFailed {
+                    path,
+                    reason: format!("`journal.path` must contain {field}"),
+                });
+            }
+            if !pattern.ends_with(".md") {
+                return Err(ConfigError::ParseFailed {
+                    path,
+                    reason: "`journal.path` must end with .md".into(),
+                });
+            }
+            config.journal.path = pattern.to_string();
+        }
+        if let Some(template) = journal["template"].as_str() {
+            config.journal.template = Some(template.to_string());
+        }
+
+        match &root["trash"]["retention-days"] {
+            Yaml::Integer(days) if *days >= 0 => {
+                config.trash_retention = Duration::from_secs(*days as u64 * 86_400);
+            }
+            Yaml::BadValue => {}
+            _ => {
+                return Err(ConfigError::ParseFailed {
+                    path,
+                    reason: "`trash.retention-days` must be a number of days".into(),
+                });
+            }
+        }
+
+        if let Some(checks) = root["doctor"].as_hash() {
+            for (check, severity) in checks {
+                let parse_failed = |reason: String| ConfigError::ParseFailed {
+                    path: path.clone(),
+                    reason,
+                };
+                let check: Check = check
+                    .as_str()
+                    .ok_or_else(|| parse_failed("the keys of `doctor` must be checks".into()))?
+                    .parse()
+                    .map_err(parse_failed)?;
+                let severity = match severity.as_str() {
+                    Some("off") => None,
+                    Some(severity) => Some(severity.parse().map_err(parse_failed)?),
+                    None => {
+                        return Err(parse_failed(format!(
+                            "`doctor.{check}` must be `warning`, `error` or `off`"
+                        )));
+                    }
+                };
+                config.doctor.insert(check, severity);
+            }
+        }
+
+        let tags = &root["tags"];
+        if let Some(case_fold) = tags["case-fold"].as_bool() {
+            config.tags.case_fold = case_fold;
+        }
+        match &tags["word-separator"] {
+            Yaml::String(separator) if matches!(separator.as_str(), "-" | "_") => {
+                config.tags.word_separator = separator.chars().next();
+            }
+            Yaml::BadValue => {}
+            _ => {
+                return Err(ConfigError::ParseFailed {
+                    path,
+                    reason: "`tags.word-separator` must be `-` or `_`".into(),
+                });
+            }
+        }
+        for separator in Self::strings::<String>(&tags["hierarchy-separators"]) {
+            // Separators are swapped one character for another
+            let mut chars = separator.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if !c.is_alphanumeric() && !matches!(c, '-' | '_') => {
+                    config.tags.hierarchy_separators.push(c);
+                }
+                _ => {
+                    return Err(ConfigError::ParseFailed {
+                        path,
+                        reason: format!(
+                            "`{separator}` in `tags.hierarchy-separators` must be a single \
+                             punctuation character other than `-` and `_`"
+                        ),
+                    });
+                }
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Accept either a single string or a list of strings
+    fn strings<T: From<String>>(value: &Yaml) -> Vec<T> {
+        match value {
+            Yaml::String(val) => vec![val.to_owned().into()],
+            Yaml::Array(values) => values
+                .iter()
+                .filter_map(|val| val.as_str().map(|val| val.to_string().into()))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
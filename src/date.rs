@@ -0,0 +1,227 @@
+use std::{
+    fmt::Display,
+    ops::Range,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use once_cell::sync::Lazy;
+use proptest::prelude::*;
+use regex::Regex;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::pos::Pos;
+
+#[derive(Debug, Error)]
+pub enum DateError {
+    #[error("`{0}` is not a valid date; dates are written as YYYY-MM-DD")]
+    InvalidDate(String),
+    #[error("the date format `{format}` is invalid because {reason}")]
+    InvalidFormat { format: String, reason: String },
+}
+
+/// A calendar date, without any time zone
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub struct Date {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl Date {
+    /// Build a date, as long as the day exists in that month
+    pub fn new(year: i32, month: u32, day: u32) -> Option<Self> {
+        let leap = (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+        let days = match month {
+            1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+            4 | 6 | 9 | 11 => 30,
+            2 if leap => 29,
+            2 => 28,
+            _ => return None,
+        };
+        (1..=days)
+            .contains(&day)
+            .then_some(Date { year, month, day })
+    }
+
+    /// Today's date, in UTC
+    pub fn today() -> Self {
+        let elapsed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        Self::from_unix_days((elapsed / 86_400) as i64)
+    }
+
+    /// Write the date following a pattern of [`DateFormat`], e.g. `journal/%Y/%m/%Y-%m-%d.md`
+    pub fn format(&self, pattern: &str) -> String {
+        let mut res = String::new();
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            match (c, chars.clone().next()) {
+                ('%', Some('Y')) => res.push_str(&format!("{:04}", self.year)),
+                ('%', Some('m')) => res.push_str(&format!("{:02}", self.month)),
+                ('%', Some('d')) => res.push_str(&format!("{:02}", self.day)),
+                ('%', Some('%')) => res.push('%'),
+                (c, _) => {
+                    res.push(c);
+                    continue;
+                }
+            }
+            chars.next();
+        }
+        res
+    }
+
+    /// The number of days from the Unix epoch to the date, negative before it
+    pub fn unix_days(&self) -> i64 {
+        // Howard Hinnant's `days_from_civil`, the inverse of `from_unix_days`
+        let year = i64::from(self.year) - i64::from(self.month <= 2);
+        let era = year.div_euclid(400);
+        let year_of_era = year.rem_euclid(400);
+        let shifted_month = i64::from((self.month + 9) % 12);
+        let day_of_year = (153 * shifted_month + 2) / 5 + i64::from(self.day) - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146_097 + day_of_era - 719_468
+    }
+
+    /// The date the given number of days after the Unix epoch, in UTC
+    pub fn from_unix_days(days: i64) -> Self {
+        // Howard Hinnant's `civil_from_days`, counting in eras of 400 years from 0000-03-01
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let day_of_era = z.rem_euclid(146_097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+        let month = if shifted_month < 10 {
+            shifted_month + 3
+        } else {
+            shifted_month - 9
+        } as u32;
+        let year = (year_of_era + era * 400 + i64::from(month <= 2)) as i32;
+        Date { year, month, day }
+    }
+}
+
+impl Display for Date {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl FromStr for Date {
+    type Err = DateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ISO.find(s)
+            .into_iter()
+            .find(|(_, range)| *range == (0..s.len()))
+            .map(|(date, _)| date)
+            .ok_or_else(|| DateError::InvalidDate(s.to_string()))
+    }
+}
+
+/// A date mentioned in the body of a note
+#[derive(Debug, Clone, Serialize)]
+pub struct DateMention {
+    pub date: Date,
+    /// The text of the mention, as written in the note
+    pub text: String,
+    pub pos: Pos,
+    /// The byte range of the mention in the source file
+    pub range: Range<usize>,
+}
+
+/// A way of writing dates, given as a pattern where `%Y`, `%m` and `%d` stand for the year, the
+/// month and the day, e.g. `%d/%m/%Y`. Any other character is matched literally.
+#[derive(Debug, Clone)]
+pub struct DateFormat {
+    regex: Regex,
+}
+
+/// The format of the names of daily notes, and of dates in queries
+pub static ISO: Lazy<DateFormat> = Lazy::new(|| DateFormat::new("%Y-%m-%d").unwrap());
+
+impl DateFormat {
+    pub fn new(pattern: &str) -> Result<Self, DateError> {
+        let invalid = |reason: &str| DateError::InvalidFormat {
+            format: pattern.to_string(),
+            reason: reason.to_string(),
+        };
+        let mut regex = String::from(r"\b");
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '%' => match chars.next() {
+                    Some('Y') => regex.push_str(r"(?P<year>\d{4})"),
+                    Some('m') => regex.push_str(r"(?P<month>\d{1,2})"),
+                    Some('d') => regex.push_str(r"(?P<day>\d{1,2})"),
+                    Some('%') => regex.push('%'),
+                    _ => return Err(invalid("only %Y, %m and %d are supported")),
+                },
+                c => regex.push_str(&regex::escape(&c.to_string())),
+            }
+        }
+        regex.push_str(r"\b");
+
+        let regex = Regex::new(&regex).map_err(|_| invalid("a field is used more than once"))?;
+        if ["year", "month", "day"]
+            .iter()
+            .any(|name| !regex.capture_names().any(|n| n == Some(name)))
+        {
+            return Err(invalid("it must contain %Y, %m and %d"));
+        }
+        Ok(DateFormat { regex })
+    }
+
+    /// Find the valid dates written in this format in the text, along with their byte ranges
+    pub fn find(&self, text: &str) -> Vec<(Date, Range<usize>)> {
+        self.regex
+            .captures_iter(text)
+            .filter_map(|caps| {
+                let date = Date::new(
+                    caps["year"].parse().ok()?,
+                    caps["month"].parse().ok()?,
+                    caps["day"].parse().ok()?,
+                )?;
+                Some((date, caps.get(0)?.range()))
+            })
+            .collect()
+    }
+}
+
+proptest! {
+    #[test]
+    /// Dates must be read back as they are written, since daily notes are named after them
+    fn display_round_trip(year in 0i32..10_000, month in 1u32..=12, day in 1u32..=31) {
+        if let Some(date) = Date::new(year, month, day) {
+            prop_assert_eq!(date.to_string().parse::<Date>().unwrap(), date);
+        }
+    }
+
+    #[test]
+    /// Dates written following a pattern must be found again by the format of that pattern
+    fn format_round_trip(year in 1000i32..10_000, month in 1u32..=12, day in 1u32..=31) {
+        if let Some(date) = Date::new(year, month, day) {
+            let pattern = "%d/%m/%Y";
+            let found = DateFormat::new(pattern).unwrap().find(&date.format(pattern));
+            prop_assert_eq!(found.first().map(|(date, _)| *date), Some(date));
+        }
+    }
+
+    #[test]
+    fn unix_days_round_trip(days in -1_000_000i64..1_000_000) {
+        prop_assert_eq!(Date::from_unix_days(days).unix_days(), days);
+    }
+
+    #[test]
+    fn unix_days_are_consecutive(days in -1_000_000i64..1_000_000) {
+        let (date, next) = (Date::from_unix_days(days), Date::from_unix_days(days + 1));
+        prop_assert!(Date::new(date.year, date.month, date.day).is_some());
+        prop_assert!(date < next);
+        prop_assert!(next.day == date.day + 1 || next.day == 1);
+    }
+}
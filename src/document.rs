@@ -1,14 +1,36 @@
-use std::{collections::BTreeMap, fmt::Display, fs, hash::Hash, path::PathBuf};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+    fs,
+    hash::Hash,
+    ops::Range,
+    path::PathBuf,
+    str::FromStr,
+    time::UNIX_EPOCH,
+};
 
 use owo_colors::OwoColorize;
-use pulldown_cmark::{Event, LinkType, MetadataBlockKind, Options, Parser, Tag, TextMergeStream};
+use pulldown_cmark::{
+    Event, LinkType, MetadataBlockKind, Options, Parser, Tag, TagEnd, TextMergeWithOffset,
+};
 use rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
 use serde::Serialize;
 use tabled::Tabled;
 use thiserror::Error;
 use yaml_rust2::{Yaml, YamlLoader};
 
-use crate::{link::Link, path::MarkdownPath};
+use crate::{
+    date::{Date, DateFormat, DateMention},
+    edit,
+    heading::{Heading, Section},
+    kind::Kind,
+    link::Link,
+    path::MarkdownPath,
+    pos::{LineIndex, Pos},
+    tag::{self, InlineTag, TagRules},
+    task::Task,
+    tokenize::Tokenize,
+};
 
 type HashMap<K, V> = BTreeMap<K, V>;
 
@@ -53,6 +75,60 @@ impl Value {
     }
 }
 
+/// The type of a frontmatter value, as declared in the schema of the vault
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueType {
+    String,
+    Number,
+    Boolean,
+    List,
+    Map,
+}
+
+impl ValueType {
+    /// The type of the given value, if it has a meaningful one
+    pub fn of(value: &Value) -> Option<Self> {
+        match value {
+            Value::String(_) => Some(Self::String),
+            Value::Real(_) | Value::Integer(_) => Some(Self::Number),
+            Value::Boolean(_) => Some(Self::Boolean),
+            Value::Array(_) => Some(Self::List),
+            Value::Hash(_) => Some(Self::Map),
+            Value::Alias(_) | Value::Null | Value::Bad => None,
+        }
+    }
+}
+
+impl Display for ValueType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ValueType::String => "string",
+            ValueType::Number => "number",
+            ValueType::Boolean => "boolean",
+            ValueType::List => "list",
+            ValueType::Map => "map",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for ValueType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "string" => Ok(ValueType::String),
+            "number" => Ok(ValueType::Number),
+            "boolean" => Ok(ValueType::Boolean),
+            "list" => Ok(ValueType::List),
+            "map" => Ok(ValueType::Map),
+            _ => Err(format!(
+                "`{s}` is not a type; the types are string, number, boolean, list and map"
+            )),
+        }
+    }
+}
+
 impl Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let display_str = match self {
@@ -110,13 +186,62 @@ impl From<Yaml> for Value {
     }
 }
 
+/// The plain text of a document, as indexed by the search corpus
+#[derive(Debug, Default)]
+pub struct Stripped {
+    pub text: String,
+    /// Pairs of the offset of each piece of text in `text`, and its offset in the source file.
+    /// Both are strictly increasing.
+    offsets: Vec<(usize, usize)>,
+    source: String,
+}
+
+impl Stripped {
+    /// Map a byte offset into the stripped text back onto a byte offset into the source file
+    pub fn source_offset(&self, offset: usize) -> usize {
+        let idx = self
+            .offsets
+            .partition_point(|&(stripped, _)| stripped <= offset);
+        match idx.checked_sub(1).map(|idx| self.offsets[idx]) {
+            Some((stripped, source)) => (source + offset - stripped).min(self.source.len()),
+            None => 0,
+        }
+    }
+
+    /// Map a byte offset into the source file onto the offset of the first piece of stripped text
+    /// at or after it
+    pub fn stripped_offset(&self, source: usize) -> usize {
+        let idx = self.offsets.partition_point(|&(_, offset)| offset < source);
+        self.offsets
+            .get(idx)
+            .map_or(self.text.len(), |&(stripped, _)| stripped)
+    }
+
+    /// Map a byte offset into the stripped text back onto a position in the source file
+    #[inline]
+    pub fn source_pos(&self, offset: usize) -> Pos {
+        LineIndex::new(&self.source).pos(self.source_offset(offset))
+    }
+}
+
 /// A single Markdown document
 /// TODO: Implement metadata parsing
-#[derive(Debug, Serialize, Clone, Hash, PartialEq, Eq)]
+#[derive(Debug, Serialize, Clone)]
 pub struct Document {
     path: MarkdownPath,
     links: Vec<Link>,
+    headings: Vec<Heading>,
+    inline_tags: Vec<InlineTag>,
+    tasks: Vec<Task>,
     metadata: HashMap<String, Value>,
+    /// The byte range of the YAML inside the frontmatter block, if there is one
+    frontmatter: Option<Range<usize>>,
+    kind: Kind,
+    /// The modification time of the file when it was parsed, in milliseconds since the epoch
+    mtime: Option<u64>,
+    /// The digest of the contents the document was parsed from
+    #[serde(skip)]
+    digest: u64,
 }
 
 impl Document {
@@ -125,6 +250,65 @@ impl Document {
         self.path.clone()
     }
     #[inline]
+    pub fn kind(&self) -> Kind {
+        self.kind
+    }
+    /// When the note was last modified, in milliseconds since the epoch: the date of its
+    /// `modified` attribute if it has one, e.g. `2024-05-01` or `2024-05-01T09:30`, at midnight
+    /// UTC, or else the modification time of its file
+    pub fn modified(&self) -> Option<u64> {
+        self.date_attribute("modified")
+            .and_then(|date| u64::try_from(date.unix_days()).ok())
+            .map(|days| days * 86_400_000)
+            .or(self.mtime)
+    }
+    /// The date of the `key` attribute of the note, e.g. `2024-05-01` or `2024-05-01T09:30`
+    pub fn date_attribute(&self, key: &str) -> Option<Date> {
+        self.get_metadata(&key.to_string())
+            .and_then(|value| value.to_string().get(..10)?.parse().ok())
+    }
+    #[inline]
+    pub fn digest(&self) -> u64 {
+        self.digest
+    }
+    #[inline]
+    pub fn set_kind(&mut self, kind: Kind) {
+        self.kind = kind;
+    }
+    /// Whether the note is marked with `draft: true` or `private: true`, and should be left out
+    /// of the results unless drafts are explicitly included
+    pub fn is_draft(&self) -> bool {
+        ["draft", "private"].iter().any(|key| {
+            matches!(
+                self.get_metadata(&key.to_string()),
+                Some(Value::Boolean(true))
+            )
+        })
+    }
+    /// Whether the note is marked with `pinned: true`, to be listed before the others
+    pub fn is_pinned(&self) -> bool {
+        matches!(
+            self.get_metadata(&"pinned".to_string()),
+            Some(Value::Boolean(true))
+        )
+    }
+    /// The factor the score of the note is multiplied by, given by its `boost` attribute. A boost
+    /// which isn't a positive, finite number is ignored.
+    pub fn boost(&self) -> f32 {
+        self.get_metadata(&"boost".to_string())
+            .and_then(Self::parse_boost)
+            .unwrap_or(1.0)
+    }
+    /// The factor given by a `boost` attribute, if it's a positive, finite number
+    pub fn parse_boost(value: &Value) -> Option<f32> {
+        let boost = match value {
+            Value::Real(boost) => boost.parse().ok()?,
+            Value::Integer(boost) => *boost as f32,
+            _ => return None,
+        };
+        (boost.is_finite() && boost > 0.0).then_some(boost)
+    }
+    #[inline]
     pub fn insert_link(&mut self, link: Link) {
         self.links.push(link);
     }
@@ -133,6 +317,97 @@ impl Document {
         self.links.clone()
     }
     #[inline]
+    pub fn insert_heading(&mut self, heading: Heading) {
+        self.headings.push(heading);
+    }
+    /// The headings of the document, in the order they appear in
+    #[inline]
+    pub fn headings(&self) -> Vec<Heading> {
+        self.headings.clone()
+    }
+    /// The raw source of the section under the heading called `name`, if there is one
+    pub fn section(&self, name: &str) -> Result<Option<String>, ParseError> {
+        let contents = self.contents()?;
+        Ok(Section::from_headings(&self.headings, contents.len())
+            .into_iter()
+            .find(|section| section.heading.is(name))
+            .map(|section| contents[section.range].to_string()))
+    }
+    /// The raw source of the document, without its frontmatter block
+    pub fn body(&self) -> Result<String, ParseError> {
+        let contents = self.contents()?;
+        let start = self.frontmatter.as_ref().map_or(0, |range| {
+            // Skip the closing delimiter of the frontmatter block
+            contents[range.end..]
+                .find('\n')
+                .map_or(contents.len(), |i| range.end + i + 1)
+        });
+        Ok(contents[start..].to_string())
+    }
+    #[inline]
+    pub fn insert_inline_tag(&mut self, tag: InlineTag) {
+        self.inline_tags.push(tag);
+    }
+    #[inline]
+    pub fn insert_task(&mut self, task: Task) {
+        self.tasks.push(task);
+    }
+    /// The task list items of the document, in the order they appear in
+    #[inline]
+    pub fn tasks(&self) -> Vec<Task> {
+        self.tasks.clone()
+    }
+    /// Bring the tags of the document, in its `tags` attribute and in its body, to their canonical
+    /// form. The ranges of inline tags still point at the tags as written.
+    pub fn normalise_tags(&mut self, rules: &TagRules) {
+        if let Some(tags) = self.metadata.get_mut("tags") {
+            *tags = rules.normalise_metadata(tags);
+        }
+        self.inline_tags
+            .iter_mut()
+            .for_each(|tag| tag.name = rules.normalise(&tag.name));
+    }
+    /// The `#tags` found in the body of the document
+    #[inline]
+    pub fn inline_tags(&self) -> Vec<InlineTag> {
+        self.inline_tags.clone()
+    }
+    /// Every tag of the document, both from its `tags` attribute and from its body
+    pub fn tags(&self) -> BTreeSet<String> {
+        self.metadata
+            .get("tags")
+            .map(tag::from_metadata)
+            .unwrap_or_default()
+            .into_iter()
+            .chain(self.inline_tags.iter().map(|tag| tag.name.clone()))
+            .collect()
+    }
+    #[inline]
+    pub fn frontmatter(&self) -> Option<Range<usize>> {
+        self.frontmatter.clone()
+    }
+
+    /// Where the given top-level key of the frontmatter is written, if it is
+    pub fn metadata_pos(&self, key: &str) -> Result<Option<Pos>, ParseError> {
+        let Some(range) = self.frontmatter.clone() else {
+            return Ok(None);
+        };
+        let contents = self.contents()?;
+        let mut offset = range.start;
+        for line in contents[range].split_inclusive('\n') {
+            // Nested keys are indented
+            let name = line
+                .split_once(':')
+                .filter(|_| !line.starts_with([' ', '\t']))
+                .map(|(name, _)| name.trim().trim_matches(['"', '\'']));
+            if name == Some(key) {
+                return Ok(Some(LineIndex::new(&contents).pos(offset)));
+            }
+            offset += line.len();
+        }
+        Ok(None)
+    }
+    #[inline]
     pub fn insert_metadata(&mut self, key: Yaml, value: Yaml) -> Result<(), ParseError> {
         let key = if let Yaml::String(val) = key {
             Ok(val)
@@ -143,26 +418,40 @@ impl Document {
         Ok(())
     }
 
+    /// Read the raw contents of the document
+    pub fn contents(&self) -> Result<String, ParseError> {
+        fs::read_to_string(self.path.path()).map_err(|e| ParseError::FailedToReadFile {
+            path: self.path.path(),
+            reason: e.to_string(),
+        })
+    }
+
+    #[inline]
     pub fn stripped(&self) -> Result<String, ParseError> {
-        let mut res = String::new();
-        let path = &self.path;
-        let contents =
-            fs::read_to_string(path.path()).map_err(|e| ParseError::FailedToReadFile {
-                path: path.path(),
-                reason: e.to_string(),
-            })?;
+        Ok(self.stripped_with_offsets()?.text)
+    }
+
+    /// Strip the document down to its plain text, keeping track of where each piece of text came
+    /// from in the source file.
+    pub fn stripped_with_offsets(&self) -> Result<Stripped, ParseError> {
+        let mut res = Stripped::default();
+        let contents = self.contents()?;
 
         let mut options = Options::empty();
         options.insert(Options::ENABLE_YAML_STYLE_METADATA_BLOCKS);
         options.insert(Options::ENABLE_FOOTNOTES);
         options.insert(Options::ENABLE_MATH);
-        let mut iter = TextMergeStream::new(Parser::new_ext(&contents, options));
+        let mut iter =
+            TextMergeWithOffset::new(Parser::new_ext(&contents, options).into_offset_iter());
 
-        while let Some(event) = iter.next() {
+        while let Some((event, range)) = iter.next() {
             match event {
-                Event::Text(t) => res.push_str(format!("{t} ").as_str()),
-                Event::SoftBreak => res.push(' '),
-                Event::HardBreak | Event::Rule => res.push('\n'),
+                Event::Text(t) => {
+                    res.offsets.push((res.text.len(), range.start));
+                    res.text.push_str(format!("{t} ").as_str())
+                }
+                Event::SoftBreak => res.text.push(' '),
+                Event::HardBreak | Event::Rule => res.text.push('\n'),
                 // Skip unwanted events
                 Event::Start(
                     Tag::MetadataBlock(_)
@@ -178,7 +467,7 @@ impl Document {
                         id: _,
                     },
                 ) => {
-                    while let Some(event) = iter.next()
+                    while let Some((event, _)) = iter.next()
                         && !matches!(event, Event::End(_))
                     {}
                 }
@@ -186,9 +475,118 @@ impl Document {
             }
         }
 
+        res.source = contents;
         Ok(res)
     }
 
+    /// Split the stripped text of the document at its headings: the text before the first heading,
+    /// then every heading along with the text up to the next one, whatever its level
+    pub fn stripped_sections(&self) -> Result<Vec<(Option<Heading>, String)>, ParseError> {
+        let stripped = self.stripped_with_offsets()?;
+        let starts: Vec<usize> = std::iter::once(0)
+            .chain(
+                self.headings
+                    .iter()
+                    .map(|heading| stripped.stripped_offset(heading.range.start)),
+            )
+            .chain(std::iter::once(stripped.text.len()))
+            .collect();
+        let headings = std::iter::once(None).chain(self.headings.iter().cloned().map(Some));
+        Ok(headings
+            .zip(starts.windows(2))
+            .map(|(heading, bounds)| (heading, stripped.text[bounds[0]..bounds[1]].to_string()))
+            .filter(|(heading, text)| heading.is_some() || !text.trim().is_empty())
+            .collect())
+    }
+
+    /// Find the dates written in any of the given formats in the body of the document, leaving out
+    /// those in code, in the frontmatter, or already inside of a link
+    pub fn date_mentions(&self, formats: &[DateFormat]) -> Result<Vec<DateMention>, ParseError> {
+        let contents = self.contents()?;
+        let lines = LineIndex::new(&contents);
+        let mut options = Options::empty();
+        options.insert(Options::ENABLE_YAML_STYLE_METADATA_BLOCKS);
+
+        let mut mentions = Vec::new();
+        // How many code blocks, metadata blocks, links or images we're in
+        let mut depth = 0usize;
+        for (event, range) in
+            TextMergeWithOffset::new(Parser::new_ext(&contents, options).into_offset_iter())
+        {
+            match event {
+                Event::Start(
+                    Tag::CodeBlock(_)
+                    | Tag::MetadataBlock(_)
+                    | Tag::Link { .. }
+                    | Tag::Image { .. },
+                ) => depth += 1,
+                Event::End(
+                    TagEnd::CodeBlock | TagEnd::MetadataBlock(_) | TagEnd::Link | TagEnd::Image,
+                ) => depth = depth.saturating_sub(1),
+                // Only keep the dates whose text maps back onto the source verbatim
+                Event::Text(text) if depth == 0 => {
+                    for (date, date_range) in formats.iter().flat_map(|format| format.find(&text)) {
+                        let source = range.start + date_range.start..range.start + date_range.end;
+                        if contents.get(source.clone()) == text.get(date_range.clone()) {
+                            mentions.push(DateMention {
+                                date,
+                                text: text[date_range].to_string(),
+                                pos: lines.pos(source.start),
+                                range: source,
+                            });
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        mentions.sort_by_key(|mention| mention.range.start);
+        // Several formats may match the same text, e.g. `%d/%m/%Y` and `%m/%d/%Y`
+        mentions.dedup_by(|b, a| b.range.start < a.range.end);
+        Ok(mentions)
+    }
+
+    /// Every link and image of the document, of any kind. Unlike [`Document::links`], this takes
+    /// in images and reference-style links, e.g. to find out which files are used by notes.
+    pub fn references(&self) -> Result<Vec<Link>, ParseError> {
+        let contents = self.contents()?;
+        let lines = LineIndex::new(&contents);
+        let references = Parser::new_ext(&contents, Options::all())
+            .into_offset_iter()
+            .filter_map(|(event, range)| match event {
+                Event::Start(Tag::Link { dest_url, .. } | Tag::Image { dest_url, .. }) => {
+                    Some(Link {
+                        text: contents[range.clone()].to_string(),
+                        url: dest_url.into_string(),
+                        pos: lines.pos(range.start),
+                        range,
+                    })
+                }
+                _ => None,
+            })
+            .collect();
+        Ok(references)
+    }
+
+    /// Find the position of the first occurrence of any of the given terms in the source of the
+    /// document, splitting its words into terms like the search corpus does.
+    pub fn find_terms(
+        &self,
+        terms: &[&str],
+        tokenizer: &dyn Tokenize,
+    ) -> Result<Option<Pos>, ParseError> {
+        let stripped = self.stripped_with_offsets()?;
+        let mut offset = 0;
+        for word in stripped.text.split_inclusive(char::is_whitespace) {
+            let found = tokenizer.tokenize(word);
+            if found.iter().any(|term| terms.contains(&term.as_str())) {
+                return Ok(Some(stripped.source_pos(offset)));
+            }
+            offset += word.len();
+        }
+        Ok(None)
+    }
+
     pub fn new(base_path: PathBuf, path: PathBuf) -> Result<Self, ParseError> {
         let path = MarkdownPath::new(base_path.clone(), path.clone()).map_err(|e| {
             ParseError::InvalidPath {
@@ -200,7 +598,18 @@ impl Document {
         let mut document = Document {
             path: path.clone(),
             links: Vec::new(),
+            headings: Vec::new(),
+            inline_tags: Vec::new(),
+            tasks: Vec::new(),
             metadata: HashMap::new(),
+            frontmatter: None,
+            kind: Kind::default(),
+            mtime: fs::metadata(path.path())
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map(|elapsed| elapsed.as_millis() as u64),
+            digest: 0,
         };
 
         let contents =
@@ -208,13 +617,20 @@ impl Document {
                 path: path.path(),
                 reason: e.to_string(),
             })?;
+        document.digest = edit::digest(&contents);
 
         let mut options = Options::empty();
         options.insert(Options::ENABLE_YAML_STYLE_METADATA_BLOCKS);
-        let mut iter = TextMergeStream::new(Parser::new_ext(&contents, options)).peekable();
+        options.insert(Options::ENABLE_TASKLISTS);
+        let lines = LineIndex::new(&contents);
+        let mut iter =
+            TextMergeWithOffset::new(Parser::new_ext(&contents, options).into_offset_iter())
+                .peekable();
+        // Whether we're inside of a code or metadata block, where `#` doesn't start a tag
+        let mut in_verbatim = false;
 
-        while let Some(event) = iter.next() {
-            match (event, iter.peek()) {
+        while let Some((event, range)) = iter.next() {
+            match (event, iter.peek().map(|(event, _)| event)) {
                 // Parse link
                 (
                     Event::Start(Tag::Link {
@@ -228,6 +644,8 @@ impl Document {
                     document.insert_link(Link {
                         text: text.clone().into_string(),
                         url: dest_url.into_string(),
+                        pos: lines.pos(range.start),
+                        range,
                     });
                 }
                 // Parse frontmatter
@@ -256,6 +674,58 @@ impl Document {
                     hash.iter().for_each(|(k, v)| {
                         _ = document.insert_metadata(k.to_owned(), v.to_owned());
                     });
+                    document.frontmatter = iter.peek().map(|(_, range)| range.clone());
+                    in_verbatim = true;
+                }
+                (Event::Start(Tag::CodeBlock(_)), _) => in_verbatim = true,
+                (Event::End(TagEnd::CodeBlock | TagEnd::MetadataBlock(_)), _) => {
+                    in_verbatim = false
+                }
+                // Parse inline tags, as long as the text maps back onto the source verbatim
+                (Event::Text(text), _) if !in_verbatim => {
+                    for (name, tag_range) in tag::find_inline(&text) {
+                        let source = range.start + tag_range.start..range.start + tag_range.end;
+                        if contents.get(source.clone()) == text.get(tag_range) {
+                            document.insert_inline_tag(InlineTag {
+                                name,
+                                pos: lines.pos(source.start),
+                                range: source,
+                            });
+                        }
+                    }
+                }
+                // Parse task, which belongs to the last heading parsed so far
+                (Event::TaskListMarker(done), _) => {
+                    let heading = document.headings.last().map(|h| h.text.clone());
+                    let pos = lines.pos(range.start);
+                    document.insert_task(Task::new(&contents, range, done, pos, heading));
+                }
+                // Parse heading, whose text may be split across several inline events
+                (Event::Start(Tag::Heading { level, .. }), _) => {
+                    let mut text = String::new();
+                    let mut content: Option<Range<usize>> = None;
+                    while let Some((event, event_range)) = iter.next()
+                        && !matches!(event, Event::End(TagEnd::Heading(_)))
+                    {
+                        content = Some(match content {
+                            Some(content) => {
+                                content.start.min(event_range.start)
+                                    ..content.end.max(event_range.end)
+                            }
+                            None => event_range,
+                        });
+                        if let Event::Text(t) | Event::Code(t) = event {
+                            text.push_str(&t);
+                        }
+                    }
+                    document.insert_heading(Heading {
+                        level: level as u8,
+                        text,
+                        pos: lines.pos(range.start),
+                        // An empty heading has no text to point to
+                        content: content.unwrap_or(range.end..range.end),
+                        range,
+                    });
                 }
                 _ => {}
             }
@@ -264,12 +734,47 @@ impl Document {
         Ok(document)
     }
     pub fn has_link_to(&self, path: &MarkdownPath) -> bool {
-        self.links.iter().any(|link| link.points_to(path))
+        self.links
+            .iter()
+            .any(|link| link.points_to(self.path.dir(), path))
     }
     #[inline]
     pub fn get_metadata(&self, key: &String) -> Option<&Value> {
         self.metadata.get(key)
     }
+    /// The `title` attribute of the document, falling back to its file name if there is none or
+    /// if it isn't a plain value (e.g. in an unrendered template)
+    pub fn title(&self) -> String {
+        self.get_metadata(&"title".to_string())
+            .filter(|title| {
+                !matches!(
+                    title,
+                    Value::Array(_) | Value::Hash(_) | Value::Null | Value::Bad
+                )
+            })
+            .map(|title| title.to_string())
+            .unwrap_or_else(|| {
+                self.path
+                    .path()
+                    .file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string()
+            })
+    }
+    /// The other names the document goes by, from its `aliases` attribute, which may either be a
+    /// list or a single string
+    pub fn aliases(&self) -> Vec<String> {
+        match self.get_metadata(&"aliases".to_string()) {
+            Some(Value::Array(values)) => values
+                .iter()
+                .filter(|alias| !matches!(alias, Value::Array(_) | Value::Hash(_) | Value::Null))
+                .map(|alias| alias.to_string())
+                .collect(),
+            Some(Value::String(alias)) => vec![alias.clone()],
+            _ => Vec::new(),
+        }
+    }
     #[inline]
     pub fn metadata(&self) -> HashMap<String, Value> {
         self.metadata.clone()
@@ -305,16 +810,26 @@ impl Display for Document {
 
         let mut formatted_links = tabled::Table::new(formatted_links);
         formatted_links.with(tabled::settings::style::Style::rounded());
+
+        // Format headings into an indented outline
+        let formatted_headings: Vec<String> =
+            self.headings().iter().map(|val| val.to_string()).collect();
+        let mut formatted_headings = tabled::Table::new(formatted_headings);
+        formatted_headings.with(tabled::settings::style::Style::rounded());
         let display = format!(
             r#"{}
 
 Metadata:
 {}
 
+Headings:
+{}
+
 Links:
 {}"#,
             self.path(),
             formatted_metadata,
+            formatted_headings,
             formatted_links
         );
         write!(f, "{display}")
@@ -0,0 +1,270 @@
+use std::{
+    fmt::Display,
+    fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    io,
+    ops::Range,
+    path::PathBuf,
+};
+
+use owo_colors::OwoColorize;
+use serde::Serialize;
+
+use crate::{lock::Lock, trash::Trash};
+
+/// A replacement of a byte range of a file
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct TextEdit {
+    pub range: Range<usize>,
+    pub replacement: String,
+}
+
+/// A set of non-overlapping edits to a single file
+#[derive(Debug, Clone, Serialize)]
+pub struct FileEdit {
+    pub path: PathBuf,
+    pub edits: Vec<TextEdit>,
+    /// The [`digest`] of the contents the edits were planned against
+    #[serde(skip)]
+    pub digest: u64,
+    /// Where the file is moved to once edited, if it is
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rename: Option<PathBuf>,
+}
+
+/// A fingerprint of the contents of a file, to tell whether it changed since it was read
+pub fn digest(contents: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    contents.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl FileEdit {
+    /// Apply the edits to the given text. The edits are applied back to front, so that the ranges
+    /// of the earlier ones stay valid.
+    pub fn apply(&self, text: &str) -> String {
+        let mut edits = self.edits.clone();
+        edits.sort_unstable_by_key(|edit| std::cmp::Reverse(edit.range.start));
+        edits.dedup();
+        edits.into_iter().fold(text.to_string(), |mut acc, edit| {
+            acc.replace_range(edit.range, &edit.replacement);
+            acc
+        })
+    }
+}
+
+/// Apply a set of file edits all at once. Every file is first rendered into a hidden temporary
+/// file next to it, and only once all of them were written successfully are they renamed over the
+/// originals, so a failure midway doesn't leave the vault half-rewritten.
+///
+/// Nothing is written if any of the files changed since the edits were planned, e.g. because it
+/// was saved from an editor in the meantime, as the ranges of the edits would no longer line up.
+/// The lock of the vault is held from that check until the files are in place, so that another
+/// run of `n` can't change them in between.
+/// Likewise, nothing is written if a file would be moved over an existing one. The originals of
+/// moved files are only removed once everything else is in place.
+///
+/// The files are copied into the trash before being overwritten or removed, so that the whole set
+/// of edits can be undone with `n trash restore`.
+pub fn commit(edits: &[FileEdit], trash: &Trash) -> io::Result<()> {
+    let _lock = Lock::acquire(trash.root()).map_err(io::Error::other)?;
+    // Files which are moved are staged at their destination
+    let staged: Vec<(PathBuf, &PathBuf)> = edits
+        .iter()
+        .map(|edit| {
+            let target = edit.rename.as_ref().unwrap_or(&edit.path);
+            let name = target.file_name().unwrap_or_default().to_string_lossy();
+            (target.with_file_name(format!(".{name}.n-tmp")), target)
+        })
+        .collect();
+
+    let stage = || -> io::Result<()> {
+        edits.iter().zip(&staged).try_for_each(|(edit, (tmp, _))| {
+            let contents = fs::read_to_string(&edit.path)?;
+            if digest(&contents) != edit.digest {
+                return Err(io::Error::other(format!(
+                    "`{}` was modified since it was read",
+                    edit.path.to_string_lossy()
+                )));
+            }
+            if let Some(target) = &edit.rename {
+                if target.exists() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::AlreadyExists,
+                        format!("`{}` already exists", target.to_string_lossy()),
+                    ));
+                }
+                if let Some(dir) = target.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+            }
+            fs::write(tmp, edit.apply(&contents))
+        })
+    };
+    let store = || -> io::Result<()> {
+        if edits.is_empty() {
+            return Ok(());
+        }
+        let originals: Vec<_> = edits
+            .iter()
+            .map(|edit| (edit.path.as_path(), edit.rename.as_deref()))
+            .collect();
+        trash
+            .store(&originals)
+            .map(|_| ())
+            .map_err(io::Error::other)
+    };
+    if let Err(e) = stage().and_then(|_| store()) {
+        staged.iter().for_each(|(tmp, _)| _ = fs::remove_file(tmp));
+        return Err(e);
+    }
+
+    staged
+        .iter()
+        .try_for_each(|(tmp, target)| fs::rename(tmp, target))?;
+    edits
+        .iter()
+        .filter(|edit| edit.rename.is_some())
+        .try_for_each(|edit| fs::remove_file(&edit.path))
+}
+
+impl Display for FileEdit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let edits = self.edits.len();
+        let noun = if edits == 1 { "edit" } else { "edits" };
+        write!(
+            f,
+            "{}",
+            self.path.to_string_lossy().bright_blue().underline()
+        )?;
+        if let Some(target) = &self.rename {
+            write!(
+                f,
+                " → {}",
+                target.to_string_lossy().bright_blue().underline()
+            )?;
+        }
+        write!(f, " ({edits} {noun})")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::testing::TempDir;
+
+    fn trash(dir: &TempDir) -> Trash {
+        Trash::new(dir.path().to_path_buf(), Duration::from_secs(86_400))
+    }
+
+    /// An edit of `file`, planned against its current contents
+    fn plan(dir: &TempDir, file: &str, edits: Vec<TextEdit>, rename: Option<&str>) -> FileEdit {
+        FileEdit {
+            path: dir.join(file),
+            edits,
+            digest: digest(&dir.read(file)),
+            rename: rename.map(|rename| dir.join(rename)),
+        }
+    }
+
+    fn replace(range: Range<usize>, replacement: &str) -> TextEdit {
+        TextEdit {
+            range,
+            replacement: replacement.to_string(),
+        }
+    }
+
+    #[test]
+    fn apply_back_to_front() {
+        let edit = FileEdit {
+            path: PathBuf::new(),
+            edits: vec![replace(0..5, "Howdy"), replace(6..11, "there, world")],
+            digest: 0,
+            rename: None,
+        };
+        assert_eq!(edit.apply("Hello world!"), "Howdy there, world!");
+    }
+
+    #[test]
+    /// Committed edits land in the files, and `trash restore` undoes all of them at once
+    fn commit_and_restore() {
+        let dir = TempDir::new(&[("a.md", "Hello world"), ("b.md", "Bye")]);
+        let trash = trash(&dir);
+        let edits = [
+            plan(&dir, "a.md", vec![replace(0..5, "Howdy")], None),
+            plan(&dir, "b.md", vec![], Some("sub/c.md")),
+        ];
+
+        commit(&edits, &trash).unwrap();
+        assert_eq!(dir.read("a.md"), "Howdy world");
+        assert_eq!(dir.read("sub/c.md"), "Bye");
+        assert!(!dir.join("b.md").exists());
+        let batches = trash.batches().unwrap();
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].entries.len(), 2);
+
+        trash.restore(None).unwrap();
+        assert_eq!(dir.read("a.md"), "Hello world");
+        assert_eq!(dir.read("b.md"), "Bye");
+        assert!(!dir.join("sub/c.md").exists());
+    }
+
+    #[test]
+    /// Nothing is written, nor trashed, if a file changed since the edits were planned
+    fn commit_refuses_changed_file() {
+        let dir = TempDir::new(&[("a.md", "Hello world"), ("b.md", "Bye")]);
+        let trash = trash(&dir);
+        let edits = [
+            plan(&dir, "a.md", vec![replace(0..5, "Howdy")], None),
+            plan(&dir, "b.md", vec![replace(0..3, "Ciao")], None),
+        ];
+        dir.write("b.md", "Bye bye");
+
+        let error = commit(&edits, &trash).unwrap_err();
+        assert!(error.to_string().contains("was modified since it was read"));
+        assert_eq!(dir.read("a.md"), "Hello world");
+        assert_eq!(dir.read("b.md"), "Bye bye");
+        assert!(trash.batches().unwrap().is_empty());
+    }
+
+    #[test]
+    /// Edits wait for another run holding the lock of the vault to be done
+    fn commit_holds_the_lock() {
+        let dir = TempDir::new(&[("a.md", "Hello world")]);
+        let trash = trash(&dir);
+        let edits = [plan(&dir, "a.md", vec![replace(0..5, "Howdy")], None)];
+
+        let lock = Lock::acquire(dir.path()).unwrap();
+        std::thread::scope(|scope| {
+            let committed = scope.spawn(|| commit(&edits, &trash));
+            std::thread::sleep(Duration::from_millis(200));
+            assert_eq!(dir.read("a.md"), "Hello world");
+            drop(lock);
+            committed.join().unwrap().unwrap();
+        });
+        assert_eq!(dir.read("a.md"), "Howdy world");
+    }
+
+    #[test]
+    /// Nothing is written, nor trashed, if a file would be moved over another one
+    fn commit_refuses_existing_target() {
+        let dir = TempDir::new(&[("a.md", "Hello world"), ("b.md", "Bye"), ("c.md", "C")]);
+        let trash = trash(&dir);
+        let edits = [
+            plan(&dir, "a.md", vec![replace(0..5, "Howdy")], None),
+            plan(&dir, "b.md", vec![], Some("c.md")),
+        ];
+
+        let error = commit(&edits, &trash).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(dir.read("a.md"), "Hello world");
+        assert_eq!(dir.read("b.md"), "Bye");
+        assert_eq!(dir.read("c.md"), "C");
+        assert!(trash.batches().unwrap().is_empty());
+        // The staged files are cleaned up
+        let files = fs::read_dir(dir.path()).unwrap().filter_map(Result::ok);
+        assert_eq!(files.filter(|file| file.path().is_file()).count(), 3);
+    }
+}
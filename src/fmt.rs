@@ -0,0 +1,178 @@
+use std::ops::Range;
+
+use pulldown_cmark::{Event, Options, Parser, Tag};
+
+use crate::{
+    document::Document,
+    edit::{FileEdit, TextEdit},
+    link::Link,
+    path,
+    vault::{EditError, Vault},
+};
+
+/// Plan the edits normalising a document. Only what `n` already parses is touched, so that the
+/// formatting is always safe:
+///
+/// - the top-level keys of the frontmatter are put in the configured order, each moving along
+///   with its nested values and the comments above it,
+/// - runs of blank lines are collapsed into one, outside of code and HTML blocks, and the file
+///   ends with a single line break,
+/// - links to notes of the vault are rewritten into their shortest relative, percent-encoded form.
+///   Absolute links are only made relative if the link policy requires it.
+pub fn format(vault: &Vault, doc: &Document) -> Result<FileEdit, EditError> {
+    let contents = doc.contents()?;
+    let mut edits = Vec::new();
+
+    let body_start = match doc.frontmatter() {
+        Some(range) => {
+            edits.extend(order_keys(
+                &contents,
+                range.clone(),
+                &vault.config().key_order,
+            ));
+            // Skip the closing delimiter of the frontmatter block
+            contents[range.end..]
+                .find('\n')
+                .map_or(contents.len(), |i| range.end + i + 1)
+        }
+        None => 0,
+    };
+    edits.extend(collapse_blank_lines(&contents, body_start));
+    edits.extend(
+        non_canonical_links(vault, doc, &contents)
+            .into_iter()
+            .map(|(_, edit)| edit),
+    );
+
+    Ok(FileEdit {
+        path: doc.path().path(),
+        edits,
+        digest: doc.digest(),
+        rename: None,
+    })
+}
+
+/// Put the top-level keys of the YAML in `range` in the given order. Keys which aren't listed
+/// keep their relative order, after the listed ones.
+fn order_keys(contents: &str, range: Range<usize>, order: &[String]) -> Option<TextEdit> {
+    let yaml = &contents[range.clone()];
+    // Every entry starts at an unindented key, and takes the comments above it along
+    let mut entries: Vec<(Option<&str>, String)> = Vec::new();
+    let mut comments = String::new();
+    for raw in yaml.split_inclusive('\n') {
+        let line = if raw.ends_with('\n') {
+            raw.to_string()
+        } else {
+            format!("{raw}\n")
+        };
+        let is_nested = line.starts_with([' ', '\t', '-']) || line.trim().is_empty();
+        let key = raw.split_once(':').map(|(key, _)| key.trim());
+        match (line.starts_with('#'), is_nested, key, entries.last_mut()) {
+            (true, _, _, _) => comments.push_str(&line),
+            (false, false, Some(key), _) => {
+                entries.push((Some(key), std::mem::take(&mut comments) + &line));
+            }
+            (false, _, _, Some((_, entry))) => {
+                entry.push_str(&std::mem::take(&mut comments));
+                entry.push_str(&line);
+            }
+            (false, _, _, None) => comments.push_str(&line),
+        }
+    }
+    let trailing = comments;
+
+    let rank = |key: Option<&str>| {
+        key.and_then(|key| order.iter().position(|k| k == key))
+            .unwrap_or(order.len())
+    };
+    let mut sorted = entries.clone();
+    sorted.sort_by_key(|(key, _)| rank(*key));
+    if sorted == entries {
+        return None;
+    }
+    let mut replacement: String = sorted.into_iter().map(|(_, entry)| entry).collect();
+    replacement.push_str(&trailing);
+    if !yaml.ends_with('\n') {
+        replacement.pop();
+    }
+    Some(TextEdit { range, replacement })
+}
+
+/// Collapse runs of blank lines after `start` into a single one, leaving code and HTML blocks
+/// alone, and make the file end with a single line break
+fn collapse_blank_lines(contents: &str, start: usize) -> Vec<TextEdit> {
+    let verbatim: Vec<Range<usize>> = Parser::new_ext(contents, Options::all())
+        .into_offset_iter()
+        .filter_map(|(event, range)| match event {
+            Event::Start(Tag::CodeBlock(_) | Tag::HtmlBlock) => Some(range),
+            _ => None,
+        })
+        .collect();
+    let is_verbatim = |offset: usize| verbatim.iter().any(|range| range.contains(&offset));
+
+    let mut edits = Vec::new();
+    // The start of the current run of blank lines
+    let mut run: Option<usize> = None;
+    // Blank lines at the very start of the file are dropped, but one is kept after the frontmatter
+    let mut seen_text = start > 0;
+    let mut offset = start;
+    for line in contents[start..].split_inclusive('\n') {
+        if line.trim().is_empty() && !is_verbatim(offset) {
+            run.get_or_insert(offset);
+        } else {
+            if let Some(run_start) = run.take() {
+                let keep = match seen_text {
+                    true => line_len(&contents[run_start..]),
+                    false => 0,
+                };
+                if run_start + keep < offset {
+                    edits.push(TextEdit {
+                        range: run_start + keep..offset,
+                        replacement: String::new(),
+                    });
+                }
+            }
+            seen_text = true;
+        }
+        offset += line.len();
+    }
+
+    // Trailing blank lines, or a missing final line break
+    match run {
+        Some(run_start) if run_start < contents.len() => edits.push(TextEdit {
+            range: run_start..contents.len(),
+            replacement: String::new(),
+        }),
+        _ if !contents.is_empty() && !contents.ends_with('\n') => edits.push(TextEdit {
+            range: contents.len()..contents.len(),
+            replacement: "\n".to_string(),
+        }),
+        _ => {}
+    }
+    edits
+}
+
+/// The length of the first line of the text, including its line break
+fn line_len(text: &str) -> usize {
+    text.find('\n').map_or(text.len(), |i| i + 1)
+}
+
+/// Find the links to notes of the vault which aren't in their shortest relative, percent-encoded
+/// form, along with the edits rewriting them into it
+pub fn non_canonical_links(vault: &Vault, doc: &Document, contents: &str) -> Vec<(Link, TextEdit)> {
+    let policy = &vault.config().links;
+    let base_path = doc.path().dir();
+    doc.links()
+        .into_iter()
+        .filter(|link| !link.is_external())
+        .filter(|link| policy.relative_only || !link.target().starts_with('/'))
+        .filter_map(|link| {
+            let target = link.to_markdown_path(base_path.clone())?;
+            vault.get_document(&target)?;
+            let range = link.target_range(contents)?;
+            let replacement = path::relative_link(&target.path(), &base_path);
+            (contents[range.clone()] != replacement)
+                .then_some((link, TextEdit { range, replacement }))
+        })
+        .collect()
+}
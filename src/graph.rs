@@ -0,0 +1,134 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+};
+
+use serde::Serialize;
+
+use crate::{
+    path::MarkdownPath,
+    vault::{DocId, Vault},
+};
+
+/// What the nodes of the graph are coloured by
+#[derive(Debug, Clone, Copy)]
+pub enum Colouring {
+    /// Darker nodes rank higher
+    Rank,
+    /// Nodes sharing their first tag share a colour
+    Tag,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Node {
+    pub id: DocId,
+    pub path: MarkdownPath,
+    pub title: String,
+    /// The Graphviz colour of the node, if the graph is coloured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub colour: Option<String>,
+}
+
+/// The link graph of some notes of a vault, with the notes as nodes and the links between them as
+/// edges
+#[derive(Debug, Serialize)]
+pub struct Graph {
+    pub nodes: Vec<Node>,
+    /// The links between the nodes, as pairs of source and target. Several links from a note to
+    /// another make up a single edge.
+    pub edges: Vec<(DocId, DocId)>,
+}
+
+impl Graph {
+    /// The graph of the links between the given notes, leaving out links to other notes
+    pub fn new(vault: &Vault, ids: &[DocId]) -> Self {
+        let included: BTreeSet<DocId> = ids.iter().copied().collect();
+        let nodes = ids
+            .iter()
+            .map(|&id| {
+                let doc = vault.document(id);
+                Node {
+                    id,
+                    path: doc.path(),
+                    title: doc.title(),
+                    colour: None,
+                }
+            })
+            .collect();
+        let edges = ids
+            .iter()
+            .flat_map(|&src| {
+                vault
+                    .outbound(src)
+                    .iter()
+                    .filter(|target| included.contains(target))
+                    .map(move |&target| (src, target))
+            })
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        Self { nodes, edges }
+    }
+
+    /// Colour the nodes with the Graphviz `blues9` scheme, given the rank of each of them
+    pub fn colour_by_rank(&mut self, ranks: &[f32]) {
+        let max = ranks.iter().copied().fold(f32::MIN_POSITIVE, f32::max);
+        for (node, rank) in self.nodes.iter_mut().zip(ranks) {
+            let level = 1 + (8.0 * rank / max).round() as usize;
+            node.colour = Some(format!("/blues9/{level}"));
+        }
+    }
+
+    /// Colour the nodes with the Graphviz `set312` scheme by their first tag. Untagged nodes are
+    /// left uncoloured, and colours are reused past twelve tags.
+    pub fn colour_by_tag(&mut self, vault: &Vault) {
+        let first_tags: Vec<Option<String>> = self
+            .nodes
+            .iter()
+            .map(|node| vault.document(node.id).tags().into_iter().next())
+            .collect();
+        let colours: BTreeMap<&String, usize> = first_tags
+            .iter()
+            .flatten()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .zip(0..)
+            .collect();
+        for (node, tag) in self.nodes.iter_mut().zip(&first_tags) {
+            node.colour = tag
+                .as_ref()
+                .map(|tag| format!("/set312/{}", colours[tag] % 12 + 1));
+        }
+    }
+}
+
+/// Quote a string for the DOT language
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+impl Display for Graph {
+    /// Write the graph in the DOT language of Graphviz
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "digraph vault {{")?;
+        writeln!(f, "  node [shape=box, style=rounded];")?;
+        for node in &self.nodes {
+            let path = node.path.path().to_string_lossy().to_string();
+            write!(
+                f,
+                "  n{} [label={}, tooltip={}",
+                node.id.index(),
+                quote(&node.title),
+                quote(&path)
+            )?;
+            if let Some(colour) = &node.colour {
+                write!(f, ", style=\"rounded,filled\", fillcolor={}", quote(colour))?;
+            }
+            writeln!(f, "];")?;
+        }
+        for (src, target) in &self.edges {
+            writeln!(f, "  n{} -> n{};", src.index(), target.index())?;
+        }
+        writeln!(f, "}}")
+    }
+}
@@ -0,0 +1,122 @@
+use std::{fmt::Display, ops::Range};
+
+use proptest::prelude::*;
+use serde::Serialize;
+
+use crate::pos::Pos;
+
+#[derive(Debug, Serialize, Clone, Hash, PartialEq, Eq)]
+/// An ATX or setext heading in a Markdown file
+pub struct Heading {
+    /// From 1 for `#` to 6 for `######`
+    pub level: u8,
+    pub text: String,
+    pub pos: Pos,
+    /// The byte range of the whole heading, including the `#`s, in the source file
+    pub range: Range<usize>,
+    /// The byte range of the text of the heading, markup included, in the source file
+    pub content: Range<usize>,
+}
+
+impl Heading {
+    /// The anchor used to link to this heading, e.g. `note.md#some-heading`
+    #[inline]
+    pub fn slug(&self) -> String {
+        slugify(&self.text)
+    }
+
+    /// Whether the heading is the one referred to by `name`, either by its text or by its slug
+    #[inline]
+    pub fn is(&self, name: &str) -> bool {
+        self.text == name || self.slug() == slugify(name.trim_start_matches('#'))
+    }
+}
+
+/// A heading along with everything under it, up to the next heading of the same or a higher level
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct Section {
+    pub heading: Heading,
+    /// The byte range of the section, including its heading, in the source file
+    pub range: Range<usize>,
+}
+
+impl Section {
+    /// Split a document into its sections, given its headings and the length of its source
+    pub fn from_headings(headings: &[Heading], len: usize) -> Vec<Section> {
+        headings
+            .iter()
+            .enumerate()
+            .map(|(i, heading)| {
+                let end = headings[i + 1..]
+                    .iter()
+                    .find(|next| next.level <= heading.level)
+                    .map_or(len, |next| next.range.start);
+                Section {
+                    heading: heading.clone(),
+                    range: heading.range.start..end,
+                }
+            })
+            .collect()
+    }
+}
+
+/// The comments between which `n toc --insert` writes the table of contents of a note
+pub const TOC_START: &str = "<!-- toc -->";
+pub const TOC_END: &str = "<!-- /toc -->";
+
+/// A nested list of links to the headings, indented by their level relative to the highest one. A
+/// lone level 1 heading is taken to be the title of the note, and left out.
+pub fn table_of_contents(headings: &[Heading]) -> String {
+    let titles = headings.iter().filter(|heading| heading.level == 1).count();
+    let headings: Vec<&Heading> = headings
+        .iter()
+        .filter(|heading| titles != 1 || heading.level > 1)
+        .collect();
+    let top = headings
+        .iter()
+        .map(|heading| heading.level)
+        .min()
+        .unwrap_or(1);
+    headings
+        .iter()
+        .map(|heading| {
+            let indent = "  ".repeat((heading.level - top) as usize);
+            format!("{indent}- [{}](#{})\n", heading.text, heading.slug())
+        })
+        .collect()
+}
+
+/// Turn a heading into its anchor the same way GitHub does: lowercase it, drop punctuation, and
+/// replace spaces with hyphens.
+pub fn slugify(text: &str) -> String {
+    text.trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+impl Display for Heading {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let indent = "  ".repeat(self.level.saturating_sub(1) as usize);
+        write!(
+            f,
+            "{indent}{} {}",
+            "#".repeat(self.level as usize),
+            self.text
+        )
+    }
+}
+
+proptest! {
+    #[test]
+    /// Slugifying a slug must not change it, so anchors can be compared by slugifying both sides
+    fn slug_idempotence(text in any::<String>()) {
+        let slug = slugify(&text);
+        prop_assert_eq!(slugify(&slug), slug);
+    }
+}
@@ -0,0 +1,260 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use owo_colors::OwoColorize;
+use serde::Serialize;
+
+use crate::{document::Document, kind::Kind, path::MarkdownPath, vault::Vault};
+
+/// Something a note can be nested under
+#[derive(Debug, Clone)]
+pub enum Ancestor<'a> {
+    Note(&'a Document),
+    /// A folder, relative to the root of the vault
+    Folder(PathBuf),
+}
+
+/// A node of the hierarchy, either a folder or a note
+#[derive(Debug, Serialize)]
+pub struct Node {
+    pub name: String,
+    /// The path of the note, or `None` if the node is a folder
+    pub path: Option<MarkdownPath>,
+    pub children: Vec<Node>,
+}
+
+impl Node {
+    fn fmt_children(&self, f: &mut std::fmt::Formatter<'_>, prefix: &str) -> std::fmt::Result {
+        for (i, child) in self.children.iter().enumerate() {
+            let last = i + 1 == self.children.len();
+            let (branch, indent) = if last {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+            let name = match child.path {
+                Some(_) => child.name.clone(),
+                None => format!("{}/", child.name).bright_blue().bold().to_string(),
+            };
+            writeln!(f, "{prefix}{branch}{name}")?;
+            child.fmt_children(f, &format!("{prefix}{indent}"))?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Node {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.name.bright_blue().bold())?;
+        self.fmt_children(f, "")
+    }
+}
+
+/// The hierarchy of the notes of a vault, given by the folders they are in, and by the `parent`
+/// attribute in their frontmatter, which nests a note under another one. The parents of the notes
+/// are resolved once, when the hierarchy is built.
+pub struct Hierarchy<'a> {
+    vault: &'a Vault,
+    root: PathBuf,
+    /// The visible notes by title, the first one by path when several share a title
+    titles: HashMap<String, &'a Document>,
+    /// The explicit parent of every visible note which has one
+    parents: HashMap<MarkdownPath, &'a Document>,
+    /// The notes whose explicit parent is the given note, sorted by title
+    children: HashMap<MarkdownPath, Vec<&'a Document>>,
+}
+
+impl<'a> Hierarchy<'a> {
+    pub fn new(vault: &'a Vault) -> Self {
+        let root = vault.path().canonicalize().unwrap_or_else(|_| vault.path());
+        let documents = vault.documents();
+        let mut titles = HashMap::new();
+        for &doc in &documents {
+            titles.entry(doc.title()).or_insert(doc);
+        }
+        let mut hierarchy = Self {
+            vault,
+            root,
+            titles,
+            parents: HashMap::new(),
+            children: HashMap::new(),
+        };
+
+        let declared: HashMap<MarkdownPath, &'a Document> = documents
+            .iter()
+            .filter_map(|&doc| Some((doc.path(), hierarchy.declared_parent(doc)?)))
+            .collect();
+        // Parents leading back to the note itself are left out
+        let parents: HashMap<MarkdownPath, &'a Document> = declared
+            .iter()
+            .filter(|(path, parent)| {
+                let mut seen = BTreeSet::from([(*path).clone()]);
+                let mut current = Some(**parent);
+                while let Some(doc) = current {
+                    if !seen.insert(doc.path()) {
+                        return false;
+                    }
+                    current = declared.get(&doc.path()).copied();
+                }
+                true
+            })
+            .map(|(path, parent)| (path.clone(), *parent))
+            .collect();
+        let mut children: HashMap<MarkdownPath, Vec<&'a Document>> = HashMap::new();
+        for &doc in &documents {
+            if let Some(parent) = parents.get(&doc.path()) {
+                children.entry(parent.path()).or_default().push(doc);
+            }
+        }
+        children
+            .values_mut()
+            .for_each(|children| children.sort_by_key(|doc| doc.title()));
+
+        hierarchy.parents = parents;
+        hierarchy.children = children;
+        hierarchy
+    }
+
+    /// The folder containing the document, relative to the root of the vault
+    pub fn folder(&self, document: &Document) -> PathBuf {
+        let dir = document.path().dir();
+        dir.strip_prefix(&self.root)
+            .map(Path::to_path_buf)
+            .unwrap_or(dir)
+    }
+
+    /// Resolve the `parent` attribute of a document, which can either be a path relative to the
+    /// document, or the title of another note.
+    fn declared_parent(&self, document: &Document) -> Option<&'a Document> {
+        let parent = document.get_metadata(&"parent".to_string())?.to_string();
+        let parent = parent.trim_start_matches("[[").trim_end_matches("]]");
+        let by_path = MarkdownPath::new(document.path().dir(), PathBuf::from(parent))
+            .ok()
+            .and_then(|path| self.vault.get_document(&path));
+        by_path.or_else(|| self.titles.get(parent).copied())
+    }
+
+    /// The explicit parent of a visible document, unless following the parents leads back to it
+    pub fn parent(&self, document: &Document) -> Option<&'a Document> {
+        self.parents.get(&document.path()).copied()
+    }
+
+    /// Every note and folder the document is nested under, from the closest to the furthest
+    pub fn ancestors(&self, document: &Document) -> Vec<Ancestor<'a>> {
+        let mut ancestors = Vec::new();
+        let mut current = self.parent(document);
+        while let Some(doc) = current {
+            ancestors.push(Ancestor::Note(doc));
+            current = self.parent(doc);
+        }
+        ancestors.extend(
+            self.folder(document)
+                .ancestors()
+                .filter(|folder| !folder.as_os_str().is_empty())
+                .map(|folder| Ancestor::Folder(folder.to_path_buf())),
+        );
+        ancestors
+    }
+
+    /// Whether the document is nested under a note or a folder called `name`. Notes are matched
+    /// by title or by path relative to the vault, and folders by path or by name.
+    pub fn is_descendant_of(&self, document: &Document, name: &str) -> bool {
+        let name = name.trim_end_matches('/');
+        self.ancestors(document)
+            .iter()
+            .any(|ancestor| match ancestor {
+                Ancestor::Note(doc) => {
+                    let path = doc.path().path();
+                    let relative = path.strip_prefix(&self.root).unwrap_or(&path);
+                    doc.title() == name
+                        || relative == Path::new(name)
+                        || relative.with_extension("") == Path::new(name)
+                }
+                Ancestor::Folder(folder) => {
+                    folder == Path::new(name) || folder.file_name().is_some_and(|f| f == name)
+                }
+            })
+    }
+
+    /// Build the tree of folders and notes of the given kinds. If `explicit` is set, notes with a
+    /// `parent` are nested under it rather than under their folder, unless it's left out.
+    pub fn tree(&self, explicit: bool, kinds: &[Kind]) -> Node {
+        let name = self
+            .root
+            .file_name()
+            .map_or_else(
+                || self.root.to_string_lossy(),
+                |name| name.to_string_lossy(),
+            )
+            .to_string();
+        Node {
+            name,
+            path: None,
+            children: self.folder_children(Path::new(""), explicit, kinds),
+        }
+    }
+
+    fn folder_children(&self, folder: &Path, explicit: bool, kinds: &[Kind]) -> Vec<Node> {
+        let mut documents = self.vault.documents();
+        documents.retain(|doc| kinds.contains(&doc.kind()));
+        let subfolders: BTreeSet<PathBuf> = documents
+            .iter()
+            .filter_map(|doc| {
+                let relative = self.folder(doc);
+                let rest = relative.strip_prefix(folder).ok()?;
+                rest.components()
+                    .next()
+                    .map(|component| folder.join(component))
+            })
+            .collect();
+
+        let mut notes: Vec<&Document> = documents
+            .into_iter()
+            .filter(|doc| self.folder(doc) == folder)
+            .filter(|doc| {
+                !explicit
+                    || self
+                        .parent(doc)
+                        .is_none_or(|parent| !kinds.contains(&parent.kind()))
+            })
+            .collect();
+        notes.sort_by_key(|doc| doc.title());
+
+        subfolders
+            .into_iter()
+            .map(|subfolder| Node {
+                name: subfolder
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string(),
+                path: None,
+                children: self.folder_children(&subfolder, explicit, kinds),
+            })
+            .chain(notes.into_iter().map(|doc| self.note(doc, explicit, kinds)))
+            .collect()
+    }
+
+    fn note(&self, document: &Document, explicit: bool, kinds: &[Kind]) -> Node {
+        let children = match explicit {
+            true => self
+                .children
+                .get(&document.path())
+                .cloned()
+                .unwrap_or_default(),
+            false => Vec::new(),
+        };
+        Node {
+            name: document.title(),
+            path: Some(document.path()),
+            children: children
+                .into_iter()
+                .filter(|doc| kinds.contains(&doc.kind()))
+                .map(|doc| self.note(doc, explicit, kinds))
+                .collect(),
+        }
+    }
+}
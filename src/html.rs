@@ -0,0 +1,98 @@
+use std::path::{Path, PathBuf};
+
+use pulldown_cmark::{CowStr, Event, Options, Parser, Tag, TagEnd, html};
+
+use crate::{
+    document::{Document, ParseError},
+    path::MarkdownPath,
+};
+
+/// How deep notes embedded in one another are rendered, so that notes embedding each other don't
+/// loop forever
+const MAX_EMBED_DEPTH: usize = 3;
+
+/// Decides what the links between notes turn into when a note is rendered as HTML, e.g. a route
+/// of a web viewer or the page of an exported site
+pub trait LinkResolver {
+    /// The URL a link to the given note should point to, or `None` to keep the link as written
+    fn url(&self, target: &MarkdownPath) -> Option<String>;
+
+    /// The note to render in place of an embed, i.e. an image `![...](note.md)` of a note
+    fn embed(&self, target: &MarkdownPath) -> Option<&Document>;
+}
+
+/// The note a relative URL, found in a note inside of `base_path`, points to
+fn note_target(base_path: &Path, url: &str) -> Option<MarkdownPath> {
+    let target = url.split_once('#').map_or(url, |(target, _)| target);
+    match url::Url::parse(url) {
+        Err(url::ParseError::RelativeUrlWithoutBase) if !target.is_empty() => {
+            MarkdownPath::new(base_path.to_path_buf(), PathBuf::from(target)).ok()
+        }
+        _ => None,
+    }
+}
+
+impl Document {
+    /// Render the body of the note as HTML, pointing its links to other notes where the resolver
+    /// says, and expanding the notes it embeds
+    pub fn to_html(&self, resolver: &dyn LinkResolver) -> Result<String, ParseError> {
+        self.to_html_at(resolver, 0)
+    }
+
+    fn to_html_at(&self, resolver: &dyn LinkResolver, depth: usize) -> Result<String, ParseError> {
+        let body = self.body()?;
+        let base_path = self.path().dir();
+        let mut options = Options::empty();
+        options.insert(Options::ENABLE_TABLES);
+        options.insert(Options::ENABLE_FOOTNOTES);
+        options.insert(Options::ENABLE_STRIKETHROUGH);
+        options.insert(Options::ENABLE_TASKLISTS);
+
+        let mut events = Vec::new();
+        let mut parser = Parser::new_ext(&body, options);
+        while let Some(event) = parser.next() {
+            match event {
+                Event::Start(Tag::Link {
+                    link_type,
+                    dest_url,
+                    title,
+                    id,
+                }) => {
+                    let dest_url = note_target(&base_path, &dest_url)
+                        .and_then(|target| resolver.url(&target))
+                        .map_or(dest_url, CowStr::from);
+                    events.push(Event::Start(Tag::Link {
+                        link_type,
+                        dest_url,
+                        title,
+                        id,
+                    }));
+                }
+                Event::Start(Tag::Image { ref dest_url, .. }) => {
+                    let embedded = (depth < MAX_EMBED_DEPTH)
+                        .then(|| note_target(&base_path, dest_url))
+                        .flatten()
+                        .and_then(|target| resolver.embed(&target));
+                    match embedded {
+                        Some(note) => {
+                            // The alternative text of the image is replaced by the note
+                            parser
+                                .by_ref()
+                                .find(|e| matches!(e, Event::End(TagEnd::Image)));
+                            let html = note.to_html_at(resolver, depth + 1)?;
+                            events.push(Event::Html(
+                                format!("<div class=\"embed\">\n{html}</div>\n").into(),
+                            ));
+                        }
+                        None => events.push(event),
+                    }
+                }
+                event => events.push(event),
+            }
+        }
+
+        let mut res = String::new();
+        html::push_html(&mut res, events.into_iter());
+        Ok(res)
+    }
+}
@@ -0,0 +1,222 @@
+use std::{
+    io::{self, BufRead, Write},
+    path::PathBuf,
+    str::FromStr,
+};
+
+use owo_colors::OwoColorize;
+use thiserror::Error;
+
+use crate::{
+    document::{Document, ParseError},
+    edit::{self, FileEdit, TextEdit},
+    kind::Kind,
+    path::MarkdownPath,
+    vault::{EditError, Vault, VaultInitialisationError},
+};
+
+/// The tag marking notes which still need to be triaged
+pub const TAG: &str = "inbox";
+/// The folder, relative to the root of the vault, holding notes which still need to be triaged
+pub const FOLDER: &str = "inbox";
+
+#[derive(Debug, Error)]
+pub enum InboxError {
+    #[error(transparent)]
+    Vault(#[from] VaultInitialisationError),
+    #[error(transparent)]
+    Edit(#[from] EditError),
+    #[error(transparent)]
+    ReadFailed(#[from] ParseError),
+    #[error("failed to apply the edits because {0}")]
+    CommitFailed(#[from] io::Error),
+    #[error("`{0}` is not an action; the actions are tag, move, link, archive, skip and quit")]
+    UnknownAction(String),
+    #[error("the action `{0}` needs an argument")]
+    MissingArgument(String),
+    #[error("`{0}` is not a note in the vault")]
+    UnknownNote(PathBuf),
+}
+
+/// What to do with a note of the inbox
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// Tag the note, instead of `inbox`
+    Tag(String),
+    /// Move the note into a folder, relative to the root of the vault
+    Move(PathBuf),
+    /// Link to the note from the end of another note, relative to the root of the vault
+    Link(PathBuf),
+    /// Move the note into the archive
+    Archive,
+    Skip,
+    Quit,
+}
+
+impl FromStr for Action {
+    type Err = InboxError;
+
+    /// Parse an action as typed at the prompt, e.g. `t rust` or `move projects`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (action, argument) = s
+            .trim()
+            .split_once(char::is_whitespace)
+            .map_or((s.trim(), ""), |(action, argument)| {
+                (action, argument.trim())
+            });
+        let argument = || {
+            (!argument.is_empty())
+                .then(|| argument.to_string())
+                .ok_or_else(|| InboxError::MissingArgument(action.to_string()))
+        };
+        match action {
+            "t" | "tag" => Ok(Action::Tag(argument()?.trim_start_matches('#').to_string())),
+            "m" | "move" => Ok(Action::Move(argument()?.into())),
+            "l" | "link" => Ok(Action::Link(argument()?.into())),
+            "a" | "archive" => Ok(Action::Archive),
+            "s" | "skip" | "" => Ok(Action::Skip),
+            "q" | "quit" => Ok(Action::Quit),
+            action => Err(InboxError::UnknownAction(action.to_string())),
+        }
+    }
+}
+
+/// Whether the note still needs to be triaged, i.e. whether it's tagged `inbox` or inside of the
+/// inbox folder
+pub fn is_inbox(vault: &Vault, doc: &Document) -> bool {
+    let root = vault.path().canonicalize().unwrap_or_else(|_| vault.path());
+    doc.tags().contains(TAG) || doc.path().path().starts_with(root.join(FOLDER))
+}
+
+/// Plan the edits carrying out the action on the note. Every action takes the note out of the
+/// inbox: its `inbox` tag is dropped, or replaced by the new tag.
+pub fn plan(vault: &Vault, doc: &Document, action: &Action) -> Result<Vec<FileEdit>, InboxError> {
+    let root = vault.path().canonicalize().unwrap_or_else(|_| vault.path());
+    let file_name = doc.path().path().file_name().unwrap_or_default().to_owned();
+    let untag = vault.untag(doc, TAG)?;
+    let mut edits = match action {
+        Action::Tag(tag) if doc.tags().contains(TAG) => vec![vault.retag(doc, TAG, tag, true)?],
+        Action::Tag(tag) => {
+            let contents = doc.contents()?;
+            let separator = if contents.ends_with('\n') { "" } else { "\n" };
+            vec![FileEdit {
+                path: doc.path().path(),
+                edits: vec![TextEdit {
+                    range: contents.len()..contents.len(),
+                    replacement: format!("{separator}\n#{tag}\n"),
+                }],
+                digest: doc.digest(),
+                rename: None,
+            }]
+        }
+        Action::Move(folder) => {
+            let to = root.join(folder).join(file_name);
+            vault.move_document(&doc.path(), to)?
+        }
+        Action::Archive => {
+            let folder = vault
+                .config()
+                .kinds
+                .get(&Kind::Archive)
+                .and_then(|folders| folders.first().cloned())
+                .unwrap_or_else(|| "archive".into());
+            let to = root.join(folder).join(file_name);
+            vault.move_document(&doc.path(), to)?
+        }
+        Action::Link(target) => {
+            let target = MarkdownPath::new(root.clone(), target.clone())
+                .ok()
+                .and_then(|path| vault.get_document(&path))
+                .ok_or_else(|| InboxError::UnknownNote(target.clone()))?;
+            let contents = target.contents()?;
+            let separator = if contents.ends_with('\n') { "" } else { "\n" };
+            let link = doc.path().link_from(&target.path().dir());
+            vec![FileEdit {
+                path: target.path().path(),
+                edits: vec![TextEdit {
+                    range: contents.len()..contents.len(),
+                    replacement: format!("{separator}- [{}]({link})\n", doc.title()),
+                }],
+                digest: target.digest(),
+                rename: None,
+            }]
+        }
+        Action::Skip | Action::Quit => return Ok(Vec::new()),
+    };
+
+    // A tag which is replaced doesn't need to be removed as well
+    if !matches!(action, Action::Tag(_)) {
+        match edits.iter_mut().find(|edit| edit.path == untag.path) {
+            Some(edit) => edit.edits.extend(untag.edits),
+            None => edits.push(untag),
+        }
+    }
+    edits.retain(|edit| !edit.edits.is_empty() || edit.rename.is_some());
+    Ok(edits)
+}
+
+/// Go through the notes of the inbox one at a time, reading what to do with each of them from
+/// `input`, and applying the edits right away. The vault is read again after every change, so
+/// that the following edits are planned against the current contents of the files. Returns the
+/// number of notes which were triaged.
+pub fn triage(
+    vault_dir: PathBuf,
+    include_drafts: bool,
+    mut input: impl BufRead,
+) -> Result<usize, InboxError> {
+    let load = || -> Result<Vault, InboxError> {
+        let mut vault = Vault::new(vault_dir.clone())?;
+        vault.set_include_drafts(include_drafts);
+        Ok(vault)
+    };
+    let mut vault = load()?;
+    let inbox: Vec<MarkdownPath> = vault
+        .documents()
+        .into_iter()
+        .filter(|doc| is_inbox(&vault, doc))
+        .map(Document::path)
+        .collect();
+
+    let mut triaged = 0;
+    for (i, path) in inbox.iter().enumerate() {
+        // The note is asked about again until a valid action is given
+        while let Some(doc) = vault.get_document(path) {
+            println!(
+                "{} {} ({})",
+                format!("[{}/{}]", i + 1, inbox.len()).dimmed(),
+                doc.title().bold(),
+                path.path().to_string_lossy().bright_blue().underline()
+            );
+            let tags: Vec<String> = doc.tags().into_iter().collect();
+            if !tags.is_empty() {
+                println!("tags: {}", tags.join(", "));
+            }
+            let text = doc.stripped().unwrap_or_default();
+            let preview: String = text.split_whitespace().collect::<Vec<_>>().join(" ");
+            println!("{}", preview.chars().take(280).collect::<String>());
+            print!("[t]ag TAG, [m]ove FOLDER, [l]ink NOTE, [a]rchive, [s]kip, [q]uit > ");
+            io::stdout().flush()?;
+
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                return Ok(triaged);
+            }
+            let planned = line
+                .parse::<Action>()
+                .and_then(|action| Ok((plan(&vault, doc, &action)?, action)));
+            match planned {
+                Ok((_, Action::Quit)) => return Ok(triaged),
+                Ok((_, Action::Skip)) => break,
+                Ok((edits, _)) => {
+                    edit::commit(&edits, &vault.trash())?;
+                    edits.iter().for_each(|edit| println!("{edit}"));
+                    triaged += 1;
+                    vault = load()?;
+                    break;
+                }
+                Err(e) => eprintln!("{}", e.to_string().bright_red()),
+            }
+        }
+    }
+    Ok(triaged)
+}
@@ -0,0 +1,244 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    config::Config,
+    document::Document,
+    lock::{self, Lock, LockError},
+    vault::{Vault, VaultInitialisationError},
+};
+
+#[derive(Debug, Error)]
+pub enum IndexError {
+    #[error(transparent)]
+    ListingFailed(#[from] VaultInitialisationError),
+    #[error("failed to write the index `{path}` because {reason}")]
+    WriteFailed { path: PathBuf, reason: String },
+    #[error(transparent)]
+    Lock(#[from] LockError),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TitleEntry {
+    pub title: String,
+    /// The other names of the note, from its `aliases` attribute
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    /// The modification time of the file when it was indexed, in milliseconds since the epoch
+    modified: u64,
+}
+
+impl TitleEntry {
+    /// The modification time of the file when it was indexed, in milliseconds since the epoch
+    #[inline]
+    pub fn modified(&self) -> u64 {
+        self.modified
+    }
+}
+
+/// How up to date the title index is with the files on disk
+#[derive(Debug, Clone, Serialize)]
+pub struct IndexStatus {
+    /// The number of notes in the index
+    pub indexed: usize,
+    /// The number of indexed notes which changed or were removed since
+    pub stale: usize,
+    /// The number of notes which aren't indexed yet
+    pub missing: usize,
+    /// When the index was last written, in milliseconds since the epoch
+    pub updated: Option<u64>,
+}
+
+/// A small persistent index of the title and aliases of every note, kept in
+/// `.n/title-index.json`.
+///
+/// Commands which only need titles and paths can read this instead of building the whole `Vault`
+/// with its corpus. Only notes whose modification time changed since the last run are re-parsed.
+/// The index is shared by every run on the vault: it is written under the lock of the vault, and
+/// an index written in another format version is rebuilt rather than misread.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TitleIndex {
+    /// The format of the index, missing from the indexes written before it was versioned
+    #[serde(default)]
+    version: u32,
+    entries: BTreeMap<PathBuf, TitleEntry>,
+}
+
+impl Default for TitleIndex {
+    fn default() -> Self {
+        Self {
+            version: Self::VERSION,
+            entries: BTreeMap::new(),
+        }
+    }
+}
+
+impl Display for TitleIndex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Every name of a note gets its own line, all pointing to the same file
+        self.entries.iter().try_for_each(|(path, entry)| {
+            std::iter::once(&entry.title)
+                .chain(&entry.aliases)
+                .try_for_each(|name| writeln!(f, "{}\t{name}", path.to_string_lossy()))
+        })
+    }
+}
+
+impl TitleIndex {
+    pub const FILE: &str = "title-index.json";
+    /// Bumped whenever the format of the index changes
+    const VERSION: u32 = 1;
+
+    #[inline]
+    pub fn entries(&self) -> &BTreeMap<PathBuf, TitleEntry> {
+        &self.entries
+    }
+
+    /// The notes whose title or one of whose aliases is `name`. Names are compared exactly, or
+    /// regardless of case if no note matches exactly.
+    pub fn find(&self, name: &str) -> Vec<(&PathBuf, &TitleEntry)> {
+        let matching = |same: fn(&str, &str) -> bool| -> Vec<(&PathBuf, &TitleEntry)> {
+            self.entries
+                .iter()
+                .filter(|(_, entry)| {
+                    std::iter::once(&entry.title)
+                        .chain(&entry.aliases)
+                        .any(|other| same(other, name))
+                })
+                .collect()
+        };
+        let exact = matching(|a, b| a == b);
+        if exact.is_empty() {
+            matching(|a, b| a.to_lowercase() == b.to_lowercase())
+        } else {
+            exact
+        }
+    }
+
+    #[inline]
+    fn location(base_path: &Path) -> PathBuf {
+        base_path.join(Config::DIR).join(Self::FILE)
+    }
+
+    /// Load the index of the vault at `base_path`, bringing it up to date with the files on disk
+    /// and, if `persist` is set, saving it again if anything changed.
+    pub fn load(base_path: &Path, persist: bool) -> Result<Self, IndexError> {
+        let location = Self::location(base_path);
+        let cached = Self::read(&location);
+
+        let files: Vec<PathBuf> = Vault::walk(base_path)?
+            .into_iter()
+            .filter(|path| path.extension().is_some_and(|ext| ext == "md"))
+            .collect();
+
+        let entries: BTreeMap<PathBuf, TitleEntry> = files
+            .into_par_iter()
+            .filter_map(|path| {
+                let modified = Self::modified(&path)?;
+                match cached.entries.get(&path) {
+                    Some(entry) if entry.modified == modified => Some((path, entry.clone())),
+                    _ => {
+                        let entry = Self::entry(base_path, &path, modified)?;
+                        Some((path, entry))
+                    }
+                }
+            })
+            .collect();
+
+        let changed = entries.len() != cached.entries.len()
+            || entries.iter().any(|(path, entry)| {
+                cached
+                    .entries
+                    .get(path)
+                    .is_none_or(|e| e.modified != entry.modified)
+            });
+        let index = TitleIndex {
+            entries,
+            ..Default::default()
+        };
+        if changed && persist {
+            index.save(base_path)?;
+        }
+        Ok(index)
+    }
+
+    /// Compare the index of the vault at `base_path` with the files on disk, without updating it
+    pub fn status(base_path: &Path) -> Result<IndexStatus, IndexError> {
+        let location = Self::location(base_path);
+        let cached = Self::read(&location);
+
+        let files: BTreeMap<PathBuf, Option<u64>> = Vault::walk(base_path)?
+            .into_iter()
+            .filter(|path| path.extension().is_some_and(|ext| ext == "md"))
+            .map(|path| {
+                let modified = Self::modified(&path);
+                (path, modified)
+            })
+            .collect();
+
+        let stale = cached
+            .entries
+            .iter()
+            .filter(|(path, entry)| files.get(*path) != Some(&Some(entry.modified)))
+            .count();
+        let missing = files
+            .keys()
+            .filter(|path| !cached.entries.contains_key(*path))
+            .count();
+        Ok(IndexStatus {
+            indexed: cached.entries.len(),
+            stale,
+            missing,
+            updated: Self::modified(&location),
+        })
+    }
+
+    /// The index written at `location`. A missing or corrupted index, or one in another format,
+    /// is simply rebuilt from scratch.
+    fn read(location: &Path) -> Self {
+        fs::read_to_string(location)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .filter(|index: &TitleIndex| index.version == Self::VERSION)
+            .unwrap_or_default()
+    }
+
+    fn save(&self, base_path: &Path) -> Result<(), IndexError> {
+        let location = Self::location(base_path);
+        let _lock = Lock::acquire(base_path)?;
+        let write = || -> io::Result<()> {
+            if let Some(parent) = location.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            lock::write_atomically(&location, &serde_json::to_string(self)?)
+        };
+        write().map_err(|e| IndexError::WriteFailed {
+            path: location.clone(),
+            reason: e.to_string(),
+        })
+    }
+
+    fn modified(path: &Path) -> Option<u64> {
+        let modified = fs::metadata(path).ok()?.modified().ok()?;
+        Some(modified.duration_since(UNIX_EPOCH).ok()?.as_millis() as u64)
+    }
+
+    /// The title of a note is its `title` attribute, falling back to its file name
+    fn entry(base_path: &Path, path: &Path, modified: u64) -> Option<TitleEntry> {
+        let document = Document::new(base_path.to_path_buf(), path.to_path_buf()).ok()?;
+        Some(TitleEntry {
+            title: document.title(),
+            aliases: document.aliases(),
+            modified,
+        })
+    }
+}
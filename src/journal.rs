@@ -0,0 +1,71 @@
+use std::{fs, io, path::PathBuf};
+
+use thiserror::Error;
+
+use crate::{
+    date::Date,
+    template::{Template, TemplateError},
+    vault::Vault,
+};
+
+/// What daily notes hold when the vault has no daily template
+const DEFAULT_TEMPLATE: &str = "---\ntitle: {{title}}\ndate: {{date}}\n---\n";
+
+/// The template daily notes are rendered from when none is configured, if the vault has it
+const DAILY_TEMPLATE: &str = "daily";
+
+#[derive(Debug, Error)]
+pub enum JournalError {
+    #[error(transparent)]
+    Template(#[from] TemplateError),
+    #[error("there is no daily note for {0} yet, and the vault is opened read-only")]
+    ReadOnly(Date),
+    #[error("failed to write the daily note `{path}` because {reason}")]
+    WriteFailed { path: PathBuf, reason: String },
+}
+
+/// The path of the daily note of the given date. If the vault has none yet and `create` is set, it
+/// is created where `journal.path` says, from the daily template.
+///
+/// The template is given the variables `date`, `title`, `year`, `month` and `day`.
+pub fn open(vault: &Vault, date: Date, create: bool) -> Result<PathBuf, JournalError> {
+    if let Some(note) = vault.daily_note(date) {
+        return Ok(note.path().path());
+    }
+    let path = vault.path().join(date.format(&vault.config().journal.path));
+    // The note may have been created since the vault was read
+    if path.is_file() {
+        return Ok(path);
+    }
+    if !create {
+        return Err(JournalError::ReadOnly(date));
+    }
+
+    let dirs = vault.template_dirs();
+    let name = match &vault.config().journal.template {
+        Some(name) => Some(name.as_str()),
+        None => Template::locate(DAILY_TEMPLATE, &dirs).map(|_| DAILY_TEMPLATE),
+    };
+    let text = match name {
+        Some(name) => Template::expand(&format!("{{{{> {name}}}}}\n"), &dirs)?,
+        None => DEFAULT_TEMPLATE.to_string(),
+    };
+    let variables = format!(
+        "date:{date},title:{date},year:{:04},month:{:02},day:{:02}",
+        date.year, date.month, date.day
+    );
+    let template = Template::new(text, Some(variables));
+    template.validate()?;
+
+    let write = || -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        template.write(&path)
+    };
+    write().map_err(|e| JournalError::WriteFailed {
+        path: path.clone(),
+        reason: e.to_string(),
+    })?;
+    Ok(path)
+}
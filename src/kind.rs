@@ -0,0 +1,77 @@
+use std::{fmt::Display, path::Path, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{config::Config, document::Document};
+
+#[derive(Debug, Error)]
+#[error("`{kind}` is not a valid kind of note; expected one of regular, template, daily, archive")]
+pub struct InvalidKind {
+    kind: String,
+}
+
+/// What a note is used for. Anything other than `Regular` is excluded from the subcommands by
+/// default, since e.g. templates full of `{{placeholders}}` only pollute the results.
+#[derive(
+    Debug, Clone, Copy, Serialize, Deserialize, Hash, PartialEq, Eq, PartialOrd, Ord, Default,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    #[default]
+    Regular,
+    Template,
+    Daily,
+    Archive,
+}
+
+impl Kind {
+    pub const ALL: [Kind; 4] = [Kind::Regular, Kind::Template, Kind::Daily, Kind::Archive];
+
+    /// Infer the kind of a document, either from its `kind` frontmatter attribute, or otherwise
+    /// from the folder it lives in, relative to the root of the vault.
+    pub fn infer(document: &Document, base_path: &Path, config: &Config) -> Kind {
+        if let Some(kind) = document
+            .get_metadata(&"kind".to_string())
+            .and_then(|kind| kind.to_string().parse().ok())
+        {
+            return kind;
+        }
+
+        let path = document.path().path();
+        let relative = path.strip_prefix(base_path).unwrap_or(&path);
+        config
+            .kinds
+            .iter()
+            .find(|(_, folders)| folders.iter().any(|folder| relative.starts_with(folder)))
+            .map_or(Kind::Regular, |(kind, _)| *kind)
+    }
+}
+
+impl FromStr for Kind {
+    type Err = InvalidKind;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "regular" | "note" => Ok(Kind::Regular),
+            "template" | "templates" => Ok(Kind::Template),
+            "daily" | "journal" => Ok(Kind::Daily),
+            "archive" | "archived" => Ok(Kind::Archive),
+            _ => Err(InvalidKind {
+                kind: s.to_string(),
+            }),
+        }
+    }
+}
+
+impl Display for Kind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self {
+            Kind::Regular => "regular",
+            Kind::Template => "template",
+            Kind::Daily => "daily",
+            Kind::Archive => "archive",
+        };
+        write!(f, "{kind}")
+    }
+}
@@ -1,40 +1,65 @@
-use std::{
-    fmt::Display,
-    path::{Path, PathBuf},
-};
+use std::{fmt::Display, ops::Range, path::PathBuf};
 
 use owo_colors::OwoColorize;
 use percent_encoding::percent_decode_str;
 use serde::Serialize;
 
-use crate::path::MarkdownPath;
+use crate::{path::MarkdownPath, pos::Pos};
 
 #[derive(Debug, Serialize, Clone, Hash, PartialEq, Eq)]
 /// A link in a Markdown file
 pub struct Link {
     pub text: String,
     pub url: String,
+    pub pos: Pos,
+    /// The byte range of the whole link, including its text, in the source file
+    pub range: Range<usize>,
 }
 
 impl Link {
-    /// Check if the link points to the given Markdown document
-    pub fn points_to(&self, target: &MarkdownPath) -> bool {
-        if let Some(path) = self.to_markdown_path(
-            target
-                .path()
-                .parent()
-                .unwrap_or_else(|| Path::new(""))
-                .to_path_buf(),
-        ) {
-            return &path == target;
+    /// Check if the link, found in a document inside of `base_path`, points to the given Markdown
+    /// document
+    pub fn points_to(&self, base_path: PathBuf, target: &MarkdownPath) -> bool {
+        self.to_markdown_path(base_path)
+            .is_some_and(|path| &path == target)
+    }
+
+    /// Whether the link points outside of the vault, e.g. to a website
+    #[inline]
+    pub fn is_external(&self) -> bool {
+        url::Url::parse(self.url.as_str()).is_ok()
+    }
+
+    /// The part of the URL before the `#anchor`, if any
+    #[inline]
+    pub fn target(&self) -> &str {
+        self.url
+            .split_once('#')
+            .map_or(self.url.as_str(), |(target, _)| target)
+    }
+
+    /// The `#anchor` of the URL, usually the slug of a heading in the target document
+    #[inline]
+    pub fn anchor(&self) -> Option<&str> {
+        self.url.split_once('#').map(|(_, anchor)| anchor)
+    }
+
+    /// The byte range of the part of the URL before the `#anchor` in the source file, if it has
+    /// any, i.e. the part which changes when the target is moved
+    pub fn target_range(&self, contents: &str) -> Option<Range<usize>> {
+        let target = self.target();
+        if target.is_empty() {
+            return None;
         }
-        false
+        let offset = contents.get(self.range.clone())?.rfind(target)?;
+        let start = self.range.start + offset;
+        Some(start..start + target.len())
     }
 
     #[inline]
     pub fn to_markdown_path(&self, base_path: PathBuf) -> Option<MarkdownPath> {
         if let Err(url::ParseError::RelativeUrlWithoutBase) = url::Url::parse(self.url.as_str()) {
-            MarkdownPath::new(base_path, PathBuf::from(self.url.clone())).ok()
+            MarkdownPath::new(base_path, PathBuf::from(self.target())).ok()
         } else {
             None
         }
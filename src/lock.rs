@@ -0,0 +1,95 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use thiserror::Error;
+
+use crate::config::Config;
+
+#[derive(Debug, Error)]
+pub enum LockError {
+    #[error("failed to lock `{path}` because {reason}")]
+    Io { path: PathBuf, reason: String },
+    #[error("`{path}` is held by another run of n; remove it if no other run is going on anymore")]
+    Timeout { path: PathBuf },
+}
+
+/// An exclusive lock on the state `n` keeps in the `.n` folder of a vault, such as its index, so
+/// that several runs on the same vault don't write over each other. The lock is held for as long
+/// as the value lives.
+#[derive(Debug)]
+pub struct Lock {
+    path: PathBuf,
+}
+
+impl Lock {
+    const FILE: &str = "lock";
+    /// How long to wait for another run to release the lock
+    const TIMEOUT: Duration = Duration::from_secs(10);
+    /// How old a lock must be to be taken as left behind by a run which was killed. Runs only hold
+    /// the lock while writing, which takes far less.
+    const STALE: Duration = Duration::from_secs(60);
+
+    /// Wait for the lock of the vault at `base_path` to be free, and take it
+    pub fn acquire(base_path: &Path) -> Result<Self, LockError> {
+        let dir = base_path.join(Config::DIR);
+        let path = dir.join(Self::FILE);
+        let io_error = |e: io::Error| LockError::Io {
+            path: path.clone(),
+            reason: e.to_string(),
+        };
+        fs::create_dir_all(&dir).map_err(io_error)?;
+
+        let start = Instant::now();
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    // Only there to tell who holds the lock
+                    writeln!(file, "{}", std::process::id()).map_err(io_error)?;
+                    return Ok(Self { path });
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    if Self::is_stale(&path) {
+                        // Another run may have cleared it first
+                        _ = fs::remove_file(&path);
+                    } else if start.elapsed() >= Self::TIMEOUT {
+                        return Err(LockError::Timeout { path });
+                    } else {
+                        thread::sleep(Duration::from_millis(20));
+                    }
+                }
+                Err(e) => return Err(io_error(e)),
+            }
+        }
+    }
+
+    fn is_stale(path: &Path) -> bool {
+        fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .is_some_and(|age| age > Self::STALE)
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Replace the file at `path` with the given contents, so that readers see either the old or the
+/// new contents in full, never a partial write
+pub fn write_atomically(path: &Path, contents: &str) -> io::Result<()> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(format!(".{}.tmp", std::process::id()));
+    let temporary = PathBuf::from(temporary);
+    fs::write(&temporary, contents)?;
+    fs::rename(&temporary, path).inspect_err(|_| {
+        _ = fs::remove_file(&temporary);
+    })
+}
@@ -1,99 +1,188 @@
+mod bundle;
+mod check;
 mod cli;
+mod config;
+mod date;
 mod document;
+mod edit;
+mod fmt;
+mod graph;
+mod heading;
+mod hierarchy;
+mod html;
+mod inbox;
+mod index;
+mod journal;
+mod kind;
 mod link;
+mod lock;
 mod path;
+mod pos;
 mod query;
 mod rank;
+mod related;
 mod search;
+mod serve;
+mod stats;
+mod status;
+mod tag;
+mod task;
 mod template;
+#[cfg(test)]
+mod testing;
+mod timings;
+mod tokenize;
+mod trash;
 mod vault;
+mod watch;
 
-use std::collections::HashMap;
+use std::{
+    cell::LazyCell,
+    collections::{BTreeMap, BTreeSet},
+};
 
-use rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
+use rand::seq::IndexedRandom;
+use rayon::iter::{IntoParallelIterator, IntoParallelRefMutIterator, ParallelIterator};
 use serde::Serialize;
 
 use crate::{
-    cli::{Args, Subcommand},
+    bundle::Bundle,
+    check::{Issue, Severity},
+    cli::{Args, Format, Grouping, Subcommand, View},
+    date::Date,
     document::Document,
+    edit::FileEdit,
+    graph::{Colouring, Graph},
+    heading::Heading,
+    hierarchy::Hierarchy,
+    index::{TitleEntry, TitleIndex},
+    kind::Kind,
     path::MarkdownPath,
+    pos::Pos,
     query::Query,
-    rank::rank,
-    vault::Vault,
+    rank::{MAX_ITER, TOLERANCE, rank, score_hits},
+    search::Corpus,
+    stats::{Snapshot, Stats},
+    status::Status,
+    task::Task,
+    template::Template,
+    timings::Timings,
+    vault::{DocId, Vault},
 };
 
 pub const MAX_RESULTS: usize = 10;
 
 fn main() {
-    let args = Args::parse().unwrap();
-    let vault = Vault::new(args.vault_dir.clone()).unwrap();
-    const MAX_ITER: usize = 100_000;
-    const TOLERANCE: f32 = 0.0000001;
+    let args = Args::parse().unwrap_or_else(|e| {
+        eprintln!("error: {e}");
+        std::process::exit(2);
+    });
+    // Only build the vault, along with its corpus, for the subcommands that actually need it
+    let vault_dir = args.vault_dir.clone();
+    let include_drafts = args.include_drafts;
+    let vault = LazyCell::new(move || {
+        let mut vault = Vault::new(vault_dir).unwrap();
+        vault.set_include_drafts(include_drafts);
+        vault
+    });
+    let mut timings = Timings::default();
     // TODO: Pretty-print the results
     match args.subcommand {
-        Subcommand::New { template, path } => {
+        Subcommand::New { mut template, path } => {
             let path = vault.path().join(format!("{path}.md"));
+            template.resolve(&vault.template_dirs()).unwrap();
+            template.validate().unwrap();
             template.write(&path).unwrap();
             println!("{}", path.to_string_lossy());
         }
-        Subcommand::Search(query) => {
-            let bm25: Vec<(Document, f32)> = vault
-                .search(query)
-                .into_par_iter()
+        Subcommand::Journal(date) => {
+            let date = date.unwrap_or_else(Date::today);
+            let path = journal::open(&vault, date, !args.read_only).unwrap();
+            println!("{}", path.to_string_lossy());
+        }
+        Subcommand::Search { query, sections } => {
+            // Load the vault first, so that it isn't timed as part of the query
+            let vault: &Vault = &vault;
+            let tokenizer = vault.config().tokenizer.get();
+            let terms: Vec<String> = tokenizer.tokenize(&query);
+            let bm25 = timings.time("query", || {
+                if sections {
+                    vault.search_sections(&query)
+                } else {
+                    let scores = vault.search(&query).into_iter();
+                    scores.map(|(id, score)| (id, None, score)).collect()
+                }
+            });
+            let bm25: Vec<(DocId, Option<Heading>, f32)> = bm25
+                .into_iter()
                 // We don't care about documents with no matches.
-                .filter(|(_, score)| score > &0f32)
-                .collect();
-            let matches: Vec<&Document> = bm25.iter().map(|(doc, _)| doc).collect();
-
-            let rank: HashMap<Document, f32> = matches
-                .iter()
-                .zip(rank(matches.clone(), vault.path(), MAX_ITER, TOLERANCE))
-                .map(|(k, v)| ((**k).clone(), v))
+                .filter(|(_, _, score)| score > &0f32)
+                .filter(|(id, _, _)| args.kinds.contains(&vault.document(*id).kind()))
                 .collect();
-
-            // How much should the BM25 score count over the PageRank score?
-            let factor = 0.7f32;
+            let hits = timings.time("rank", || {
+                score_hits(vault, bm25, args.rank_mode, MAX_ITER, TOLERANCE)
+            });
 
             #[derive(Serialize)]
             /// Label the results in the JSON output
-            struct SearchResult {
-                document: Document,
+            struct SearchResult<'a> {
+                document: &'a Document,
+                /// The heading of the matching section, when searching by section
+                #[serde(skip_serializing_if = "Option::is_none")]
+                section: Option<Heading>,
                 bm25: f32,
                 rank: f32,
                 combined: f32,
+                /// Where the first hit is in the source of the document
+                position: Option<Pos>,
             }
 
-            // Adjust the score to incorporate the pagerank score
-            let mut res: Vec<SearchResult> = bm25
+            let mut res: Vec<SearchResult> = hits
                 .into_iter()
-                .map(|(doc, bm25)| {
-                    let rank = rank.get(&doc).unwrap();
-                    SearchResult {
-                        document: doc.clone(),
-                        bm25,
-                        rank: rank.to_owned(),
-                        combined: (factor * bm25) + ((1f32 - factor) * rank),
-                    }
+                .take(MAX_RESULTS)
+                .map(|hit| SearchResult {
+                    document: vault.document(hit.id),
+                    section: hit.section,
+                    bm25: hit.bm25,
+                    rank: hit.rank,
+                    combined: hit.combined,
+                    position: None,
                 })
                 .collect();
-
-            res.sort_unstable_by(|a, b| {
-                b.combined
-                    .partial_cmp(&a.combined)
-                    .unwrap_or(std::cmp::Ordering::Greater)
+            // Only locate the hits of the results we actually show
+            let terms: Vec<&str> = terms.iter().map(String::as_str).collect();
+            res.par_iter_mut().for_each(|result| {
+                result.position = match &result.section {
+                    Some(heading) => Some(heading.pos),
+                    None => result.document.find_terms(&terms, tokenizer).ok().flatten(),
+                };
             });
-            res.truncate(MAX_RESULTS);
-            if args.json {
+            if args.format != Format::Table {
+                print_column(res.iter().map(|result| result.document), args.format);
+            } else if args.json {
                 println!("{}", serde_json::to_string(&res).unwrap());
             } else {
-                let res: Vec<(String, f32, f32, f32)> = res
+                let root = vault.path().canonicalize().unwrap_or_else(|_| vault.path());
+                let res: Vec<(String, String, f32, f32, f32)> = res
                     .into_iter()
                     .map(|result| {
                         (
+                            if sections {
+                                // Point to the section the same way a link to it would
+                                let path = result.document.path().link_from(&root);
+                                match &result.section {
+                                    Some(heading) => format!("{path}#{}", heading.slug()),
+                                    None => path,
+                                }
+                            } else {
+                                result
+                                    .document
+                                    .get_metadata(&"title".to_string())
+                                    .map_or_else(|| "".to_string(), |res| res.to_string())
+                            },
                             result
-                                .document
-                                .get_metadata(&"title".to_string())
-                                .map_or_else(|| "".to_string(), |res| res.to_string()),
+                                .position
+                                .map_or_else(String::new, |pos| pos.to_string()),
                             result.bm25,
                             result.rank,
                             result.combined,
@@ -101,47 +190,117 @@ fn main() {
                     })
                     .collect();
                 let mut builder = tabled::builder::Builder::new();
-                builder.push_record(["Title", "BM25", "Rank", "Score"]);
-                res.iter().for_each(|(title, bm25, rank, combined)| {
-                    builder.push_record([
-                        title,
-                        &bm25.to_string(),
-                        &rank.to_string(),
-                        &combined.to_string(),
-                    ])
-                });
+                let label = if sections { "Section" } else { "Title" };
+                builder.push_record([label, "Position", "BM25", "Rank", "Score"]);
+                res.iter()
+                    .for_each(|(title, position, bm25, rank, combined)| {
+                        builder.push_record([
+                            title,
+                            position,
+                            &bm25.to_string(),
+                            &rank.to_string(),
+                            &combined.to_string(),
+                        ])
+                    });
                 let mut table = builder.build();
                 table.with(tabled::settings::style::Style::rounded());
                 println!("{table}");
             }
         }
         Subcommand::Query(query) => {
-            let parsed_query = Query::parse(query.as_str()).unwrap();
-            let results = vault.query(parsed_query);
-            results
-                .par_iter()
-                .filter_map(|doc| doc.get_metadata(&"title".to_string()))
-                .for_each(|title| println!("{title}"));
-        }
-        Subcommand::Inspect(path) => {
+            let vault: &Vault = &vault;
+            let parsed_query = parse_query(&query);
+            let results = timings.time("query", || vault.query(parsed_query));
+            let results = results
+                .into_iter()
+                .filter(|doc| args.kinds.contains(&doc.kind()));
+            if args.format != Format::Table {
+                print_column(results, args.format);
+            } else {
+                results
+                    .filter_map(|doc| doc.get_metadata(&"title".to_string()))
+                    .for_each(|title| println!("{title}"));
+            }
+        }
+        Subcommand::QueryMany(queries) => {
+            #[derive(Serialize)]
+            /// Label the results of each query in the JSON output
+            struct QueryResult<'a> {
+                query: &'a str,
+                paths: Vec<MarkdownPath>,
+            }
+
+            let vault: &Vault = &vault;
+            let parsed: Vec<Query> = queries.iter().map(|query| parse_query(query)).collect();
+            let results = timings.time("query", || vault.query_many(&parsed));
+            let results: Vec<(&String, Vec<&Document>)> = queries
+                .iter()
+                .zip(results)
+                .map(|(query, docs)| {
+                    let docs = docs
+                        .into_iter()
+                        .filter(|doc| args.kinds.contains(&doc.kind()))
+                        .collect();
+                    (query, docs)
+                })
+                .collect();
+
+            if args.json {
+                let results: Vec<QueryResult> = results
+                    .iter()
+                    .map(|(query, docs)| QueryResult {
+                        query,
+                        paths: docs.iter().map(|doc| doc.path()).collect(),
+                    })
+                    .collect();
+                println!("{}", serde_json::to_string(&results).unwrap());
+            } else {
+                for (i, (query, docs)) in results.into_iter().enumerate() {
+                    if i > 0 {
+                        println!();
+                    }
+                    println!("{query}");
+                    print_column(docs.into_iter(), args.format);
+                }
+            }
+        }
+        Subcommand::Inspect { path, view } => {
             let base_path = args.vault_dir;
 
             match path {
                 Some(path) => {
                     let full_path = MarkdownPath::new(base_path, path).unwrap();
                     let document = vault.get_document(&full_path).unwrap();
-                    if args.json {
-                        println!("{}", serde_json::to_string(document).unwrap());
-                    } else {
-                        println!("{document}");
+                    // Print the raw text as is, so that it can be piped into other tools
+                    let text = match view {
+                        View::Summary => None,
+                        View::Content => Some(document.body().unwrap()),
+                        View::Stripped => Some(document.stripped().unwrap()),
+                        View::Section(heading) => match document.section(&heading).unwrap() {
+                            Some(section) => Some(section),
+                            None => {
+                                eprintln!("error: the note has no section `{heading}`");
+                                std::process::exit(1);
+                            }
+                        },
+                    };
+                    match text {
+                        Some(text) if args.json => {
+                            println!("{}", serde_json::to_string(&text).unwrap())
+                        }
+                        Some(text) => print!("{text}"),
+                        None if args.json => {
+                            println!("{}", serde_json::to_string(document).unwrap())
+                        }
+                        None => println!("{document}"),
                     }
                 }
                 // Print out the whole vault if no arguments are provided
                 None => {
                     if args.json {
-                        println!("{}", serde_json::to_string(&vault).unwrap());
+                        println!("{}", serde_json::to_string(&*vault).unwrap());
                     } else {
-                        println!("{vault}");
+                        println!("{}", *vault);
                     }
                 }
             }
@@ -175,18 +334,748 @@ fn main() {
                 println!("{links:?}");
             }
         }
-        Subcommand::List => {
-            let mut res: Vec<(Document, f32)> = vault
+        Subcommand::Move { path, to, dry_run } => {
+            let full_path = MarkdownPath::new(args.vault_dir.clone(), path).unwrap();
+            let mut to = args.vault_dir.join(&to);
+            // A folder keeps the name of the file
+            if to.is_dir() || to.extension().is_none_or(|ext| ext != "md") {
+                to.push(full_path.path().file_name().unwrap_or_default());
+            }
+            let edits = vault.move_document(&full_path, to).unwrap();
+            if !dry_run {
+                edit::commit(&edits, &vault.trash()).unwrap();
+            }
+            print_edits(&edits, args.json);
+        }
+        Subcommand::Rename {
+            path,
+            title,
+            dry_run,
+        } => {
+            let full_path = MarkdownPath::new(args.vault_dir, path).unwrap();
+            let edits = vault.rename_document(&full_path, &title).unwrap();
+            if !dry_run {
+                edit::commit(&edits, &vault.trash()).unwrap();
+            }
+            print_edits(&edits, args.json);
+        }
+        Subcommand::RenameHeading {
+            path,
+            old,
+            new,
+            dry_run,
+        } => {
+            let full_path = MarkdownPath::new(args.vault_dir, path).unwrap();
+            let edits = vault.rename_heading(&full_path, &old, &new).unwrap();
+            if !dry_run {
+                edit::commit(&edits, &vault.trash()).unwrap();
+            }
+            print_edits(&edits, args.json);
+        }
+        Subcommand::LinkDates(path) => {
+            let path = path.map(|path| MarkdownPath::new(args.vault_dir, path).unwrap());
+            let edits = vault.link_dates(path.as_ref()).unwrap();
+            edit::commit(&edits, &vault.trash()).unwrap();
+            print_edits(&edits, args.json);
+        }
+        Subcommand::RenameTag { old, new, merge } => {
+            let edits = vault.rename_tag(&old, &new, merge).unwrap();
+            edit::commit(&edits, &vault.trash()).unwrap();
+            print_edits(&edits, args.json);
+        }
+        Subcommand::Tags { fix: true, .. } => {
+            let vault: &Vault = &vault;
+            let edits: Vec<FileEdit> = vault
+                .documents()
+                .into_par_iter()
+                .map(|doc| vault.canonicalise_tags(doc).unwrap())
+                .filter(|edit| !edit.edits.is_empty())
+                .collect();
+            edit::commit(&edits, &vault.trash()).unwrap();
+            print_edits(&edits, args.json);
+        }
+        Subcommand::Tags { name: None, .. } => {
+            #[derive(Serialize)]
+            /// Label the tags in the JSON output
+            struct TagCount {
+                tag: String,
+                notes: usize,
+            }
+
+            let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+            vault
+                .documents()
+                .into_iter()
+                .filter(|doc| args.kinds.contains(&doc.kind()))
+                .flat_map(|doc| doc.tags())
+                .for_each(|tag| *counts.entry(tag).or_default() += 1);
+            let mut tags: Vec<TagCount> = counts
+                .into_iter()
+                .map(|(tag, notes)| TagCount { tag, notes })
+                .collect();
+            tags.sort_by(|a, b| b.notes.cmp(&a.notes).then_with(|| a.tag.cmp(&b.tag)));
+
+            if args.json {
+                println!("{}", serde_json::to_string(&tags).unwrap());
+            } else {
+                let mut builder = tabled::builder::Builder::new();
+                builder.push_record(["Tag", "Notes"]);
+                tags.iter().for_each(|tag| {
+                    builder.push_record([format!("#{}", tag.tag), tag.notes.to_string()])
+                });
+                let mut table = builder.build();
+                table.with(tabled::settings::style::Style::rounded());
+                println!("{table}");
+            }
+        }
+        Subcommand::Tags {
+            name: Some(name), ..
+        } => {
+            let name = vault.config().tags.normalise(&name);
+            let documents: Vec<&Document> = vault
+                .documents()
+                .into_iter()
+                .filter(|doc| args.kinds.contains(&doc.kind()))
+                .filter(|doc| doc.tags().iter().any(|tag| tag::is_within(tag, &name)))
+                .collect();
+            print_documents(&documents, args.format, args.json);
+        }
+        Subcommand::Tasks { query, open, done } => {
+            #[derive(Serialize)]
+            /// Label the tasks in the JSON output
+            struct NoteTask {
+                path: MarkdownPath,
+                title: String,
+                #[serde(flatten)]
+                task: Task,
+            }
+
+            let documents = match query {
+                Some(query) => vault.query(parse_query(&query)),
+                None => vault.documents(),
+            };
+            let mut tasks: Vec<NoteTask> = documents
+                .into_iter()
+                .filter(|doc| args.kinds.contains(&doc.kind()))
+                .flat_map(|doc| {
+                    doc.tasks().into_iter().map(|task| NoteTask {
+                        path: doc.path(),
+                        title: doc.title(),
+                        task,
+                    })
+                })
+                .filter(|note_task| if note_task.task.done { done } else { open })
+                .collect();
+            tasks.sort_by(|a, b| a.path.cmp(&b.path).then(a.task.pos.cmp(&b.task.pos)));
+
+            let checkbox = |task: &Task| if task.done { "[x]" } else { "[ ]" };
+            match args.format {
+                // Like compiler diagnostics, so that editors can jump to the tasks
+                Format::Path => tasks.iter().for_each(|t| {
+                    println!(
+                        "{}:{}: {} {}",
+                        t.path.path().to_string_lossy(),
+                        t.task.pos,
+                        checkbox(&t.task),
+                        t.task.text
+                    )
+                }),
+                Format::Title => tasks
+                    .iter()
+                    .for_each(|t| println!("{}: {} {}", t.title, checkbox(&t.task), t.task.text)),
+                _ if args.json => println!("{}", serde_json::to_string(&tasks).unwrap()),
+                _ => {
+                    let mut builder = tabled::builder::Builder::new();
+                    builder.push_record(["Path", "Line", "Heading", "Task"]);
+                    tasks.iter().for_each(|t| {
+                        builder.push_record([
+                            t.path.path().to_string_lossy().to_string(),
+                            (t.task.pos.line + 1).to_string(),
+                            t.task.heading.clone().unwrap_or_default(),
+                            format!("{} {}", checkbox(&t.task), t.task.text),
+                        ])
+                    });
+                    let mut table = builder.build();
+                    table.with(tabled::settings::style::Style::rounded());
+                    println!("{table}");
+                }
+            }
+        }
+        Subcommand::TrashList => {
+            let batches = vault.trash().batches().unwrap();
+            if args.json {
+                println!("{}", serde_json::to_string(&batches).unwrap());
+            } else {
+                let mut builder = tabled::builder::Builder::new();
+                builder.push_record(["ID", "Trashed (UTC)", "Files"]);
+                batches.iter().rev().for_each(|batch| {
+                    let files: Vec<String> = batch
+                        .entries
+                        .iter()
+                        .map(|entry| match &entry.moved_to {
+                            Some(to) => format!(
+                                "{} → {}",
+                                entry.path.to_string_lossy(),
+                                to.to_string_lossy()
+                            ),
+                            None => entry.path.to_string_lossy().to_string(),
+                        })
+                        .collect();
+                    builder.push_record([
+                        batch.id.clone(),
+                        timestamp(batch.trashed),
+                        files.join("\n"),
+                    ])
+                });
+                let mut table = builder.build();
+                table.with(tabled::settings::style::Style::rounded());
+                println!("{table}");
+            }
+        }
+        Subcommand::TrashRestore(id) => {
+            let batch = vault.trash().restore(id.as_deref()).unwrap();
+            if args.json {
+                println!("{}", serde_json::to_string(&batch).unwrap());
+            } else {
+                batch
+                    .entries
+                    .iter()
+                    .for_each(|entry| println!("{}", entry.path.to_string_lossy()));
+            }
+        }
+        Subcommand::Recent { limit } => {
+            #[derive(Serialize)]
+            /// Label the notes in the JSON output
+            struct RecentNote<'a> {
+                #[serde(skip)]
+                document: &'a Document,
+                path: MarkdownPath,
+                title: String,
+                /// In milliseconds since the epoch
+                modified: Option<u64>,
+            }
+
+            let mut notes: Vec<RecentNote> = vault
+                .documents()
+                .into_iter()
+                .filter(|doc| args.kinds.contains(&doc.kind()))
+                .map(|doc| RecentNote {
+                    document: doc,
+                    path: doc.path(),
+                    title: doc.title(),
+                    modified: doc.modified(),
+                })
+                .collect();
+            // Notes which can't be dated come last
+            notes.sort_by(|a, b| {
+                b.modified
+                    .cmp(&a.modified)
+                    .then_with(|| a.path.cmp(&b.path))
+            });
+            notes.truncate(limit);
+
+            if args.format != Format::Table {
+                print_column(notes.iter().map(|note| note.document), args.format);
+            } else if args.json {
+                println!("{}", serde_json::to_string(&notes).unwrap());
+            } else {
+                let mut builder = tabled::builder::Builder::new();
+                builder.push_record(["Modified (UTC)", "Path", "Title"]);
+                notes.iter().for_each(|note| {
+                    let modified = note.modified.map_or_else(String::new, timestamp);
+                    builder.push_record([
+                        modified,
+                        note.path.path().to_string_lossy().to_string(),
+                        note.title.clone(),
+                    ])
+                });
+                let mut table = builder.build();
+                table.with(tabled::settings::style::Style::rounded());
+                println!("{table}");
+            }
+        }
+        Subcommand::Random { query, count } => {
+            let candidates = match query {
+                Some(query) => vault.query(parse_query(&query)),
+                None => vault.documents(),
+            };
+            let candidates: Vec<&Document> = candidates
+                .into_iter()
+                .filter(|doc| args.kinds.contains(&doc.kind()))
+                .collect();
+            // Every note is drawn at most once
+            let sample: Vec<&Document> = candidates
+                .choose_multiple(&mut rand::rng(), count)
+                .copied()
+                .collect();
+            print_documents(&sample, args.format, args.json);
+        }
+        Subcommand::PublishCheck(query) => {
+            let query = parse_query(&query);
+            let documents = vault.query(query);
+            let issues = check::publish(&vault, &documents);
+            print_issues(&issues, args.json);
+        }
+        Subcommand::Fmt { path, check } => {
+            let vault: &Vault = &vault;
+            let documents = match path {
+                Some(path) => {
+                    let full_path = MarkdownPath::new(args.vault_dir, path).unwrap();
+                    vec![vault.get_document(&full_path).unwrap()]
+                }
+                None => vault.documents(),
+            };
+            let edits: Vec<FileEdit> = documents
+                .into_par_iter()
+                .map(|doc| fmt::format(vault, doc).unwrap())
+                .filter(|edit| !edit.edits.is_empty())
+                .collect();
+            if !check {
+                edit::commit(&edits, &vault.trash()).unwrap();
+            }
+            print_edits(&edits, args.json);
+            if check && !edits.is_empty() {
+                std::process::exit(1);
+            }
+        }
+        Subcommand::Toc { path, insert } => {
+            let full_path = MarkdownPath::new(args.vault_dir, path).unwrap();
+            let doc = vault.get_document(&full_path).unwrap();
+            if insert {
+                let edits: Vec<FileEdit> = std::iter::once(vault.toc_section(doc).unwrap())
+                    .filter(|edit| !edit.edits.is_empty())
+                    .collect();
+                edit::commit(&edits, &vault.trash()).unwrap();
+                print_edits(&edits, args.json);
+            } else if args.json {
+                println!("{}", serde_json::to_string(&doc.headings()).unwrap());
+            } else {
+                doc.headings()
+                    .iter()
+                    .for_each(|heading| println!("{heading}"));
+            }
+        }
+        Subcommand::InsertBacklinks(path) => {
+            let vault: &Vault = &vault;
+            let documents = match path {
+                Some(path) => {
+                    let full_path = MarkdownPath::new(args.vault_dir, path).unwrap();
+                    vec![vault.get_document(&full_path).unwrap()]
+                }
+                None => vault
+                    .documents()
+                    .into_iter()
+                    .filter(|doc| args.kinds.contains(&doc.kind()))
+                    .collect(),
+            };
+            let edits: Vec<FileEdit> = documents
+                .into_par_iter()
+                .map(|doc| vault.backlinks_section(doc).unwrap())
+                .filter(|edit| !edit.edits.is_empty())
+                .collect();
+            edit::commit(&edits, &vault.trash()).unwrap();
+            print_edits(&edits, args.json);
+        }
+        Subcommand::Inbox => {
+            let stdin = std::io::stdin().lock();
+            let triaged = inbox::triage(args.vault_dir, args.include_drafts, stdin).unwrap();
+            println!("triaged {triaged} notes");
+        }
+        Subcommand::Related { path, method } => {
+            let full_path = MarkdownPath::new(args.vault_dir, path).unwrap();
+            let id = vault
+                .id(&full_path)
+                .ok_or_else(|| format!("`{}` is not a note in the vault", full_path))
+                .unwrap();
+            let mut res: Vec<(&Document, usize)> = related::related(&vault, id, method)
+                .into_iter()
+                .map(|(id, count)| (vault.document(id), count))
+                .filter(|(doc, _)| args.kinds.contains(&doc.kind()))
+                .collect();
+            res.truncate(MAX_RESULTS);
+
+            if args.format != Format::Table {
+                print_column(res.iter().map(|(doc, _)| *doc), args.format);
+            } else if args.json {
+                println!("{}", serde_json::to_string(&res).unwrap());
+            } else {
+                let mut builder = tabled::builder::Builder::new();
+                builder.push_record(["Title", "Shared links"]);
+                res.iter()
+                    .for_each(|(doc, count)| builder.push_record([doc.title(), count.to_string()]));
+                let mut table = builder.build();
+                table.with(tabled::settings::style::Style::rounded());
+                println!("{table}");
+            }
+        }
+        Subcommand::SchemaCheck => {
+            let documents: Vec<&Document> = vault
                 .documents()
                 .into_iter()
-                .zip(rank(vault.documents(), vault.path(), MAX_ITER, TOLERANCE))
-                .map(|(k, v)| (k.to_owned(), v))
+                .filter(|doc| args.kinds.contains(&doc.kind()))
                 .collect();
-            res.sort_unstable_by(|a, b| {
-                b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Greater)
+            let issues = check::verify_schema(&documents, &vault.config().schema);
+            print_issues(&issues, args.json);
+        }
+        Subcommand::Doctor => {
+            let documents: Vec<&Document> = vault
+                .documents()
+                .into_iter()
+                .filter(|doc| args.kinds.contains(&doc.kind()))
+                .collect();
+            let files = Vault::walk(&vault.path()).unwrap();
+            let issues = check::doctor(&vault, &documents, &files, &vault.config().doctor);
+            print_issues(&issues, args.json);
+        }
+        Subcommand::Watch {
+            interval,
+            exec,
+            query,
+        } => {
+            let query = query.map(|query| parse_query(&query));
+            let followed = query.as_ref().map(|query| watch::Followed {
+                query,
+                kinds: &args.kinds,
+                include_drafts: args.include_drafts,
             });
+            watch::watch(
+                &args.vault_dir,
+                !args.read_only,
+                interval,
+                exec.as_deref(),
+                followed.as_ref(),
+            )
+            .unwrap();
+        }
+        Subcommand::BrokenLinks(path) => {
+            let documents = match path {
+                Some(path) => {
+                    let full_path = MarkdownPath::new(args.vault_dir, path).unwrap();
+                    vec![vault.get_document(&full_path).unwrap()]
+                }
+                None => vault.documents(),
+            };
+            let issues = check::dangling_links(&vault, &documents);
+            print_issues(&issues, args.json);
+        }
+        Subcommand::VerifyLinks { allowlist } => {
+            let mut policy = vault.config().links.clone();
+            if allowlist.is_some() {
+                policy.allowed_domains = allowlist;
+            }
+            let issues = check::verify_links(&vault, &vault.documents(), &policy);
+            print_issues(&issues, args.json);
+        }
+        Subcommand::Tree { hierarchy } => {
+            let tree = Hierarchy::new(&vault).tree(hierarchy, &args.kinds);
+            if args.json {
+                println!("{}", serde_json::to_string(&tree).unwrap());
+            } else {
+                print!("{tree}");
+            }
+        }
+        Subcommand::Serve { host, port, ui } => {
+            serve::serve(&vault, &args.kinds, args.rank_mode, &host, port, ui).unwrap()
+        }
+        Subcommand::Orphans { inbound, outbound } => {
+            #[derive(Serialize)]
+            /// Label the orphans in the JSON output
+            struct OrphanInfo<'a> {
+                #[serde(skip)]
+                document: &'a Document,
+                path: MarkdownPath,
+                title: String,
+                backlinks: usize,
+                links: usize,
+            }
+
+            let query = match (inbound, outbound) {
+                (true, false) => Query::Orphan,
+                (false, true) => Query::Leaf,
+                _ => Query::And(Box::new(Query::Orphan), Box::new(Query::Leaf)),
+            };
+            let vault: &Vault = &vault;
+            let count = |links: &[DocId], id: DocId| {
+                links
+                    .iter()
+                    .filter(|&&other| other != id && vault.is_visible(vault.document(other)))
+                    .count()
+            };
+            let mut orphans: Vec<OrphanInfo> = vault
+                .query(query)
+                .into_iter()
+                .filter(|doc| args.kinds.contains(&doc.kind()))
+                .filter_map(|doc| {
+                    let id = vault.id(&doc.path())?;
+                    Some(OrphanInfo {
+                        document: doc,
+                        path: doc.path(),
+                        title: doc.title(),
+                        backlinks: count(vault.inbound(id), id),
+                        links: count(vault.outbound(id), id),
+                    })
+                })
+                .collect();
+            orphans.sort_by(|a, b| a.path.cmp(&b.path));
+
+            if args.format != Format::Table {
+                print_column(orphans.iter().map(|orphan| orphan.document), args.format);
+            } else if args.json {
+                println!("{}", serde_json::to_string(&orphans).unwrap());
+            } else {
+                let mut builder = tabled::builder::Builder::new();
+                builder.push_record(["Path", "Title", "Backlinks", "Links"]);
+                orphans.iter().for_each(|orphan| {
+                    builder.push_record([
+                        orphan.path.path().to_string_lossy().to_string(),
+                        orphan.title.clone(),
+                        orphan.backlinks.to_string(),
+                        orphan.links.to_string(),
+                    ])
+                });
+                let mut table = builder.build();
+                table.with(tabled::settings::style::Style::rounded());
+                println!("{table}");
+            }
+        }
+        Subcommand::Graph { colour } => {
+            let ids: Vec<DocId> = vault
+                .ids()
+                .into_iter()
+                .filter(|&id| args.kinds.contains(&vault.document(id).kind()))
+                .collect();
+            let mut graph = Graph::new(&vault, &ids);
+            match colour {
+                Some(Colouring::Rank) => {
+                    let ranks = timings.time("rank", || {
+                        rank(&vault, &ids, args.rank_mode, MAX_ITER, TOLERANCE)
+                    });
+                    graph.colour_by_rank(&ranks);
+                }
+                Some(Colouring::Tag) => graph.colour_by_tag(&vault),
+                None => {}
+            }
+            if args.json {
+                println!("{}", serde_json::to_string(&graph).unwrap());
+            } else {
+                print!("{graph}");
+            }
+        }
+        Subcommand::Templates => {
+            #[derive(Serialize)]
+            /// Label the templates in the JSON output
+            struct TemplateInfo {
+                path: MarkdownPath,
+                title: String,
+                variables: BTreeSet<String>,
+            }
+
+            let dirs = vault.template_dirs();
+            let mut templates: Vec<TemplateInfo> = vault
+                .documents()
+                .into_iter()
+                .filter(|doc| doc.kind() == Kind::Template)
+                .map(|doc| {
+                    // The variables of the partials and layout count as well
+                    let contents = doc.contents().unwrap();
+                    let text = Template::expand(&contents, &dirs).unwrap_or(contents);
+                    TemplateInfo {
+                        path: doc.path(),
+                        title: doc.title(),
+                        variables: Template::variables(&text),
+                    }
+                })
+                .collect();
+            templates.sort_by(|a, b| a.path.cmp(&b.path));
+
+            if args.json {
+                println!("{}", serde_json::to_string(&templates).unwrap());
+            } else {
+                let mut builder = tabled::builder::Builder::new();
+                builder.push_record(["Path", "Title", "Variables"]);
+                templates.iter().for_each(|template| {
+                    builder.push_record([
+                        template.path.path().to_string_lossy().to_string(),
+                        template.title.clone(),
+                        template
+                            .variables
+                            .iter()
+                            .cloned()
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                    ])
+                });
+                let mut table = builder.build();
+                table.with(tabled::settings::style::Style::rounded());
+                println!("{table}");
+            }
+        }
+        Subcommand::BundleExport(path) => {
+            Bundle::new(&vault, MAX_ITER, TOLERANCE)
+                .export(&path)
+                .unwrap();
+            println!("{}", path.to_string_lossy());
+        }
+        Subcommand::BundleImport(path) => {
+            let mut bundle = Bundle::import(&path).unwrap();
+            if args.json {
+                println!("{}", serde_json::to_string(&bundle).unwrap());
+            } else {
+                bundle
+                    .documents
+                    .sort_by(|a, b| b.rank.total_cmp(&a.rank).then_with(|| a.path.cmp(&b.path)));
+                let mut builder = tabled::builder::Builder::new();
+                builder.push_record(["Path", "Title", "Links", "Rank"]);
+                bundle.documents.iter().for_each(|doc| {
+                    builder.push_record([
+                        doc.path.to_string_lossy().to_string(),
+                        doc.title.clone(),
+                        doc.links.len().to_string(),
+                        doc.rank.to_string(),
+                    ])
+                });
+                let mut table = builder.build();
+                table.with(tabled::settings::style::Style::rounded());
+                println!("{table}");
+            }
+        }
+        Subcommand::WordFrequency { by: None } => {
+            let vocabulary = corpus_of_kinds(&vault, &args.kinds).vocabulary();
+            if args.json {
+                println!("{}", serde_json::to_string(&vocabulary).unwrap());
+            } else {
+                println!("term,df,idf");
+                vocabulary.iter().for_each(|term| {
+                    println!("{},{},{}", csv_field(&term.term), term.df, term.idf)
+                });
+            }
+        }
+        Subcommand::WordFrequency { by: Some(by) } => {
+            // Gather the text of every note of each group
+            let hierarchy = Hierarchy::new(&vault);
+            let corpus = corpus_of_kinds(&vault, &args.kinds);
+            let mut groups: BTreeMap<String, String> = BTreeMap::new();
+            for doc in vault
+                .documents()
+                .into_iter()
+                .filter(|doc| args.kinds.contains(&doc.kind()))
+            {
+                let names: Vec<String> = match by {
+                    Grouping::Tag => doc.tags().into_iter().collect(),
+                    Grouping::Folder => {
+                        let folder = hierarchy.folder(doc);
+                        // Notes at the root of the vault are grouped under `.`
+                        vec![match folder.as_os_str().is_empty() {
+                            true => ".".to_string(),
+                            false => folder.to_string_lossy().to_string(),
+                        }]
+                    }
+                };
+                let text = doc.stripped().unwrap();
+                for name in names {
+                    let group = groups.entry(name).or_default();
+                    group.push(' ');
+                    group.push_str(&text);
+                }
+            }
+            let top: BTreeMap<String, Vec<(String, f32)>> = groups
+                .into_iter()
+                .map(|(name, text)| {
+                    let terms = corpus.top_terms(&text, MAX_RESULTS);
+                    (name, terms)
+                })
+                .collect();
 
             if args.json {
+                println!("{}", serde_json::to_string(&top).unwrap());
+            } else {
+                println!("group,term,tf_idf");
+                top.iter().for_each(|(group, terms)| {
+                    terms.iter().for_each(|(term, score)| {
+                        println!("{},{},{score}", csv_field(group), csv_field(term))
+                    })
+                });
+            }
+        }
+        Subcommand::Status => {
+            let status = Status::new(&vault, MAX_ITER, TOLERANCE).unwrap();
+            if args.json {
+                println!("{}", serde_json::to_string(&status).unwrap());
+            } else {
+                print!("{status}");
+            }
+        }
+        Subcommand::Stats { record, history } => {
+            let stats = Stats::new(&vault, &args.kinds).unwrap();
+            if record {
+                Snapshot::record(&args.vault_dir, &stats).unwrap();
+            }
+            if history {
+                let snapshots = Snapshot::history(&args.vault_dir).unwrap();
+                if args.json {
+                    println!("{}", serde_json::to_string(&snapshots).unwrap());
+                } else if snapshots.is_empty() {
+                    eprintln!("no snapshots were recorded yet; run `n stats --record` to take one");
+                } else {
+                    println!("{}", Snapshot::table(&snapshots));
+                }
+            } else if args.json {
+                println!("{}", serde_json::to_string(&stats).unwrap());
+            } else {
+                print!("{stats}");
+            }
+        }
+        Subcommand::TitleIndex(Some(name)) => {
+            // Resolve the name without building the vault, for pickers and editor scripts
+            let index = TitleIndex::load(&args.vault_dir, !args.read_only).unwrap();
+            let found = index.find(&name);
+            if args.json {
+                let found: BTreeMap<_, &TitleEntry> = found.into_iter().collect();
+                println!("{}", serde_json::to_string(&found).unwrap());
+            } else if found.is_empty() {
+                eprintln!("error: no note is called `{name}`");
+                std::process::exit(1);
+            } else {
+                found
+                    .iter()
+                    .for_each(|(path, _)| println!("{}", path.to_string_lossy()));
+            }
+        }
+        Subcommand::TitleIndex(None) => {
+            let index = TitleIndex::load(&args.vault_dir, !args.read_only).unwrap();
+            if args.json {
+                println!("{}", serde_json::to_string(index.entries()).unwrap());
+            } else {
+                print!("{index}");
+            }
+        }
+        Subcommand::IndexDump => {
+            // The corpus is only ever useful as JSON
+            println!("{}", serde_json::to_string(vault.corpus()).unwrap());
+        }
+        Subcommand::List => {
+            let ids = vault.ids();
+            let ranks = timings.time("rank", || {
+                rank(&vault, &ids, args.rank_mode, MAX_ITER, TOLERANCE)
+            });
+            let mut res: Vec<(&Document, f32)> = ids
+                .iter()
+                .map(|&id| vault.document(id))
+                .zip(ranks)
+                .filter(|(k, _)| args.kinds.contains(&k.kind()))
+                .map(|(k, v)| (k, v * k.boost()))
+                .collect();
+            // Pinned notes come first, however they rank, and ties are broken by path
+            res.sort_by(|a, b| {
+                b.0.is_pinned()
+                    .cmp(&a.0.is_pinned())
+                    .then_with(|| b.1.total_cmp(&a.1))
+                    .then_with(|| a.0.path().cmp(&b.0.path()))
+            });
+
+            if args.format != Format::Table {
+                print_column(res.iter().map(|(doc, _)| *doc), args.format);
+            } else if args.json {
                 println!("{}", serde_json::to_string(&res).unwrap());
             } else {
                 let res: Vec<(String, f32)> = res
@@ -209,4 +1098,119 @@ fn main() {
             }
         }
     }
+
+    if args.timings {
+        // Loading the vault comes first, if it was needed at all
+        let mut all =
+            LazyCell::get(&vault).map_or_else(Timings::default, |vault| vault.timings().clone());
+        all.extend(&timings);
+        // Keep the actual output of the subcommand clean, so that it can still be piped
+        if args.json {
+            eprintln!("{}", serde_json::to_string(&all).unwrap());
+        } else {
+            eprint!("{all}");
+        }
+    }
+}
+
+/// Report the issues found by a check, and exit with an error if any of them is an error, so that
+/// e.g. publishing pipelines can gate on the result
+fn print_issues(issues: &[Issue], json: bool) {
+    if json {
+        println!("{}", serde_json::to_string(issues).unwrap());
+    } else {
+        let mut builder = tabled::builder::Builder::new();
+        builder.push_record(["Severity", "Check", "Location", "Message"]);
+        issues.iter().for_each(|issue| {
+            builder.push_record([
+                issue.severity.to_string(),
+                issue.check.to_string(),
+                issue.location(),
+                issue.message.clone(),
+            ])
+        });
+        let mut table = builder.build();
+        table.with(tabled::settings::style::Style::rounded());
+        println!("{table}");
+    }
+    if issues.iter().any(|issue| issue.severity == Severity::Error) {
+        std::process::exit(1);
+    }
+}
+
+/// The statistics of the visible notes of the given kinds, rather than of every note like the
+/// corpus of the vault
+fn corpus_of_kinds(vault: &Vault, kinds: &[Kind]) -> Corpus {
+    Corpus::new(
+        vault
+            .documents()
+            .into_par_iter()
+            .filter(|doc| kinds.contains(&doc.kind()))
+            .map(|doc| doc.stripped().unwrap())
+            .collect(),
+        vault.config().tokenizer.get(),
+    )
+}
+
+/// Parse a query given on the command line, or exit with the part of it which couldn't be parsed
+fn parse_query(query: &str) -> Query {
+    Query::parse(query).unwrap_or_else(|e| {
+        match e.input.trim() {
+            "" => eprintln!("error: invalid query `{query}`, which ends too early"),
+            near => eprintln!("error: invalid query `{query}`, near `{near}`"),
+        }
+        std::process::exit(1);
+    })
+}
+
+/// Print one path or title per line, without any decoration
+fn print_column<'a>(documents: impl Iterator<Item = &'a Document>, format: Format) {
+    documents.for_each(|doc| match format {
+        Format::Path => println!("{}", doc.path().path().to_string_lossy()),
+        Format::Title | Format::Table | Format::Dot => println!("{}", doc.title()),
+    });
+}
+
+/// Write a time in milliseconds since the epoch as a UTC date and time, to the minute
+fn timestamp(ms: u64) -> String {
+    let minutes = ms / 60_000 % 1440;
+    let date = Date::from_unix_days((ms / 86_400_000) as i64);
+    format!("{date} {:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+/// Print notes as a table of their paths and titles, as JSON, or as a column
+fn print_documents(documents: &[&Document], format: Format, json: bool) {
+    if format != Format::Table {
+        print_column(documents.iter().copied(), format);
+    } else if json {
+        let paths: Vec<MarkdownPath> = documents.iter().map(|doc| doc.path()).collect();
+        println!("{}", serde_json::to_string(&paths).unwrap());
+    } else {
+        let mut builder = tabled::builder::Builder::new();
+        builder.push_record(["Path", "Title"]);
+        documents.iter().for_each(|doc| {
+            builder.push_record([doc.path().path().to_string_lossy().to_string(), doc.title()])
+        });
+        let mut table = builder.build();
+        table.with(tabled::settings::style::Style::rounded());
+        println!("{table}");
+    }
+}
+
+/// Quote a CSV field if it contains a separator, a quote or a line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Report which files were modified, and how
+fn print_edits(edits: &[FileEdit], json: bool) {
+    if json {
+        println!("{}", serde_json::to_string(edits).unwrap());
+    } else {
+        edits.iter().for_each(|edit| println!("{edit}"));
+    }
 }
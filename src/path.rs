@@ -7,7 +7,7 @@ use std::{
     fmt::Display,
     fs,
     hash::Hash,
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf},
 };
 use thiserror::Error;
 
@@ -19,6 +19,9 @@ pub enum PathError {
     CanonicalisationFailed { path: PathBuf, reason: String },
 }
 
+/// https://url.spec.whatwg.org/#fragment-percent-encode-set
+const FRAGMENT: &AsciiSet = &CONTROLS.add(b' ').add(b'"').add(b'<').add(b'>').add(b'`');
+
 #[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
 /// A path that is guaranteed to be a Markdown file
 pub struct MarkdownPath(PathBuf);
@@ -61,6 +64,21 @@ impl MarkdownPath {
         self.0.clone()
     }
 
+    /// The directory containing the file, which relative links inside of it are resolved against
+    #[inline]
+    pub fn dir(&self) -> PathBuf {
+        self.0
+            .parent()
+            .map_or_else(PathBuf::new, |parent| parent.to_path_buf())
+    }
+
+    /// The path of the file relative to `dir`, encoded to be used as the URL of a link from a
+    /// note inside of it
+    #[inline]
+    pub fn link_from(&self, dir: &Path) -> String {
+        relative_link(&self.0, dir)
+    }
+
     // WARN: For testing purposes only!
     #[allow(dead_code)]
     fn new_unchecked(base_path: PathBuf, path: PathBuf) -> Result<Self, PathError> {
@@ -83,13 +101,45 @@ impl MarkdownPath {
     }
 }
 
+/// The path `target` relative to `dir`, encoded to be used as the URL of a link from a note inside
+/// of it. Unlike [`MarkdownPath::link_from`], the target doesn't need to exist, e.g. because the
+/// note is about to be moved there.
+pub fn relative_link(target: &Path, dir: &Path) -> String {
+    let (target, base) = (normalise(target), normalise(dir));
+    let target: Vec<_> = target.components().collect();
+    let base: Vec<_> = base.components().collect();
+    let common = target.iter().zip(&base).take_while(|(a, b)| a == b).count();
+    let parts: Vec<String> = std::iter::repeat_n("..".to_string(), base.len() - common)
+        .chain(
+            target[common..]
+                .iter()
+                .map(|part| part.as_os_str().to_string_lossy().to_string()),
+        )
+        .collect();
+    utf8_percent_encode(&parts.join("/"), FRAGMENT).to_string()
+}
+
+/// Resolve the `.` and `..` components of a path without touching the filesystem
+pub fn normalise(path: &Path) -> PathBuf {
+    path.components()
+        .fold(PathBuf::new(), |mut acc, component| match component {
+            Component::CurDir => acc,
+            Component::ParentDir => {
+                acc.pop();
+                acc
+            }
+            component => {
+                acc.push(component);
+                acc
+            }
+        })
+}
+
 #[allow(dead_code)]
 fn maybe_encode(path: &Path, do_encode: bool) -> PathBuf {
     if !do_encode {
         return path.to_path_buf();
     }
-    /// https://url.spec.whatwg.org/#fragment-percent-encode-set
-    const FRAGMENT: &AsciiSet = &CONTROLS.add(b' ').add(b'"').add(b'<').add(b'>').add(b'`');
     let encoded = utf8_percent_encode(&path.to_string_lossy(), FRAGMENT).to_string();
     PathBuf::from(encoded)
 }
@@ -0,0 +1,46 @@
+use std::fmt::Display;
+
+use serde::Serialize;
+
+/// A position in a source file. Both the line and the column are zero-based, the same way they
+/// are in LSP, and the column is counted in characters rather than bytes.
+#[derive(Debug, Clone, Copy, Serialize, Hash, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct Pos {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for Pos {
+    /// Positions are displayed one-based, as editors and compilers do
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line + 1, self.column + 1)
+    }
+}
+
+/// The byte offsets at which each line of a text starts, used to turn the byte offsets reported
+/// by the Markdown parser into positions.
+pub struct LineIndex<'a> {
+    text: &'a str,
+    starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(text: &'a str) -> Self {
+        let starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self { text, starts }
+    }
+
+    /// Get the position of the given byte offset
+    pub fn pos(&self, offset: usize) -> Pos {
+        let offset = offset.min(self.text.len());
+        let line = self.starts.partition_point(|&start| start <= offset) - 1;
+        let start = self.starts[line];
+        let column = self
+            .text
+            .get(start..offset)
+            .map_or(offset - start, |prefix| prefix.chars().count());
+        Pos { line, column }
+    }
+}
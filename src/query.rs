@@ -6,15 +6,40 @@ use nom::{
         complete::{char, multispace0},
         streaming::multispace1,
     },
-    combinator::{cut, map, value},
+    combinator::{cut, map, map_res, value},
     error::{ContextError, context},
     sequence::{delimited, preceded, terminated},
 };
 
-use crate::document::Document;
+use crate::{
+    check::{self, Check},
+    date::Date,
+    document::Document,
+    hierarchy::Hierarchy,
+    vault::Vault,
+};
 
 pub enum Query {
-    Contains { key: String, value: String },
+    Contains {
+        key: String,
+        value: String,
+    },
+    /// The document is nested under the given note or folder
+    DescendantOf(String),
+    /// The body of the document mentions the given date, in any of the formats of the vault
+    MentionsDate(Date),
+    /// The date of the given attribute of the document is before the given date. `modified`
+    /// falls back to the modification time of the file, like everywhere else.
+    Before {
+        key: String,
+        date: Date,
+    },
+    /// No other note links to the document
+    Orphan,
+    /// The document doesn't link to any other note
+    Leaf,
+    /// The document links to notes which don't exist
+    DanglingLinks,
     Not(Box<Query>),
     And(Box<Query>, Box<Query>),
     Or(Box<Query>, Box<Query>),
@@ -22,16 +47,55 @@ pub enum Query {
 }
 
 impl Query {
-    /// Check if a document matches the given query
-    pub fn matches(&self, document: &Document) -> bool {
+    /// Check if a document of the given vault matches the query, given the hierarchy of the vault,
+    /// which is built once for every document the query is evaluated over
+    pub fn matches(&self, vault: &Vault, hierarchy: &Hierarchy, document: &Document) -> bool {
         match self {
+            // Tags are canonicalised when notes are parsed
+            Query::Contains { key, value } if key == "tags" => document
+                .get_metadata(key)
+                .is_some_and(|target| target.contains(&vault.config().tags.normalise(value))),
             Query::Contains { key, value } => document
                 .get_metadata(key)
                 .map_or_else(|| false, |target| target.contains(value)),
-            Query::Not(query) => !query.matches(document),
-            Query::And(left, right) => left.matches(document) && right.matches(document),
-            Query::Or(left, right) => left.matches(document) || right.matches(document),
-            Query::Xor(left, right) => left.matches(document) ^ right.matches(document),
+            Query::DescendantOf(name) => hierarchy.is_descendant_of(document, name),
+            Query::MentionsDate(date) => document
+                .date_mentions(&vault.config().dates)
+                .is_ok_and(|mentions| mentions.iter().any(|mention| mention.date == *date)),
+            Query::Before { key, date } => match key.as_str() {
+                "modified" => document
+                    .modified()
+                    .map(|millis| Date::from_unix_days((millis / 86_400_000) as i64)),
+                key => document.date_attribute(key),
+            }
+            .is_some_and(|actual| actual < *date),
+            Query::Orphan => vault.id(&document.path()).is_some_and(|id| {
+                vault
+                    .inbound(id)
+                    .iter()
+                    .all(|&src| src == id || !vault.is_visible(vault.document(src)))
+            }),
+            Query::Leaf => vault.id(&document.path()).is_some_and(|id| {
+                vault
+                    .outbound(id)
+                    .iter()
+                    .all(|&target| target == id || !vault.is_visible(vault.document(target)))
+            }),
+            Query::DanglingLinks => check::broken_links(vault, document)
+                .iter()
+                .any(|issue| issue.check == Check::BrokenLink),
+            Query::Not(query) => !query.matches(vault, hierarchy, document),
+            Query::And(left, right) => {
+                left.matches(vault, hierarchy, document)
+                    && right.matches(vault, hierarchy, document)
+            }
+            Query::Or(left, right) => {
+                left.matches(vault, hierarchy, document)
+                    || right.matches(vault, hierarchy, document)
+            }
+            Query::Xor(left, right) => {
+                left.matches(vault, hierarchy, document) ^ right.matches(vault, hierarchy, document)
+            }
         }
     }
     pub fn parse(input: &str) -> Result<Query, nom::error::Error<&str>> {
@@ -114,6 +178,49 @@ impl Query {
             s_exp(inner).parse(i)
         }
 
+        fn parse_descendant_of(i: &str) -> IResult<&str, Query> {
+            let inner = map(
+                preceded(terminated(tag("descendant-of"), multispace1), cut(atom)),
+                Query::DescendantOf,
+            );
+            s_exp(inner).parse(i)
+        }
+
+        fn parse_mentions_date(i: &str) -> IResult<&str, Query> {
+            let inner = map(
+                preceded(
+                    terminated(tag("mentions-date"), multispace1),
+                    cut(map_res(atom, |date| date.parse())),
+                ),
+                Query::MentionsDate,
+            );
+            s_exp(inner).parse(i)
+        }
+
+        fn parse_before(i: &str) -> IResult<&str, Query> {
+            let inner = map(
+                preceded(
+                    terminated(tag("before"), multispace1),
+                    cut((
+                        atom,
+                        preceded(multispace1, map_res(atom, |date| date.parse())),
+                    )),
+                ),
+                |(key, date)| Query::Before { key, date },
+            );
+            s_exp(inner).parse(i)
+        }
+
+        /// Helper for the predicates about the links of the document, which take no arguments
+        fn parse_graph(i: &str) -> IResult<&str, Query> {
+            let inner = alt((
+                map(tag("orphan"), |_| Query::Orphan),
+                map(tag("leaf"), |_| Query::Leaf),
+                map(tag("dangling-links"), |_| Query::DanglingLinks),
+            ));
+            s_exp(inner).parse(i)
+        }
+
         fn parse_not(i: &str) -> IResult<&str, Query> {
             let inner = map(
                 preceded(terminated(tag("not"), multispace1), cut(parse_query)),
@@ -152,13 +259,27 @@ impl Query {
         fn parse_query(i: &str) -> IResult<&str, Query> {
             preceded(
                 multispace0,
-                alt((parse_contains, parse_not, parse_and, parse_or, parse_xor)),
+                alt((
+                    parse_contains,
+                    parse_descendant_of,
+                    parse_mentions_date,
+                    parse_before,
+                    parse_graph,
+                    parse_not,
+                    parse_and,
+                    parse_or,
+                    parse_xor,
+                )),
             )
             .parse(i)
         }
 
-        // WARN: Fix this unwrap; I'm only doing this to get it to work for now
-        let (rest, q) = parse_query(input).unwrap();
+        let (rest, q) = parse_query(input).map_err(|e| match e {
+            nom::Err::Error(e) | nom::Err::Failure(e) => e,
+            nom::Err::Incomplete(_) => {
+                nom::error::Error::new(input, nom::error::ErrorKind::Complete)
+            }
+        })?;
         if rest.trim().is_empty() {
             Ok(q)
         } else {
@@ -167,3 +288,78 @@ impl Query {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    /// The file names of the notes of the vault matching the query, sorted
+    fn names(dir: &TempDir, query: &str) -> Vec<String> {
+        let vault = dir.vault();
+        let mut names: Vec<String> = vault
+            .query(Query::parse(query).unwrap())
+            .into_iter()
+            .map(|document| {
+                document
+                    .path()
+                    .path()
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn parse_predicates() {
+        assert!(matches!(Query::parse("(orphan)"), Ok(Query::Orphan)));
+        assert!(matches!(Query::parse("(leaf)"), Ok(Query::Leaf)));
+        assert!(matches!(
+            Query::parse("(dangling-links)"),
+            Ok(Query::DanglingLinks)
+        ));
+        assert!(matches!(
+            Query::parse("(before modified \"2023-01-01\")"),
+            Ok(Query::Before { key, date })
+                if key == "modified" && date == "2023-01-01".parse().unwrap()
+        ));
+        assert!(Query::parse("(orphan x)").is_err());
+        assert!(Query::parse("(before modified)").is_err());
+        assert!(Query::parse("(before modified notadate)").is_err());
+    }
+
+    #[test]
+    fn evaluate_predicates() {
+        let dir = TempDir::new(&[
+            (
+                "a.md",
+                "---\nmodified: 2022-05-01\n---\n\n[b](b.md) [gone](missing.md)\n",
+            ),
+            ("b.md", "No links here.\n"),
+            (
+                "c.md",
+                "---\ncreated: 2022-12-31\n---\n\nLinked only by a draft.\n",
+            ),
+            ("d.md", "[myself](d.md)\n"),
+            ("e.md", "---\ndraft: true\n---\n\n[c](c.md)\n"),
+        ]);
+
+        assert_eq!(names(&dir, "(orphan)"), ["a.md", "c.md", "d.md"]);
+        assert_eq!(names(&dir, "(leaf)"), ["b.md", "c.md", "d.md"]);
+        assert_eq!(names(&dir, "(dangling-links)"), ["a.md"]);
+        assert_eq!(names(&dir, "(before modified 2023-01-01)"), ["a.md"]);
+        assert_eq!(names(&dir, "(before created 2023-01-01)"), ["c.md"]);
+        assert_eq!(
+            names(&dir, "(before created 2022-12-31)"),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            names(&dir, "(and (orphan) (before modified \"2023-01-01\"))"),
+            ["a.md"]
+        );
+    }
+}
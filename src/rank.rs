@@ -1,7 +1,20 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
 use rayon::iter::{IndexedParallelIterator, IntoParallelRefMutIterator, ParallelIterator};
+use serde::Serialize;
+
+use crate::{
+    heading::Heading,
+    vault::{DocId, Vault},
+};
 
-use crate::{document::Document, path::MarkdownPath};
-use std::{collections::HashMap, path::PathBuf};
+/// How many iterations of PageRank are run at most
+pub const MAX_ITER: usize = 100_000;
+/// How close two iterations of PageRank must be for the ranks to have converged
+pub const TOLERANCE: f32 = 0.0000001;
 
 /// Rank the vault using the PageRank algoritm, where the ranking of a page `A` is given by
 ///
@@ -22,7 +35,74 @@ use std::{collections::HashMap, path::PathBuf};
 /// - https://cs.brown.edu/courses/cs016/static/files/assignments/projects/GraphHelpSession.pdf
 /// - https://web.stanford.edu/class/cs315b/assignment3.html
 /// - https://pi.math.cornell.edu/~mec/Winter2009/RalucaRemus/Lecture3/lecture3.html
-pub fn rank(docs: Vec<&Document>, base_path: PathBuf, num_iter: usize, tol: f32) -> Vec<f32> {
+///
+/// Only the links between the given documents count, and the ranks are returned in the same order.
+pub fn rank(vault: &Vault, docs: &[DocId], mode: RankMode, num_iter: usize, tol: f32) -> Vec<f32> {
+    rank_with_convergence(vault, docs, mode, num_iter, tol).0
+}
+
+/// Where a user who doesn't follow a link 'teleports' to
+#[derive(Debug, Clone, Copy, Default)]
+pub enum RankMode {
+    /// Any document, with the same probability, which measures all-time centrality
+    #[default]
+    Global,
+    /// Preferably a recently modified document, the probability halving every `half_life` days
+    /// since its last modification, which measures what is currently central. Notes are dated by
+    /// [`Document::modified`](crate::document::Document::modified).
+    Fresh { half_life: f32 },
+}
+
+impl RankMode {
+    /// The probability to teleport to each of the documents, which sums up to one
+    fn teleport(&self, vault: &Vault, docs: &[DocId]) -> Vec<f32> {
+        let weights: Vec<f32> = match self {
+            RankMode::Global => vec![1.0; docs.len()],
+            RankMode::Fresh { half_life } => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |elapsed| elapsed.as_millis() as u64);
+                docs.iter()
+                    .map(|&id| {
+                        let age = vault
+                            .document(id)
+                            .modified()
+                            .map_or(0.0, |modified| now.saturating_sub(modified) as f32)
+                            / 86_400_000.0;
+                        0.5f32.powf(age / half_life)
+                    })
+                    .collect()
+            }
+        };
+        let total: f32 = weights.iter().sum();
+        if total > 0.0 {
+            weights.into_iter().map(|w| w / total).collect()
+        } else {
+            // Every note is so old that the weights underflowed
+            vec![1.0 / docs.len() as f32; docs.len()]
+        }
+    }
+}
+
+/// How the PageRank iteration went
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Convergence {
+    /// The number of iterations which were run
+    pub iterations: usize,
+    /// The sum of the differences between the last two iterations
+    pub delta: f32,
+    /// Whether `delta` fell under the tolerance before running out of iterations
+    pub converged: bool,
+}
+
+/// Same as [`rank`], but also report how well the ranks converged
+pub fn rank_with_convergence(
+    vault: &Vault,
+    docs: &[DocId],
+    mode: RankMode,
+    num_iter: usize,
+    tol: f32,
+) -> (Vec<f32>, Convergence) {
     /// The dampening factor of PageRank. This reflects the probability that the user exit the
     /// current document and 'teleport' to a new one.
     pub const D: f32 = 0.85;
@@ -31,13 +111,13 @@ pub fn rank(docs: Vec<&Document>, base_path: PathBuf, num_iter: usize, tol: f32)
 
     // "Teleport" refers to the ability for a user to switch to a different document without
     // following a link.
-    let teleport = (1.0 - D) / num_docs as f32;
+    let teleport = mode.teleport(vault, docs);
 
-    let idx: HashMap<MarkdownPath, usize> = docs
-        .iter()
+    // The position of each document of the vault in `docs`, if it's there at all
+    let mut idx: Vec<Option<usize>> = vec![None; vault.num_docs()];
+    docs.iter()
         .enumerate()
-        .map(|(i, d)| (d.path(), i))
-        .collect();
+        .for_each(|(i, id)| idx[id.index()] = Some(i));
 
     // The list of vertices pointing into each node.
     let mut inbound: Vec<Vec<usize>> = vec![Vec::new(); num_docs];
@@ -46,14 +126,12 @@ pub fn rank(docs: Vec<&Document>, base_path: PathBuf, num_iter: usize, tol: f32)
     let mut outdeg: Vec<usize> = vec![0; num_docs];
 
     // Iterate through each document...
-    for (src, doc) in docs.iter().enumerate() {
-        // ...and go through their links...
-        for link in doc.links() {
-            if let Some(target) = link.to_markdown_path(base_path.clone())
-                && let Some(&dst) = idx.get(&target)
-            {
-                // ...to find which other documents they point to, and populate the `inbound`
-                // and `outdeg` vectors accordingly.
+    for (src, &id) in docs.iter().enumerate() {
+        // ...and go through the documents their links resolve to...
+        for target in vault.outbound(id) {
+            if let Some(dst) = idx[target.index()] {
+                // ...to find which of the ranked documents they point to, and populate the
+                // `inbound` and `outdeg` vectors accordingly.
                 inbound[dst].push(src);
                 outdeg[src] += 1;
             }
@@ -63,6 +141,11 @@ pub fn rank(docs: Vec<&Document>, base_path: PathBuf, num_iter: usize, tol: f32)
     // The PageRank score of each vertex. This always sums up to one (give and take some
     // tolerance level to account for the weirdness of floating-point arithmetic).
     let mut rank = vec![1.0 / num_docs as f32; num_docs];
+    let mut convergence = Convergence {
+        iterations: 0,
+        delta: f32::INFINITY,
+        converged: false,
+    };
 
     for _ in 0..num_iter {
         // How many documents do not point to other documents (have no links).
@@ -73,9 +156,12 @@ pub fn rank(docs: Vec<&Document>, base_path: PathBuf, num_iter: usize, tol: f32)
             .map(|(_, r)| *r)
             .sum();
 
-        // The rank of a document if it does not have any documents referencing it.
-        let base = teleport + D * dangling_mass / num_docs as f32;
-        let mut next = vec![base; num_docs];
+        // The rank of a document if it does not have any documents referencing it. Dangling
+        // documents teleport their rank away as well.
+        let mut next: Vec<f32> = teleport
+            .iter()
+            .map(|w| (1.0 - D + D * dangling_mass) * w)
+            .collect();
 
         next.par_iter_mut().enumerate().for_each(|(dst, val)| {
             // Calculate the rank / out degree of each documents referencing this one.
@@ -90,10 +176,74 @@ pub fn rank(docs: Vec<&Document>, base_path: PathBuf, num_iter: usize, tol: f32)
         let delta: f32 = rank.iter().zip(&next).map(|(a, b)| (a - b).abs()).sum();
 
         rank = next;
+        convergence.iterations += 1;
+        convergence.delta = delta;
 
         if delta < tol {
+            convergence.converged = true;
             break;
         }
     }
-    rank
+    (rank, convergence)
+}
+
+/// A hit of a search, scored by both its BM25 score and the rank of its note
+#[derive(Debug, Clone)]
+pub struct Hit {
+    pub id: DocId,
+    /// The heading of the matching section, when searching by section
+    pub section: Option<Heading>,
+    pub bm25: f32,
+    pub rank: f32,
+    pub combined: f32,
+}
+
+/// Score the hits of a search by combining their BM25 score with the rank of their note among the
+/// matching notes, multiplied by the boost of the note. Pinned notes come first, then the hits
+/// are sorted by score. Ties are broken by path, then by position, so that the order is the same
+/// from one run to the next.
+pub fn score_hits(
+    vault: &Vault,
+    hits: Vec<(DocId, Option<Heading>, f32)>,
+    mode: RankMode,
+    num_iter: usize,
+    tol: f32,
+) -> Vec<Hit> {
+    // How much should the BM25 score count over the PageRank score?
+    const FACTOR: f32 = 0.7;
+
+    // Several sections of the same document may match
+    let matches: Vec<DocId> = hits
+        .iter()
+        .map(|(id, _, _)| *id)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    let ranks = rank(vault, &matches, mode, num_iter, tol);
+    let ranks: HashMap<DocId, f32> = matches.into_iter().zip(ranks).collect();
+
+    let mut hits: Vec<Hit> = hits
+        .into_iter()
+        .map(|(id, section, bm25)| {
+            let rank = ranks[&id];
+            Hit {
+                id,
+                section,
+                bm25,
+                rank,
+                combined: ((FACTOR * bm25) + ((1.0 - FACTOR) * rank)) * vault.document(id).boost(),
+            }
+        })
+        .collect();
+    hits.sort_by(|a, b| {
+        let (doc_a, doc_b) = (vault.document(a.id), vault.document(b.id));
+        let start = |hit: &Hit| hit.section.as_ref().map(|heading| heading.range.start);
+        doc_b
+            .is_pinned()
+            .cmp(&doc_a.is_pinned())
+            .then_with(|| b.combined.total_cmp(&a.combined))
+            .then_with(|| doc_a.path().cmp(&doc_b.path()))
+            .then_with(|| start(a).cmp(&start(b)))
+    });
+    hits
 }
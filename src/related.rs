@@ -0,0 +1,56 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::vault::{DocId, Vault};
+
+/// How two notes are related through the links of the vault
+#[derive(Debug, Clone, Copy, Default)]
+pub enum Method {
+    /// Both notes are linked to by the same notes (co-citation)
+    #[default]
+    Cocitation,
+    /// Both notes link to the same notes (bibliographic coupling)
+    Coupling,
+}
+
+/// Find the visible notes related to the given one, along with the number of notes they are both
+/// linked to by, or both link to, depending on the method. The most related notes come first.
+pub fn related(vault: &Vault, id: DocId, method: Method) -> Vec<(DocId, usize)> {
+    let ids: BTreeSet<DocId> = vault.ids().into_iter().collect();
+    // A note linking several times to another still only counts once
+    let targets = |src: DocId| -> BTreeSet<DocId> {
+        vault
+            .outbound(src)
+            .iter()
+            .copied()
+            .filter(|target| ids.contains(target))
+            .collect()
+    };
+
+    let mut counts: HashMap<DocId, usize> = HashMap::new();
+    match method {
+        Method::Cocitation => {
+            for &citing in &ids {
+                let cited = targets(citing);
+                if cited.contains(&id) {
+                    cited
+                        .into_iter()
+                        .for_each(|other| *counts.entry(other).or_default() += 1);
+                }
+            }
+        }
+        Method::Coupling => {
+            let cited = targets(id);
+            for &other in &ids {
+                let shared = targets(other).intersection(&cited).count();
+                if shared > 0 {
+                    counts.insert(other, shared);
+                }
+            }
+        }
+    }
+    counts.remove(&id);
+
+    let mut res: Vec<(DocId, usize)> = counts.into_iter().collect();
+    res.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    res
+}
@@ -1,7 +1,9 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+
+use crate::tokenize::Tokenize;
 
 /// We use the BM25 algorithm to search for the given query in the vault.
 ///
@@ -35,9 +37,25 @@ use serde::Serialize;
 /// - https://emschwartz.me/understanding-the-bm25-full-text-search-algorithm/
 #[derive(Serialize, Debug)]
 pub struct Corpus {
+    /// The text of each document, indexed by its `DocId`
     docs: Vec<String>,
     avgdl: f32,
-    idf: HashMap<String, f32>,
+    /// The number of documents each term appears in. Terms are sorted so that the corpus is
+    /// serialised the same way from one run to the next.
+    df: BTreeMap<String, usize>,
+    idf: BTreeMap<String, f32>,
+    /// How both the documents and the queries are split into terms
+    #[serde(skip)]
+    tokenizer: &'static dyn Tokenize,
+}
+
+/// A term of the vocabulary of a corpus
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Term {
+    pub term: String,
+    /// The number of documents the term appears in
+    pub df: usize,
+    pub idf: f32,
 }
 
 impl Corpus {
@@ -47,11 +65,11 @@ impl Corpus {
 
     /// Initilise a new corpus and calculate its statistics
     // NOTE: Figure out if we can guarantee that this document is definitely found in the corpus
-    pub fn new(docs: Vec<String>) -> Self {
+    pub fn new(docs: Vec<String>, tokenizer: &'static dyn Tokenize) -> Self {
         // Find the average length of a document in the corpus
         let avgdl = docs
             .iter()
-            .map(|doc| doc.split_whitespace().count() as f32)
+            .map(|doc| tokenizer.tokenize(doc).len() as f32)
             .sum::<f32>()
             / docs.len() as f32;
 
@@ -60,11 +78,7 @@ impl Corpus {
             .par_iter()
             // Normalise the text to make it case-insensitive, and flatten it into a set of all
             // tokens
-            .flat_map(|doc| {
-                doc.split_whitespace()
-                    .map(str::to_ascii_lowercase)
-                    .collect::<HashSet<_>>()
-            })
+            .flat_map(|doc| tokenizer.tokenize(doc).into_iter().collect::<HashSet<_>>())
             // Calculate the occurrence of each token
             .fold(HashMap::new, |mut acc: HashMap<String, f32>, curr| {
                 *acc.entry(curr).or_default() += 1f32;
@@ -79,36 +93,97 @@ impl Corpus {
 
         // Calculate the inverse document frequency of each token from the document frequency
         let idf = df
-            .into_iter()
-            .map(|(term, num_occurrence)| {
+            .iter()
+            .map(|(term, &num_occurrence)| {
                 let num_docs = docs.len() as f32;
                 let idf = ((num_docs - num_occurrence + 0.5 / (num_occurrence + 0.5)) + 1.0).ln();
-                (term, idf)
+                (term.clone(), idf)
+            })
+            .collect();
+        let df = df
+            .into_iter()
+            .map(|(term, num_occurrence)| (term, num_occurrence as usize))
+            .collect();
+        Self {
+            docs,
+            avgdl,
+            df,
+            idf,
+            tokenizer,
+        }
+    }
+
+    /// The number of documents in the corpus
+    #[inline]
+    pub fn num_docs(&self) -> usize {
+        self.docs.len()
+    }
+
+    /// The number of distinct terms in the corpus
+    #[inline]
+    pub fn num_terms(&self) -> usize {
+        self.idf.len()
+    }
+
+    /// Every term of the corpus, from the most to the least common
+    pub fn vocabulary(&self) -> Vec<Term> {
+        let mut terms: Vec<Term> = self
+            .df
+            .iter()
+            .map(|(term, &df)| Term {
+                term: term.clone(),
+                df,
+                idf: *self.idf.get(term).unwrap_or(&0f32),
+            })
+            .collect();
+        terms.sort_unstable_by(|a, b| b.df.cmp(&a.df).then_with(|| a.term.cmp(&b.term)));
+        terms
+    }
+
+    /// The `n` terms of `text` with the highest TF-IDF, i.e. the ones which are the most specific
+    /// to it compared to the rest of the corpus
+    pub fn top_terms(&self, text: &str, n: usize) -> Vec<(String, f32)> {
+        let tf: HashMap<String, usize> = self.tokenizer.tokenize(text).into_iter().fold(
+            HashMap::new(),
+            |mut frequencies, term| {
+                *frequencies.entry(term).or_default() += 1;
+                frequencies
+            },
+        );
+        let mut terms: Vec<(String, f32)> = tf
+            .into_iter()
+            .map(|(term, frequency)| {
+                let idf = *self.idf.get(&term).unwrap_or(&0f32);
+                (term, frequency as f32 * idf)
             })
             .collect();
-        Self { docs, avgdl, idf }
+        terms.sort_unstable_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        terms.truncate(n);
+        terms
     }
 
-    /// Calculate the BM25 score of a `document` given the `query`
-    pub fn score(&self, query: &str, document: &str) -> f32 {
-        let document_length = document.split_whitespace().count() as f32;
+    /// Calculate the BM25 score of the `index`th document of the corpus given the `query`
+    pub fn score(&self, query: &str, index: usize) -> f32 {
+        let document = self.tokenizer.tokenize(&self.docs[index]);
+        let document_length = document.len() as f32;
         let norm = Self::K1 * (1f32 - Self::B + Self::B * document_length / self.avgdl);
 
         // Find out how many times each term shows up in the given document
-        let tf: HashMap<&str, usize> = document.split_whitespace().fold(
+        let tf: HashMap<String, usize> = document.into_iter().fold(
             HashMap::new(),
-            |mut frequencies: HashMap<&str, usize>, term| {
+            |mut frequencies: HashMap<String, usize>, term| {
                 *frequencies.entry(term).or_default() += 1;
                 frequencies
             },
         );
 
         // Calculate the BM25 score of each term in the query
-        query
-            .split_whitespace()
+        self.tokenizer
+            .tokenize(query)
+            .into_iter()
             .map(|term| {
-                let frequency = *tf.get(term).unwrap_or(&0) as f32;
-                let idf = *self.idf.get(term).unwrap_or(&0f32);
+                let frequency = *tf.get(&term).unwrap_or(&0) as f32;
+                let idf = *self.idf.get(&term).unwrap_or(&0f32);
                 idf * ((frequency * (Self::K1 + 1f32)) / (frequency + norm))
             })
             .sum()
@@ -0,0 +1,406 @@
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, percent_decode_str, utf8_percent_encode};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{
+    document::{Document, ParseError},
+    graph::Graph,
+    html::LinkResolver,
+    kind::Kind,
+    path::MarkdownPath,
+    query::Query,
+    rank::{MAX_ITER, RankMode, TOLERANCE, score_hits},
+    vault::{DocId, Vault},
+};
+
+/// The single-page viewer served with `--ui`
+const UI: &str = include_str!("../extras/ui/index.html");
+
+/// The characters left as they are in the vault-relative paths used as URL fragments
+const PATH: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'/')
+    .remove(b'.')
+    .remove(b'-')
+    .remove(b'_');
+
+/// How long a client may take to send its request, or to receive the response
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How many connections are answered at once. Further ones wait to be accepted until a worker is
+/// free.
+const WORKERS: usize = 8;
+
+/// How many hits a search returns at most
+const MAX_HITS: usize = 50;
+
+#[derive(Debug, Error)]
+pub enum ServeError {
+    #[error("cannot listen on {addr} because {reason}")]
+    BindFailed { addr: String, reason: String },
+}
+
+/// A note, as listed by the API
+#[derive(Debug, Serialize)]
+struct Entry {
+    /// The path of the note relative to the root of the vault
+    path: String,
+    title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    score: Option<f32>,
+}
+
+/// A note, as viewed through the API
+#[derive(Debug, Serialize)]
+struct Note {
+    path: String,
+    title: String,
+    tags: Vec<String>,
+    /// The body of the note rendered as HTML, with links to other notes of the vault pointing to
+    /// their `#path`
+    html: String,
+    backlinks: Vec<Entry>,
+}
+
+/// The raw source of a note, as returned by the API
+#[derive(Debug, Serialize)]
+struct Content {
+    path: String,
+    title: String,
+    /// The Markdown source of the note, frontmatter included
+    content: String,
+}
+
+/// The links between notes, as returned by the API
+#[derive(Debug, Serialize)]
+struct Links {
+    nodes: Vec<Entry>,
+    /// The links as pairs of the paths of their source and their target
+    edges: Vec<(String, String)>,
+}
+
+/// Points the links between notes to the `#path` the viewer opens them at
+struct Viewer<'a> {
+    vault: &'a Vault,
+    root: &'a Path,
+}
+
+impl LinkResolver for Viewer<'_> {
+    fn url(&self, target: &MarkdownPath) -> Option<String> {
+        self.vault.get_document(target)?;
+        let path = relative(self.root, target);
+        Some(format!("#{}", utf8_percent_encode(&path, PATH)))
+    }
+
+    fn embed(&self, target: &MarkdownPath) -> Option<&Document> {
+        self.vault.get_document(target)
+    }
+}
+
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn json(value: &impl Serialize) -> Self {
+        Self {
+            status: 200,
+            content_type: "application/json",
+            body: serde_json::to_string(value).unwrap(),
+        }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body: serde_json::json!({ "error": message }).to_string(),
+        }
+    }
+
+    fn write(&self, mut stream: &TcpStream) -> io::Result<()> {
+        let reason = match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            _ => "Internal Server Error",
+        };
+        write!(
+            stream,
+            "HTTP/1.1 {} {reason}\r\nContent-Type: {}; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.status,
+            self.content_type,
+            self.body.len(),
+            self.body
+        )?;
+        stream.flush()
+    }
+}
+
+/// Serve the vault over HTTP on the given address until the process is stopped, with a fixed pool
+/// of worker threads taking turns accepting connections. The vault is read once, when the server
+/// starts.
+///
+/// - `GET /api/search?q=QUERY` lists the best matching notes,
+/// - `GET /api/query?q=QUERY` lists the notes matching a metadata query, e.g. `(contains tags n)`,
+/// - `GET /api/note?path=PATH` returns a note rendered as HTML, along with its backlinks,
+/// - `GET /api/content?path=PATH` returns the Markdown source of a note,
+/// - `GET /api/backlinks?path=PATH` lists the notes linking to a note,
+/// - `GET /api/graph` returns the notes along with the links between them,
+/// - `GET /` serves a small viewer built on the above, if `ui` is set.
+///
+/// Paths are relative to the root of the vault, and only notes of the given kinds are listed.
+/// Searches are ranked like `n search`, with the given rank mode.
+pub fn serve(
+    vault: &Vault,
+    kinds: &[Kind],
+    rank_mode: RankMode,
+    host: &str,
+    port: u16,
+    ui: bool,
+) -> Result<(), ServeError> {
+    let addr = format!("{host}:{port}");
+    let listener = TcpListener::bind(&addr).map_err(|e| ServeError::BindFailed {
+        addr: addr.clone(),
+        reason: e.to_string(),
+    })?;
+    let root = vault.path().canonicalize().unwrap_or_else(|_| vault.path());
+    eprintln!("serving the vault on http://{addr}");
+
+    thread::scope(|scope| {
+        for _ in 0..WORKERS {
+            let (listener, root) = (&listener, &root);
+            scope.spawn(move || {
+                for stream in listener.incoming().filter_map(Result::ok) {
+                    // A client hanging up mid-request, or never sending it, shouldn't take the
+                    // server down
+                    if let Err(e) = handle(vault, kinds, rank_mode, root, &stream, ui) {
+                        eprintln!("failed to answer a request because {e}");
+                    }
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+fn handle(
+    vault: &Vault,
+    kinds: &[Kind],
+    rank_mode: RankMode,
+    root: &Path,
+    stream: &TcpStream,
+    ui: bool,
+) -> io::Result<()> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Skip the headers, as no endpoint needs them
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let response = match (parts.next(), parts.next()) {
+        (Some("GET"), Some(target)) => route(vault, kinds, rank_mode, root, target, ui),
+        (Some(_), Some(_)) => Response::error(405, "only GET requests are supported"),
+        _ => Response::error(400, "malformed request"),
+    };
+    response.write(stream)
+}
+
+fn route(
+    vault: &Vault,
+    kinds: &[Kind],
+    rank_mode: RankMode,
+    root: &Path,
+    target: &str,
+    ui: bool,
+) -> Response {
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let params = parse_query(query);
+    let note = || {
+        let path = params.get("path").ok_or("missing the `path` parameter")?;
+        MarkdownPath::new(root.to_path_buf(), PathBuf::from(path))
+            .ok()
+            .and_then(|path| vault.id(&path))
+            .filter(|&id| vault.is_visible(vault.document(id)))
+            .ok_or("no such note")
+    };
+
+    match path {
+        "/" | "/index.html" if ui => Response {
+            status: 200,
+            content_type: "text/html",
+            body: UI.to_string(),
+        },
+        "/api/search" => match params.get("q") {
+            Some(query) => Response::json(&search(vault, kinds, rank_mode, root, query)),
+            None => Response::error(400, "missing the `q` parameter"),
+        },
+        "/api/query" => match params.get("q").map(|query| Query::parse(query)) {
+            Some(Ok(query)) => Response::json(&matching(vault, kinds, root, query)),
+            Some(Err(e)) => Response::error(400, &format!("invalid query: {e}")),
+            None => Response::error(400, "missing the `q` parameter"),
+        },
+        "/api/graph" => Response::json(&links(vault, kinds, root)),
+        "/api/content" => match note().map(|id| content(vault, root, id)) {
+            Ok(Ok(content)) => Response::json(&content),
+            Ok(Err(e)) => Response::error(500, &e.to_string()),
+            Err(e) => Response::error(404, e),
+        },
+        "/api/backlinks" => match note() {
+            Ok(id) => Response::json(&backlinks(vault, root, id)),
+            Err(e) => Response::error(404, e),
+        },
+        "/api/note" => match note().map(|id| view(vault, root, id)) {
+            Ok(Ok(note)) => Response::json(&note),
+            Ok(Err(e)) => Response::error(500, &e.to_string()),
+            Err(e) => Response::error(404, e),
+        },
+        _ => Response::error(404, "no such endpoint"),
+    }
+}
+
+/// Decode the `key=value` pairs of a query string
+fn parse_query(query: &str) -> HashMap<String, String> {
+    let decode = |s: &str| {
+        percent_decode_str(&s.replace('+', " "))
+            .decode_utf8_lossy()
+            .to_string()
+    };
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode(key), decode(value))
+        })
+        .collect()
+}
+
+/// The path of the note relative to the root of the vault
+fn relative(root: &Path, path: &MarkdownPath) -> String {
+    let path = path.path();
+    path.strip_prefix(root)
+        .unwrap_or(&path)
+        .to_string_lossy()
+        .to_string()
+}
+
+fn entry(vault: &Vault, root: &Path, id: DocId, score: Option<f32>) -> Entry {
+    let doc = vault.document(id);
+    Entry {
+        path: relative(root, &doc.path()),
+        title: doc.title(),
+        score,
+    }
+}
+
+/// The best matching notes, scored and ordered like by `n search`
+fn search(
+    vault: &Vault,
+    kinds: &[Kind],
+    rank_mode: RankMode,
+    root: &Path,
+    query: &str,
+) -> Vec<Entry> {
+    let hits: Vec<_> = vault
+        .search(query)
+        .into_iter()
+        .filter(|(_, score)| *score > 0.0)
+        .filter(|(id, _)| kinds.contains(&vault.document(*id).kind()))
+        .map(|(id, score)| (id, None, score))
+        .collect();
+    score_hits(vault, hits, rank_mode, MAX_ITER, TOLERANCE)
+        .into_iter()
+        .take(MAX_HITS)
+        .map(|hit| entry(vault, root, hit.id, Some(hit.combined)))
+        .collect()
+}
+
+fn matching(vault: &Vault, kinds: &[Kind], root: &Path, query: Query) -> Vec<Entry> {
+    let mut ids: Vec<DocId> = vault
+        .query(query)
+        .into_iter()
+        .filter(|doc| kinds.contains(&doc.kind()))
+        .filter_map(|doc| vault.id(&doc.path()))
+        .collect();
+    ids.sort_unstable();
+    ids.into_iter()
+        .map(|id| entry(vault, root, id, None))
+        .collect()
+}
+
+fn links(vault: &Vault, kinds: &[Kind], root: &Path) -> Links {
+    let ids: Vec<DocId> = vault
+        .ids()
+        .into_iter()
+        .filter(|&id| kinds.contains(&vault.document(id).kind()))
+        .collect();
+    let graph = Graph::new(vault, &ids);
+    let path = |id: DocId| relative(root, &vault.document(id).path());
+    Links {
+        nodes: graph
+            .nodes
+            .iter()
+            .map(|node| entry(vault, root, node.id, None))
+            .collect(),
+        edges: graph
+            .edges
+            .into_iter()
+            .map(|(src, target)| (path(src), path(target)))
+            .collect(),
+    }
+}
+
+fn content(vault: &Vault, root: &Path, id: DocId) -> Result<Content, ParseError> {
+    let doc = vault.document(id);
+    Ok(Content {
+        path: relative(root, &doc.path()),
+        title: doc.title(),
+        content: doc.contents()?,
+    })
+}
+
+fn backlinks(vault: &Vault, root: &Path, id: DocId) -> Vec<Entry> {
+    let mut sources: Vec<DocId> = vault
+        .inbound(id)
+        .iter()
+        .copied()
+        .filter(|&src| src != id && vault.is_visible(vault.document(src)))
+        .collect();
+    sources.sort_unstable();
+    sources.dedup();
+    sources
+        .into_iter()
+        .map(|src| entry(vault, root, src, None))
+        .collect()
+}
+
+fn view(vault: &Vault, root: &Path, id: DocId) -> Result<Note, ParseError> {
+    let doc = vault.document(id);
+    Ok(Note {
+        path: relative(root, &doc.path()),
+        title: doc.title(),
+        tags: doc.tags().into_iter().collect(),
+        html: doc.to_html(&Viewer { vault, root })?,
+        backlinks: backlinks(vault, root, id),
+    })
+}
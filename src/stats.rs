@@ -0,0 +1,298 @@
+use std::{
+    collections::BTreeSet,
+    fmt::Display,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    config::Config,
+    date::Date,
+    document::ParseError,
+    kind::Kind,
+    lock::{Lock, LockError},
+    path::MarkdownPath,
+    vault::{DocId, Vault},
+};
+
+#[derive(Debug, Error)]
+pub enum StatsError {
+    #[error("failed to read the history `{path}` because {reason}")]
+    ReadFailed { path: PathBuf, reason: String },
+    #[error("failed to write the history `{path}` because {reason}")]
+    WriteFailed { path: PathBuf, reason: String },
+    #[error(transparent)]
+    Lock(#[from] LockError),
+    #[error("line {line} of the history `{path}` is not a snapshot: {reason}")]
+    InvalidSnapshot {
+        path: PathBuf,
+        line: usize,
+        reason: String,
+    },
+}
+
+/// A note, along with how many words it has
+#[derive(Debug, Clone, Serialize)]
+pub struct NoteSize {
+    pub path: MarkdownPath,
+    pub title: String,
+    pub words: usize,
+}
+
+/// Metrics about the notes of a vault and the links between them
+#[derive(Debug, Serialize)]
+pub struct Stats {
+    pub notes: usize,
+    /// The number of words of the plain text of the notes
+    pub words: usize,
+    pub average_words: f32,
+    /// The number of links from the notes to visible notes, leaving out links of a note to itself
+    pub links: usize,
+    /// The average number of links to each note, from any visible note
+    pub average_in_degree: f32,
+    /// The average number of links from each note, to any visible note
+    pub average_out_degree: f32,
+    /// The number of notes without backlinks and without links to other notes
+    pub orphans: usize,
+    /// The number of distinct tags
+    pub tags: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub largest: Option<NoteSize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub smallest: Option<NoteSize>,
+}
+
+impl Stats {
+    /// The metrics of the visible notes of the given kinds
+    pub fn new(vault: &Vault, kinds: &[Kind]) -> Result<Self, ParseError> {
+        let ids: Vec<DocId> = vault
+            .ids()
+            .into_iter()
+            .filter(|&id| kinds.contains(&vault.document(id).kind()))
+            .collect();
+        let sizes = ids
+            .clone()
+            .into_par_iter()
+            .map(|id| {
+                let doc = vault.document(id);
+                Ok(NoteSize {
+                    path: doc.path(),
+                    title: doc.title(),
+                    words: doc.stripped()?.split_whitespace().count(),
+                })
+            })
+            .collect::<Result<Vec<NoteSize>, ParseError>>()?;
+
+        // Links from or to drafts don't count unless drafts are shown
+        let degree = |links: &[DocId], id: DocId| {
+            links
+                .iter()
+                .filter(|&&other| other != id && vault.is_visible(vault.document(other)))
+                .count()
+        };
+        let in_degrees: Vec<usize> = ids
+            .iter()
+            .map(|&id| degree(vault.inbound(id), id))
+            .collect();
+        let out_degrees: Vec<usize> = ids
+            .iter()
+            .map(|&id| degree(vault.outbound(id), id))
+            .collect();
+        let tags: BTreeSet<String> = ids
+            .iter()
+            .flat_map(|&id| vault.document(id).tags())
+            .collect();
+
+        let notes = ids.len();
+        let words = sizes.iter().map(|size| size.words).sum();
+        let average = |total: usize| {
+            if notes == 0 {
+                0.0
+            } else {
+                total as f32 / notes as f32
+            }
+        };
+        // Ties are broken by path, so that the report doesn't change from a run to another
+        let largest = sizes
+            .iter()
+            .min_by(|a, b| b.words.cmp(&a.words).then_with(|| a.path.cmp(&b.path)))
+            .cloned();
+        let smallest = sizes
+            .iter()
+            .min_by(|a, b| a.words.cmp(&b.words).then_with(|| a.path.cmp(&b.path)))
+            .cloned();
+
+        Ok(Stats {
+            notes,
+            words,
+            average_words: average(words),
+            links: out_degrees.iter().sum(),
+            average_in_degree: average(in_degrees.iter().sum()),
+            average_out_degree: average(out_degrees.iter().sum()),
+            orphans: in_degrees
+                .iter()
+                .zip(&out_degrees)
+                .filter(|&(&inbound, &outbound)| inbound == 0 && outbound == 0)
+                .count(),
+            tags: tags.len(),
+            largest,
+            smallest,
+        })
+    }
+}
+
+impl Display for NoteSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({}, {} words)",
+            self.title,
+            self.path.path().to_string_lossy(),
+            self.words
+        )
+    }
+}
+
+impl Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let note =
+            |size: &Option<NoteSize>| size.as_ref().map_or("-".to_string(), |s| s.to_string());
+        let rows = [
+            ("Notes", self.notes.to_string()),
+            ("Words", self.words.to_string()),
+            ("Average words", format!("{:.1}", self.average_words)),
+            ("Links", self.links.to_string()),
+            (
+                "Average in-degree",
+                format!("{:.2}", self.average_in_degree),
+            ),
+            (
+                "Average out-degree",
+                format!("{:.2}", self.average_out_degree),
+            ),
+            ("Orphans", self.orphans.to_string()),
+            ("Tags", self.tags.to_string()),
+            ("Largest note", note(&self.largest)),
+            ("Smallest note", note(&self.smallest)),
+        ];
+        let mut builder = tabled::builder::Builder::new();
+        builder.push_record(["Metric", "Value"]);
+        rows.into_iter()
+            .for_each(|(metric, value)| builder.push_record([metric.to_string(), value]));
+        let mut table = builder.build();
+        table.with(tabled::settings::style::Style::rounded());
+        writeln!(f, "{table}")
+    }
+}
+
+/// The size of the vault at some point in time, as recorded in `.n/stats.ndjson`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// When the snapshot was taken, in milliseconds since the epoch
+    pub recorded: u64,
+    pub notes: usize,
+    pub words: usize,
+    pub links: usize,
+}
+
+impl Snapshot {
+    const FILE: &str = "stats.ndjson";
+
+    fn location(base_path: &Path) -> PathBuf {
+        base_path.join(Config::DIR).join(Self::FILE)
+    }
+
+    /// The day the snapshot was taken, in UTC
+    pub fn date(&self) -> Date {
+        Date::from_unix_days((self.recorded / 86_400_000) as i64)
+    }
+
+    /// Append a snapshot of the given statistics to the history of the vault at `base_path`
+    pub fn record(base_path: &Path, stats: &Stats) -> Result<Self, StatsError> {
+        let snapshot = Snapshot {
+            recorded: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_millis() as u64),
+            notes: stats.notes,
+            words: stats.words,
+            links: stats.links,
+        };
+        let location = Self::location(base_path);
+        let _lock = Lock::acquire(base_path)?;
+        let write = || -> io::Result<()> {
+            if let Some(parent) = location.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&location)?;
+            writeln!(file, "{}", serde_json::to_string(&snapshot)?)
+        };
+        write().map_err(|e| StatsError::WriteFailed {
+            path: location.clone(),
+            reason: e.to_string(),
+        })?;
+        Ok(snapshot)
+    }
+
+    /// The snapshots recorded for the vault at `base_path`, oldest first
+    pub fn history(base_path: &Path) -> Result<Vec<Self>, StatsError> {
+        let location = Self::location(base_path);
+        let contents = match fs::read_to_string(&location) {
+            Ok(contents) => contents,
+            // Nothing was recorded yet
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(StatsError::ReadFailed {
+                    path: location,
+                    reason: e.to_string(),
+                });
+            }
+        };
+        let mut snapshots = contents
+            .lines()
+            .zip(1..)
+            .filter(|(line, _)| !line.trim().is_empty())
+            .map(|(line, number)| {
+                serde_json::from_str(line).map_err(|e| StatsError::InvalidSnapshot {
+                    path: location.clone(),
+                    line: number,
+                    reason: e.to_string(),
+                })
+            })
+            .collect::<Result<Vec<Snapshot>, StatsError>>()?;
+        snapshots.sort_by_key(|snapshot| snapshot.recorded);
+        Ok(snapshots)
+    }
+
+    /// A table of the snapshots, with how much each count grew since the previous one
+    pub fn table(snapshots: &[Snapshot]) -> tabled::Table {
+        let growth = |now: usize, before: Option<usize>| match before {
+            Some(before) if now != before => {
+                format!("{now} ({:+})", now as i64 - before as i64)
+            }
+            _ => now.to_string(),
+        };
+        let mut builder = tabled::builder::Builder::new();
+        builder.push_record(["Date", "Notes", "Words", "Links"]);
+        let previous = std::iter::once(None).chain(snapshots.iter().map(Some));
+        for (snapshot, previous) in snapshots.iter().zip(previous) {
+            builder.push_record([
+                snapshot.date().to_string(),
+                growth(snapshot.notes, previous.map(|p| p.notes)),
+                growth(snapshot.words, previous.map(|p| p.words)),
+                growth(snapshot.links, previous.map(|p| p.links)),
+            ]);
+        }
+        let mut table = builder.build();
+        table.with(tabled::settings::style::Style::rounded());
+        table
+    }
+}
@@ -0,0 +1,67 @@
+use std::fmt::Display;
+
+use owo_colors::OwoColorize;
+use serde::Serialize;
+
+use crate::{
+    index::{IndexError, IndexStatus, TitleIndex},
+    rank::{Convergence, RankMode, rank_with_convergence},
+    vault::Vault,
+};
+
+/// The health of the indices of a vault, so that users can tell whether results are stale
+#[derive(Debug, Serialize)]
+pub struct Status {
+    /// The number of notes in the vault
+    pub documents: usize,
+    /// The number of distinct terms in the search corpus
+    pub terms: usize,
+    pub title_index: IndexStatus,
+    pub rank: Convergence,
+}
+
+impl Status {
+    pub fn new(vault: &Vault, num_iter: usize, tol: f32) -> Result<Self, IndexError> {
+        let (_, rank) = rank_with_convergence(vault, &vault.ids(), RankMode::Global, num_iter, tol);
+        Ok(Status {
+            documents: vault.corpus().num_docs(),
+            terms: vault.corpus().num_terms(),
+            title_index: TitleIndex::status(&vault.path())?,
+            rank,
+        })
+    }
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let index = &self.title_index;
+        let freshness = if index.stale == 0 && index.missing == 0 {
+            "up to date".green().to_string()
+        } else {
+            format!("{} stale, {} missing", index.stale, index.missing)
+                .yellow()
+                .to_string()
+        };
+        let updated = index
+            .updated
+            .map_or_else(|| "never".to_string(), |ms| format!("{ms} ms since epoch"));
+        let rank = if self.rank.converged {
+            "converged".green().to_string()
+        } else {
+            "not converged".yellow().to_string()
+        };
+
+        writeln!(f, "{}", "Corpus".bold())?;
+        writeln!(f, "  documents: {}", self.documents)?;
+        writeln!(f, "  terms: {}", self.terms)?;
+        writeln!(f, "{}", "Title index".bold())?;
+        writeln!(f, "  entries: {} ({freshness})", index.indexed)?;
+        writeln!(f, "  last updated: {updated}")?;
+        writeln!(f, "{}", "Rank".bold())?;
+        writeln!(
+            f,
+            "  {rank} after {} iterations (delta {:e})",
+            self.rank.iterations, self.rank.delta
+        )
+    }
+}
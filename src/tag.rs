@@ -0,0 +1,236 @@
+use std::{collections::BTreeSet, ops::Range};
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Serialize;
+
+use crate::{document::Value, edit::TextEdit, pos::Pos};
+
+/// An inline `#tag` in the body of a note
+#[derive(Debug, Serialize, Clone, Hash, PartialEq, Eq)]
+pub struct InlineTag {
+    /// The name of the tag, without the leading `#`
+    pub name: String,
+    pub pos: Pos,
+    /// The byte range of the tag, including the `#`, in the source file
+    pub range: Range<usize>,
+}
+
+/// Find the inline tags in a piece of text, along with their byte ranges relative to it. A tag is
+/// a `#` at the start of a word followed by letters, digits, `_`, `-` or `/` (for nested tags),
+/// which isn't only made up of digits, so that e.g. issue numbers aren't counted.
+pub fn find_inline(text: &str) -> Vec<(String, Range<usize>)> {
+    static REGEX: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"(?:^|[\s(\[])(#([\p{L}\p{N}_\-/]+))").unwrap());
+    REGEX
+        .captures_iter(text)
+        .filter_map(|caps| {
+            let (tag, name) = (caps.get(1)?, caps.get(2)?);
+            let name = name.as_str().trim_end_matches('/');
+            (!name.chars().all(|c| c.is_ascii_digit())).then(|| (name.to_string(), tag.range()))
+        })
+        .collect()
+}
+
+/// How the spellings of a tag are brought to a single canonical form, so that e.g.
+/// `#Machine-Learning` and `#machine_learning` are the same tag. Tags are left as they are by
+/// default.
+#[derive(Debug, Clone, Default)]
+pub struct TagRules {
+    /// Whether tags are lowercased
+    pub case_fold: bool,
+    /// The separator between the words of a tag, which `-` and `_` are both turned into
+    pub word_separator: Option<char>,
+    /// The characters standing for `/` between a tag and its nested tags, such as `.` or `:`
+    pub hierarchy_separators: Vec<char>,
+}
+
+impl TagRules {
+    fn push_normalised(&self, c: char, canonical: &mut String) {
+        match c {
+            c if self.hierarchy_separators.contains(&c) => canonical.push('/'),
+            '-' | '_' => canonical.push(self.word_separator.unwrap_or(c)),
+            c if self.case_fold => canonical.extend(c.to_lowercase()),
+            c => canonical.push(c),
+        }
+    }
+
+    /// The canonical form of a tag
+    pub fn normalise(&self, tag: &str) -> String {
+        let mut canonical = String::with_capacity(tag.len());
+        tag.chars()
+            .for_each(|c| self.push_normalised(c, &mut canonical));
+        canonical
+    }
+
+    /// The length in bytes of the start of the tag `raw` whose canonical form is `len` bytes long,
+    /// as lowercasing may change the length of the text
+    pub fn source_len(&self, raw: &str, len: usize) -> usize {
+        let mut canonical = String::new();
+        raw.char_indices()
+            .find_map(|(i, c)| {
+                let reached = canonical.len() >= len;
+                self.push_normalised(c, &mut canonical);
+                reached.then_some(i)
+            })
+            .unwrap_or(raw.len())
+    }
+
+    /// Bring the tags of a `tags` frontmatter attribute to their canonical form, keeping the
+    /// shape of the value
+    pub fn normalise_metadata(&self, value: &Value) -> Value {
+        match value {
+            Value::Array(values) => Value::Array(
+                values
+                    .iter()
+                    .map(|value| self.normalise_metadata(value))
+                    .collect(),
+            ),
+            Value::String(val) => Value::String(self.normalise(val)),
+            value => value.clone(),
+        }
+    }
+}
+
+/// Get the tags declared in the `tags` frontmatter attribute, which may either be a list or a
+/// string of comma- or space-separated tags.
+pub fn from_metadata(value: &Value) -> BTreeSet<String> {
+    match value {
+        Value::Array(values) => values.iter().flat_map(from_metadata).collect(),
+        Value::String(val) => val
+            .split([',', ' '])
+            .map(|tag| tag.trim().trim_start_matches('#'))
+            .filter(|tag| !tag.is_empty())
+            .map(str::to_string)
+            .collect(),
+        Value::Integer(_) | Value::Real(_) => BTreeSet::from([value.to_string()]),
+        _ => BTreeSet::new(),
+    }
+}
+
+/// Whether `tag` is `name` itself, or one of its nested tags, i.e. `name/...`
+#[inline]
+pub fn is_within(tag: &str, name: &str) -> bool {
+    tag == name
+        || tag
+            .strip_prefix(name)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// A tag in the `tags` attribute of a YAML frontmatter block, with byte ranges relative to it
+struct Entry<'a> {
+    /// The tag as written
+    text: &'a str,
+    range: Range<usize>,
+    /// The line the tag is on, including its line break
+    line: Range<usize>,
+    /// The value of `tags:` on the same line, or of the `- item` the tag is
+    value: Range<usize>,
+    is_item: bool,
+}
+
+/// Find the tags in the `tags` attribute of a YAML frontmatter block
+fn entries(frontmatter: &str) -> Vec<Entry<'_>> {
+    static KEY: Lazy<Regex> = Lazy::new(|| Regex::new(r"^tags\s*:(.*)$").unwrap());
+    static ITEM: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\s*-\s+(.*)$").unwrap());
+    static TOKEN: Lazy<Regex> = Lazy::new(|| Regex::new(r##"[^\s,\[\]'"#]+"##).unwrap());
+
+    let mut entries = Vec::new();
+    let mut in_list = false;
+    let mut line_start = 0;
+    for line in frontmatter.split_inclusive('\n') {
+        let trimmed = line.trim_end_matches(['\n', '\r']);
+        // The value of `tags:` on the same line, or a `- item` of the list following it
+        let (value, is_item) = if let Some(caps) = KEY.captures(trimmed) {
+            in_list = true;
+            (caps.get(1), false)
+        } else if in_list && let Some(caps) = ITEM.captures(trimmed) {
+            (caps.get(1), true)
+        } else {
+            in_list = false;
+            (None, false)
+        };
+
+        if let Some(value) = value {
+            let value_start = line_start + value.start();
+            entries.extend(TOKEN.find_iter(value.as_str()).map(|token| Entry {
+                text: token.as_str(),
+                range: value_start + token.start()..value_start + token.end(),
+                line: line_start..line_start + line.len(),
+                value: value_start..value_start + value.len(),
+                is_item,
+            }));
+        }
+        line_start += line.len();
+    }
+    entries
+}
+
+/// Plan the edits renaming the tag `old` (and its nested tags) to `new` in the `tags` attribute of
+/// a YAML frontmatter block, starting at byte `offset` of the file. Tags are compared in their
+/// canonical form, so `old` must be canonical. If `remove_duplicate` is set, entries equal to
+/// `old` are removed instead, as the note already carries `new`.
+pub fn frontmatter_edits(
+    frontmatter: &str,
+    offset: usize,
+    old: &str,
+    new: &str,
+    remove_duplicate: bool,
+    rules: &TagRules,
+) -> Vec<TextEdit> {
+    entries(frontmatter)
+        .into_iter()
+        .filter_map(|entry| {
+            let canonical = rules.normalise(entry.text);
+            if !is_within(&canonical, old) {
+                return None;
+            }
+            let Range { start, end } = entry.range;
+            let edit = if remove_duplicate && canonical == old {
+                if entry.is_item {
+                    // Drop the whole `- item` line
+                    TextEdit {
+                        range: offset + entry.line.start..offset + entry.line.end,
+                        replacement: String::new(),
+                    }
+                } else {
+                    // Drop the entry along with the separator following it, or preceding it if
+                    // it's the last one
+                    let before = &frontmatter[entry.value.start..start];
+                    let after = &frontmatter[end..entry.value.end];
+                    let following = after.len() - after.trim_start_matches([',', ' ']).len();
+                    let preceding = before.len() - before.trim_end_matches([',', ' ']).len();
+                    TextEdit {
+                        range: if following > 0 {
+                            offset + start..offset + end + following
+                        } else {
+                            offset + start - preceding..offset + end
+                        },
+                        replacement: String::new(),
+                    }
+                }
+            } else {
+                TextEdit {
+                    range: offset + start..offset + start + rules.source_len(entry.text, old.len()),
+                    replacement: new.to_string(),
+                }
+            };
+            Some(edit)
+        })
+        .collect()
+}
+
+/// Plan the edits rewriting the tags of the `tags` attribute of a YAML frontmatter block, starting
+/// at byte `offset` of the file, into their canonical form
+pub fn canonical_edits(frontmatter: &str, offset: usize, rules: &TagRules) -> Vec<TextEdit> {
+    entries(frontmatter)
+        .into_iter()
+        .filter_map(|entry| {
+            let canonical = rules.normalise(entry.text);
+            (canonical != entry.text).then(|| TextEdit {
+                range: offset + entry.range.start..offset + entry.range.end,
+                replacement: canonical,
+            })
+        })
+        .collect()
+}
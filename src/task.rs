@@ -0,0 +1,42 @@
+use std::ops::Range;
+
+use serde::Serialize;
+
+use crate::pos::Pos;
+
+/// A `- [ ]` or `- [x]` item of a task list in the body of a note
+#[derive(Debug, Serialize, Clone, Hash, PartialEq, Eq)]
+pub struct Task {
+    /// The source of the item after its checkbox, on the line of the checkbox
+    pub text: String,
+    pub done: bool,
+    /// The text of the closest heading before the task, if there is one
+    pub heading: Option<String>,
+    /// The position of the checkbox
+    pub pos: Pos,
+    /// The byte range of the checkbox, including its brackets, in the source file
+    pub range: Range<usize>,
+}
+
+impl Task {
+    /// The task whose checkbox spans `range` in the source of a note
+    pub fn new(
+        contents: &str,
+        range: Range<usize>,
+        done: bool,
+        pos: Pos,
+        heading: Option<String>,
+    ) -> Self {
+        let rest = &contents[range.end..];
+        let text = rest[..rest.find('\n').unwrap_or(rest.len())]
+            .trim()
+            .to_string();
+        Task {
+            text,
+            done,
+            heading,
+            pos,
+            range,
+        }
+    }
+}
@@ -1,7 +1,40 @@
-use std::{collections::HashMap, fs, io, path::PathBuf};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs, io,
+    path::{Path, PathBuf},
+};
 
 use once_cell::sync::Lazy;
 use regex::{Captures, Regex};
+use thiserror::Error;
+
+/// Regex to find `{{template}}` substrings to replace
+static REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\{\{\s*([a-zA-Z_][a-zA-Z0-9_]*)\s*\}\}").unwrap());
+
+/// Regex to find `{{> partial}}` substrings, replaced by the contents of another template
+static PARTIAL: Lazy<Regex> = Lazy::new(|| Regex::new(r"\{\{\s*>\s*([^\s{}]+)\s*\}\}").unwrap());
+
+/// Regex to find the `{{< layout}}` line a template may start with, to extend another template
+static LAYOUT: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\A\s*\{\{\s*<\s*([^\s{}]+)\s*\}\}[ \t]*\r?\n?").unwrap());
+
+/// Where the body of the templates extending a layout goes in the layout
+const SLOT: &str = "{{@body}}";
+
+#[derive(Debug, Error)]
+pub enum TemplateError {
+    #[error("the template requires the variables {}", .0.join(", "))]
+    MissingVariables(Vec<String>),
+    #[error("there is no template `{0}` in the templates directory")]
+    UnknownTemplate(String),
+    #[error("the template `{0}` includes itself")]
+    RecursiveTemplate(String),
+    #[error("the layout `{0}` has no `{SLOT}` to put the body of the template in")]
+    MissingSlot(String),
+    #[error("failed to read the template `{path}` because {reason}")]
+    ReadFailed { path: PathBuf, reason: String },
+}
 
 #[derive(Debug)]
 pub struct Template {
@@ -18,13 +51,11 @@ impl Template {
         let fields = fields
             // Split the input into pairs...
             .split(",")
+            .filter(|pair| !pair.is_empty())
             // and split the pairs into keys and values
             .map(|pair| {
-                let splitted: Vec<&str> = pair.split(":").collect();
-                (
-                    splitted.first().unwrap().to_string(),
-                    splitted.get(1).unwrap().to_string(),
-                )
+                let (key, value) = pair.split_once(":").unwrap_or((pair, ""));
+                (key.to_string(), value.to_string())
             })
             .collect();
         Self {
@@ -33,11 +64,121 @@ impl Template {
         }
     }
 
+    /// Resolve the partials and the layout of the template from the given templates directories
+    pub fn resolve(&mut self, dirs: &[PathBuf]) -> Result<(), TemplateError> {
+        self.text = Self::expand(&self.text, dirs)?;
+        Ok(())
+    }
+
+    /// Expand the template text, found in the given templates directories:
+    ///
+    /// - `{{> name}}` is replaced by the template `name`, so that common blocks such as
+    ///   frontmatter or footers can be shared,
+    /// - a template starting with a `{{< name}}` line extends the layout `name`: the rest of the
+    ///   template is put in place of the `{{@body}}` of the layout.
+    ///
+    /// Templates are looked up by their path relative to a templates directory, with or without
+    /// their `.md` extension, and may themselves include or extend other templates.
+    pub fn expand(text: &str, dirs: &[PathBuf]) -> Result<String, TemplateError> {
+        Self::expand_with(text, dirs, &mut Vec::new())
+    }
+
+    /// Expand the template text, where `stack` holds the templates being expanded, to catch
+    /// templates including themselves
+    fn expand_with(
+        text: &str,
+        dirs: &[PathBuf],
+        stack: &mut Vec<String>,
+    ) -> Result<String, TemplateError> {
+        let (text, layout) = match LAYOUT.captures(text) {
+            Some(caps) => (
+                &text[caps.get(0).unwrap().end()..],
+                Some(caps[1].to_string()),
+            ),
+            None => (text, None),
+        };
+
+        let mut res = String::new();
+        let mut last = 0;
+        for caps in PARTIAL.captures_iter(text) {
+            let whole = caps.get(0).unwrap();
+            res.push_str(&text[last..whole.start()]);
+            // A partial usually sits on a line of its own, which already ends with a line break
+            let partial = Self::include(&caps[1], dirs, stack)?;
+            res.push_str(partial.strip_suffix('\n').unwrap_or(&partial));
+            last = whole.end();
+        }
+        res.push_str(&text[last..]);
+
+        match layout {
+            Some(name) => {
+                let layout = Self::include(&name, dirs, stack)?;
+                if !layout.contains(SLOT) {
+                    return Err(TemplateError::MissingSlot(name));
+                }
+                Ok(layout.replace(SLOT, res.strip_suffix('\n').unwrap_or(&res)))
+            }
+            None => Ok(res),
+        }
+    }
+
+    /// Read and expand the template `name` from the first templates directory which has it
+    fn include(
+        name: &str,
+        dirs: &[PathBuf],
+        stack: &mut Vec<String>,
+    ) -> Result<String, TemplateError> {
+        if stack.iter().any(|included| included == name) {
+            return Err(TemplateError::RecursiveTemplate(name.to_string()));
+        }
+        let path = Self::locate(name, dirs)
+            .ok_or_else(|| TemplateError::UnknownTemplate(name.to_string()))?;
+        let text = Self::read(&path)?;
+
+        stack.push(name.to_string());
+        let res = Self::expand_with(&text, dirs, stack);
+        stack.pop();
+        res
+    }
+
+    /// The file of the template `name` in the first templates directory which has it
+    pub fn locate(name: &str, dirs: &[PathBuf]) -> Option<PathBuf> {
+        dirs.iter()
+            .flat_map(|dir| [dir.join(name), dir.join(format!("{name}.md"))])
+            .find(|path| path.is_file())
+    }
+
+    fn read(path: &Path) -> Result<String, TemplateError> {
+        fs::read_to_string(path).map_err(|e| TemplateError::ReadFailed {
+            path: path.to_path_buf(),
+            reason: e.to_string(),
+        })
+    }
+
+    /// The names of the variables used in the given template text
+    pub fn variables(text: &str) -> BTreeSet<String> {
+        REGEX
+            .captures_iter(text)
+            .filter_map(|caps| caps.get(1))
+            .map(|name| name.as_str().to_string())
+            .collect()
+    }
+
+    /// Ensure that every variable used in the template was given a value
+    pub fn validate(&self) -> Result<(), TemplateError> {
+        let missing: Vec<String> = Self::variables(&self.text)
+            .into_iter()
+            .filter(|name| !self.variables.contains_key(name))
+            .collect();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(TemplateError::MissingVariables(missing))
+        }
+    }
+
     /// Replace the variables in the template with the appropriate values
     pub fn render(&self) -> String {
-        /// Regex to find `{{template}}` substrings to replace
-        static REGEX: Lazy<Regex> =
-            Lazy::new(|| Regex::new(r"\{\{\s*([a-zA-Z_][a-zA-Z0-9_]*)\s*\}\}").unwrap());
         REGEX
             .replace_all(&self.text, |caps: &Captures<'_>| {
                 self.variables
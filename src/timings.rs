@@ -0,0 +1,61 @@
+use std::{
+    fmt::Display,
+    time::{Duration, Instant},
+};
+
+use serde::{Serialize, Serializer};
+
+/// How long a phase of a subcommand took
+#[derive(Debug, Clone, Serialize)]
+pub struct Phase {
+    pub name: &'static str,
+    #[serde(rename = "ms", serialize_with = "milliseconds")]
+    pub duration: Duration,
+}
+
+fn milliseconds<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64() * 1000.0)
+}
+
+/// The duration of each phase of a subcommand, in the order they ran, so that users can see where
+/// the time goes. A phase which runs several times, e.g. ranking, is reported once per run.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Timings {
+    pub phases: Vec<Phase>,
+}
+
+impl Timings {
+    /// Run `f`, recording how long it took under `name`
+    pub fn time<T>(&mut self, name: &'static str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let res = f();
+        self.phases.push(Phase {
+            name,
+            duration: start.elapsed(),
+        });
+        res
+    }
+
+    pub fn extend(&mut self, other: &Timings) {
+        self.phases.extend(other.phases.iter().cloned());
+    }
+
+    pub fn total(&self) -> Duration {
+        self.phases.iter().map(|phase| phase.duration).sum()
+    }
+}
+
+impl Display for Timings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ms = |duration: Duration| format!("{:.3}", duration.as_secs_f64() * 1000.0);
+        let mut builder = tabled::builder::Builder::new();
+        builder.push_record(["Phase", "Duration (ms)"]);
+        self.phases.iter().for_each(|phase| {
+            builder.push_record([phase.name.to_string(), ms(phase.duration)]);
+        });
+        builder.push_record(["total".to_string(), ms(self.total())]);
+        let mut table = builder.build();
+        table.with(tabled::settings::style::Style::rounded());
+        writeln!(f, "{table}")
+    }
+}
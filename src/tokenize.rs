@@ -0,0 +1,126 @@
+use std::{fmt::Debug, str::FromStr};
+
+use proptest::prelude::*;
+
+/// Splits text into the terms the search index is made of. Documents and queries go through the
+/// same tokenizer, so that their terms can be compared as they are.
+pub trait Tokenize: Debug + Send + Sync {
+    fn tokenize(&self, text: &str) -> Vec<String>;
+}
+
+/// Split on whitespace and lowercase ASCII letters, leaving punctuation attached to words
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Whitespace;
+
+impl Tokenize for Whitespace {
+    fn tokenize(&self, text: &str) -> Vec<String> {
+        text.split_whitespace()
+            .map(str::to_ascii_lowercase)
+            .collect()
+    }
+}
+
+/// Split into words of letters, digits and inner apostrophes, lowercased
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Unicode;
+
+impl Tokenize for Unicode {
+    fn tokenize(&self, text: &str) -> Vec<String> {
+        // Lowercase first, as lowercasing can turn a letter into several characters
+        text.to_lowercase()
+            .split(|c: char| !c.is_alphanumeric() && c != '\'')
+            .map(|word| word.trim_matches('\''))
+            .filter(|word| !word.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+/// Like [`Unicode`], but Chinese and Japanese text, which isn't separated by spaces, is split into
+/// overlapping pairs of characters
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Cjk;
+
+impl Cjk {
+    fn is_cjk(c: char) -> bool {
+        matches!(c,
+            '\u{3040}'..='\u{30FF}' // Hiragana and Katakana
+            | '\u{3400}'..='\u{4DBF}' // CJK Unified Ideographs Extension A
+            | '\u{4E00}'..='\u{9FFF}' // CJK Unified Ideographs
+            | '\u{F900}'..='\u{FAFF}' // CJK Compatibility Ideographs
+        )
+    }
+}
+
+impl Tokenize for Cjk {
+    fn tokenize(&self, text: &str) -> Vec<String> {
+        let mut terms = Vec::new();
+        let mut rest = text;
+        while !rest.is_empty() {
+            let cjk = Self::is_cjk(rest.chars().next().unwrap());
+            let end = rest.find(|c| Self::is_cjk(c) != cjk).unwrap_or(rest.len());
+            let (run, tail) = rest.split_at(end);
+            if cjk {
+                let chars: Vec<char> = run.chars().collect();
+                match chars.len() {
+                    1 => terms.push(run.to_string()),
+                    _ => terms.extend(chars.windows(2).map(|pair| pair.iter().collect())),
+                }
+            } else {
+                terms.extend(Unicode.tokenize(run));
+            }
+            rest = tail;
+        }
+        terms
+    }
+}
+
+/// The tokenizers a vault can be configured with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Tokenizer {
+    Whitespace,
+    #[default]
+    Unicode,
+    Cjk,
+}
+
+impl Tokenizer {
+    pub fn get(self) -> &'static dyn Tokenize {
+        match self {
+            Tokenizer::Whitespace => &Whitespace,
+            Tokenizer::Unicode => &Unicode,
+            Tokenizer::Cjk => &Cjk,
+        }
+    }
+}
+
+impl FromStr for Tokenizer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "whitespace" => Ok(Tokenizer::Whitespace),
+            "unicode" => Ok(Tokenizer::Unicode),
+            "cjk" => Ok(Tokenizer::Cjk),
+            other => Err(format!(
+                "`{other}` is not a tokenizer; the tokenizers are whitespace, unicode and cjk"
+            )),
+        }
+    }
+}
+
+proptest! {
+    #[test]
+    /// Tokenizing a term must give back the term itself, so that query terms match the terms of
+    /// the index
+    fn unicode_idempotence(text in any::<String>()) {
+        for term in Unicode.tokenize(&text) {
+            prop_assert_eq!(Unicode.tokenize(&term), vec![term.clone()]);
+        }
+    }
+
+    #[test]
+    fn cjk_without_cjk_text(text in "[a-zA-Z0-9 ,.'!?-]*") {
+        prop_assert_eq!(Cjk.tokenize(&text), Unicode.tokenize(&text));
+    }
+}
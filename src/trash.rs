@@ -0,0 +1,351 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// The folder, relative to the root of the vault, holding the trashed versions of files
+pub const DIR: &str = ".trash";
+
+const MANIFEST: &str = "manifest.json";
+const FILES: &str = "files";
+
+#[derive(Debug, Error)]
+pub enum TrashError {
+    #[error("failed to {action} `{path}` because {reason}")]
+    Io {
+        action: &'static str,
+        path: PathBuf,
+        reason: String,
+    },
+    #[error("`{0}` is outside of the vault, so it cannot be trashed")]
+    OutsideVault(PathBuf),
+    #[error("there is no batch `{0}` in the trash")]
+    UnknownBatch(String),
+    #[error("the trash is empty")]
+    Empty,
+}
+
+/// A file as it was before an operation modified, moved or removed it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    /// The path of the file relative to the root of the vault
+    pub path: PathBuf,
+    /// Where the operation moved the file, relative to the root of the vault, if it did
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub moved_to: Option<PathBuf>,
+}
+
+/// The files trashed by a single operation, which are restored together
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Batch {
+    /// The name of the batch in the trash, from when it was trashed
+    pub id: String,
+    /// When the files were trashed, in milliseconds since the epoch
+    pub trashed: u64,
+    pub entries: Vec<Entry>,
+}
+
+/// The trash of a vault, kept in its `.trash` folder. Before an operation overwrites, moves or
+/// removes files, their current contents are copied there as a batch, which `n trash restore`
+/// puts back. Batches older than the retention are purged whenever a new one is made.
+#[derive(Debug, Clone)]
+pub struct Trash {
+    root: PathBuf,
+    retention: Duration,
+}
+
+fn io_error(action: &'static str, path: &Path) -> impl FnOnce(io::Error) -> TrashError {
+    let path = path.to_path_buf();
+    move |e| TrashError::Io {
+        action,
+        path,
+        reason: e.to_string(),
+    }
+}
+
+impl Trash {
+    /// The trash of the vault at `root`, which must be canonical like the paths of the files
+    pub fn new(root: PathBuf, retention: Duration) -> Self {
+        Self { root, retention }
+    }
+
+    /// The root of the vault the trash belongs to
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn dir(&self) -> PathBuf {
+        self.root.join(DIR)
+    }
+
+    /// The path of a file relative to the root of the vault. The file itself may not exist, as
+    /// for the destination of a move, but its folder must.
+    fn relative(&self, path: &Path) -> Result<PathBuf, TrashError> {
+        let canonical = path
+            .parent()
+            .and_then(|dir| dir.canonicalize().ok())
+            .zip(path.file_name())
+            .map(|(dir, name)| dir.join(name));
+        [Some(path.to_path_buf()), canonical]
+            .into_iter()
+            .flatten()
+            .find_map(|path| Some(path.strip_prefix(&self.root).ok()?.to_path_buf()))
+            .ok_or_else(|| TrashError::OutsideVault(path.to_path_buf()))
+    }
+
+    /// Copy the given files, along with where they are about to be moved, into a new batch, and
+    /// purge the batches past the retention
+    pub fn store(&self, files: &[(&Path, Option<&Path>)]) -> Result<Batch, TrashError> {
+        let batch = self.save(files)?;
+        self.purge(batch.trashed)?;
+        Ok(batch)
+    }
+
+    fn save(&self, files: &[(&Path, Option<&Path>)]) -> Result<Batch, TrashError> {
+        let trashed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis() as u64);
+        // Batches made within the same millisecond get a suffix
+        let (id, dir) = (0..)
+            .map(|n| match n {
+                0 => trashed.to_string(),
+                n => format!("{trashed}-{n}"),
+            })
+            .map(|id| {
+                let dir = self.dir().join(&id);
+                (id, dir)
+            })
+            .find(|(_, dir)| !dir.exists())
+            .unwrap();
+
+        let entries = files
+            .iter()
+            .map(|&(path, moved_to)| {
+                Ok(Entry {
+                    path: self.relative(path)?,
+                    moved_to: moved_to.map(|to| self.relative(to)).transpose()?,
+                })
+            })
+            .collect::<Result<Vec<Entry>, TrashError>>()?;
+        for entry in &entries {
+            let copy = dir.join(FILES).join(&entry.path);
+            if let Some(parent) = copy.parent() {
+                fs::create_dir_all(parent).map_err(io_error("create", parent))?;
+            }
+            let original = self.root.join(&entry.path);
+            fs::copy(&original, &copy).map_err(io_error("trash", &original))?;
+        }
+
+        let batch = Batch {
+            id,
+            trashed,
+            entries,
+        };
+        let manifest = dir.join(MANIFEST);
+        fs::write(&manifest, serde_json::to_string(&batch).unwrap())
+            .map_err(io_error("write", &manifest))?;
+        Ok(batch)
+    }
+
+    /// Remove the batches trashed longer ago than the retention
+    fn purge(&self, now: u64) -> Result<(), TrashError> {
+        let cutoff = now.saturating_sub(self.retention.as_millis() as u64);
+        self.batches()?
+            .into_iter()
+            .filter(|batch| batch.trashed < cutoff)
+            .try_for_each(|batch| {
+                let dir = self.dir().join(&batch.id);
+                fs::remove_dir_all(&dir).map_err(io_error("purge", &dir))
+            })
+    }
+
+    /// The batches in the trash, oldest first. Folders which aren't batches are left out.
+    pub fn batches(&self) -> Result<Vec<Batch>, TrashError> {
+        let dir = self.dir();
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(io_error("read", &dir)(e)),
+        };
+        let mut batches: Vec<Batch> = entries
+            .filter_map(Result::ok)
+            .filter_map(|entry| fs::read_to_string(entry.path().join(MANIFEST)).ok())
+            .filter_map(|manifest| serde_json::from_str(&manifest).ok())
+            .collect();
+        batches.sort_by(|a, b| a.trashed.cmp(&b.trashed).then_with(|| a.id.cmp(&b.id)));
+        Ok(batches)
+    }
+
+    /// Put back the files of a batch, the latest one by default, as they were before the operation
+    /// which trashed them. Moved files are moved back. The files this overwrites are trashed in
+    /// turn, so that restoring can be undone as well.
+    pub fn restore(&self, id: Option<&str>) -> Result<Batch, TrashError> {
+        let batches = self.batches()?;
+        let batch = match id {
+            Some(id) => batches
+                .into_iter()
+                .find(|batch| batch.id == id)
+                .ok_or_else(|| TrashError::UnknownBatch(id.to_string()))?,
+            None => batches.into_iter().last().ok_or(TrashError::Empty)?,
+        };
+
+        // The current versions of the files, where moving a file back is undone by moving it
+        // forth again
+        let mut current: Vec<(PathBuf, Option<PathBuf>)> = Vec::new();
+        for entry in &batch.entries {
+            let path = self.root.join(&entry.path);
+            if path.is_file() {
+                current.push((path.clone(), None));
+            }
+            if let Some(moved) = entry.moved_to.as_ref().map(|to| self.root.join(to))
+                && moved.is_file()
+            {
+                current.push((moved, (!path.is_file()).then_some(path)));
+            }
+        }
+        if !current.is_empty() {
+            let files: Vec<(&Path, Option<&Path>)> = current
+                .iter()
+                .map(|(path, moved_to)| (path.as_path(), moved_to.as_deref()))
+                .collect();
+            self.save(&files)?;
+        }
+
+        let dir = self.dir().join(&batch.id);
+        for entry in &batch.entries {
+            let target = self.root.join(&entry.path);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent).map_err(io_error("create", parent))?;
+            }
+            let copy = dir.join(FILES).join(&entry.path);
+            fs::copy(&copy, &target).map_err(io_error("restore", &target))?;
+        }
+        for moved in batch
+            .entries
+            .iter()
+            .filter_map(|entry| entry.moved_to.as_ref())
+        {
+            let moved = self.root.join(moved);
+            match fs::remove_file(&moved) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                    return Err(io_error("remove", &moved)(e));
+                }
+                _ => {}
+            }
+        }
+        fs::remove_dir_all(&dir).map_err(io_error("remove", &dir))?;
+        Ok(batch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    const DAY: Duration = Duration::from_secs(86_400);
+
+    fn trash(dir: &TempDir) -> Trash {
+        Trash::new(dir.path().to_path_buf(), 30 * DAY)
+    }
+
+    /// Make a batch look as if it was trashed `age` earlier than it was
+    fn backdate(trash: &Trash, batch: &Batch, age: Duration) {
+        let batch = Batch {
+            trashed: batch.trashed - age.as_millis() as u64,
+            ..batch.clone()
+        };
+        let manifest = trash.dir().join(&batch.id).join(MANIFEST);
+        fs::write(manifest, serde_json::to_string(&batch).unwrap()).unwrap();
+    }
+
+    fn ids(trash: &Trash) -> Vec<String> {
+        trash
+            .batches()
+            .unwrap()
+            .into_iter()
+            .map(|batch| batch.id)
+            .collect()
+    }
+
+    #[test]
+    /// Restoring a batch puts back edited and moved files, and can itself be undone
+    fn restore_round_trip() {
+        let dir = TempDir::new(&[("a.md", "old a"), ("sub/b.md", "old b")]);
+        let trash = trash(&dir);
+        let (a, b, c) = (dir.join("a.md"), dir.join("sub/b.md"), dir.join("c.md"));
+
+        let batch = trash.store(&[(&a, None), (&b, Some(&c))]).unwrap();
+        assert_eq!(batch.entries[1].path, PathBuf::from("sub/b.md"));
+        assert_eq!(batch.entries[1].moved_to, Some(PathBuf::from("c.md")));
+        dir.write("a.md", "new a");
+        dir.write("c.md", "new b");
+        fs::remove_file(&b).unwrap();
+
+        assert_eq!(trash.restore(None).unwrap().id, batch.id);
+        assert_eq!(dir.read("a.md"), "old a");
+        assert_eq!(dir.read("sub/b.md"), "old b");
+        assert!(!c.exists());
+
+        // The versions which were overwritten went to the trash in turn
+        assert_eq!(trash.batches().unwrap().len(), 1);
+        trash.restore(None).unwrap();
+        assert_eq!(dir.read("a.md"), "new a");
+        assert_eq!(dir.read("c.md"), "new b");
+        assert!(!b.exists());
+        assert_eq!(trash.batches().unwrap().len(), 1);
+    }
+
+    #[test]
+    /// A file changed again after it was trashed is still restored, and its latest version is
+    /// trashed rather than lost
+    fn restore_changed_file() {
+        let dir = TempDir::new(&[("a.md", "original")]);
+        let trash = trash(&dir);
+
+        trash.store(&[(&dir.join("a.md"), None)]).unwrap();
+        dir.write("a.md", "edited");
+        dir.write("a.md", "edited by hand since");
+
+        trash.restore(None).unwrap();
+        assert_eq!(dir.read("a.md"), "original");
+        trash.restore(None).unwrap();
+        assert_eq!(dir.read("a.md"), "edited by hand since");
+    }
+
+    #[test]
+    /// Making a batch purges the ones older than the retention, and only those
+    fn purge_by_retention() {
+        let dir = TempDir::new(&[("a.md", "a")]);
+        let trash = trash(&dir);
+        let a = dir.join("a.md");
+        let files = [(a.as_path(), None)];
+
+        let expired = trash.save(&files).unwrap();
+        backdate(&trash, &expired, 31 * DAY);
+        let kept = trash.save(&files).unwrap();
+        backdate(&trash, &kept, 29 * DAY);
+        assert_eq!(ids(&trash), [expired.id.clone(), kept.id.clone()]);
+
+        let latest = trash.store(&files).unwrap();
+        assert_eq!(ids(&trash), [kept.id, latest.id]);
+        assert!(!trash.dir().join(&expired.id).exists());
+    }
+
+    #[test]
+    fn restore_missing_batch() {
+        let dir = TempDir::new(&[("a.md", "a")]);
+        let trash = trash(&dir);
+
+        assert!(matches!(trash.restore(None), Err(TrashError::Empty)));
+        trash.store(&[(&dir.join("a.md"), None)]).unwrap();
+        assert!(matches!(
+            trash.restore(Some("0")),
+            Err(TrashError::UnknownBatch(id)) if id == "0"
+        ));
+    }
+}
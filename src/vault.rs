@@ -1,18 +1,69 @@
-use std::{collections::HashMap, fmt::Display, path::PathBuf};
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    ops::Range,
+    path::{Path, PathBuf},
+};
 
 use owo_colors::OwoColorize;
-use rayon::iter::{IntoParallelRefIterator, ParallelBridge, ParallelIterator};
+use percent_encoding::percent_decode_str;
+use rayon::iter::{
+    IndexedParallelIterator, IntoParallelIterator, IntoParallelRefIterator, ParallelIterator,
+};
 use serde::Serialize;
 use thiserror::Error;
 
-use crate::{document::Document, path::MarkdownPath, query::Query, search::Corpus};
+use crate::{
+    config::{Config, ConfigError},
+    date::Date,
+    document::{Document, ParseError, Value},
+    edit::{FileEdit, TextEdit},
+    heading::{self, Heading, Section, slugify},
+    hierarchy::Hierarchy,
+    kind::Kind,
+    path::{self, MarkdownPath},
+    query::Query,
+    search::Corpus,
+    tag,
+    timings::Timings,
+    trash::Trash,
+};
+
+/// A small handle to a document of a vault, cheaper to hash and to store than its path
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub struct DocId(u32);
+
+impl DocId {
+    #[inline]
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
 
 /// A collection of notes
 #[derive(Debug, Serialize)]
 pub struct Vault {
     path: PathBuf,
-    documents: HashMap<MarkdownPath, Document>,
+    /// The documents, sorted by path and indexed by their `DocId`
+    documents: Vec<Document>,
+    #[serde(skip)]
+    ids: HashMap<MarkdownPath, DocId>,
+    /// The documents each document links to, once per link, indexed by `DocId`
+    outbound: Vec<Vec<DocId>>,
+    /// The documents linking to each document, once per link, indexed by `DocId`
+    #[serde(skip)]
+    inbound: Vec<Vec<DocId>>,
+    /// The statistics of the stripped text of every document, indexed by `DocId`. They are left
+    /// out of the JSON of the vault, and dumped on their own by `n index dump` instead.
+    #[serde(skip)]
     corpus: Corpus,
+    #[serde(skip)]
+    config: Config,
+    /// Whether notes marked as `draft` or `private` are part of the results
+    include_drafts: bool,
+    /// How long each phase of loading the vault took
+    #[serde(skip)]
+    timings: Timings,
 }
 
 impl Display for Vault {
@@ -34,75 +85,282 @@ impl Display for Vault {
 pub enum VaultInitialisationError {
     #[error("the directory `{path}` cannot be opened because {reason}")]
     ReadDirFailed { path: PathBuf, reason: String },
+    #[error(transparent)]
+    InvalidConfig(#[from] ConfigError),
     // #[error("the file `{path}` in the vault cannot be initialised as a document because {reason}")]
     // CannotInitialiseDocument { path: PathBuf, reason: String },
 }
 
+#[derive(Debug, Error)]
+pub enum EditError {
+    #[error("the document `{path}` is not part of the vault")]
+    UnknownDocument { path: PathBuf },
+    #[error("the document `{path}` has no heading `{heading}`")]
+    UnknownHeading { path: PathBuf, heading: String },
+    #[error("no note is tagged `{tag}`")]
+    UnknownTag { tag: String },
+    #[error("some notes are already tagged `{tag}`; merge the tags instead")]
+    TagExists { tag: String },
+    #[error("`{path}` already exists")]
+    AlreadyExists { path: PathBuf },
+    #[error(
+        "`{path}` has no `{}` line to put the table of contents under",
+        heading::TOC_START
+    )]
+    NoTocMarker { path: PathBuf },
+    #[error("`{name}` cannot be used as the name of a file")]
+    InvalidName { name: String },
+    #[error(transparent)]
+    ReadFailed(#[from] ParseError),
+}
+
 impl Vault {
     #[inline]
     pub fn path(&self) -> PathBuf {
         self.path.clone()
     }
+    /// The documents of the vault, leaving out drafts unless they were explicitly included
     #[inline]
     pub fn documents(&self) -> Vec<&Document> {
-        self.documents.values().collect()
+        self.documents
+            .iter()
+            .filter(|doc| self.is_visible(doc))
+            .collect()
+    }
+
+    /// The IDs of the documents returned by [`Vault::documents`]
+    pub fn ids(&self) -> Vec<DocId> {
+        self.documents
+            .iter()
+            .zip(0..)
+            .filter(|(doc, _)| self.is_visible(doc))
+            .map(|(_, id)| DocId(id))
+            .collect()
+    }
+
+    /// The number of documents of the vault, drafts included, which every `DocId` is below
+    #[inline]
+    pub fn num_docs(&self) -> usize {
+        self.documents.len()
+    }
+
+    #[inline]
+    pub fn document(&self, id: DocId) -> &Document {
+        &self.documents[id.index()]
+    }
+
+    #[inline]
+    pub fn id(&self, path: &MarkdownPath) -> Option<DocId> {
+        self.ids.get(path).copied()
+    }
+
+    /// The documents of the vault the given one links to, once per link
+    #[inline]
+    pub fn outbound(&self, id: DocId) -> &[DocId] {
+        &self.outbound[id.index()]
+    }
+
+    /// The documents of the vault linking to the given one, once per link
+    #[inline]
+    pub fn inbound(&self, id: DocId) -> &[DocId] {
+        &self.inbound[id.index()]
+    }
+
+    #[inline]
+    pub fn set_include_drafts(&mut self, include_drafts: bool) {
+        self.include_drafts = include_drafts;
+    }
+
+    #[inline]
+    pub fn is_visible(&self, document: &Document) -> bool {
+        self.include_drafts || !document.is_draft()
+    }
+
+    #[inline]
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    #[inline]
+    pub fn corpus(&self) -> &Corpus {
+        &self.corpus
+    }
+
+    #[inline]
+    pub fn timings(&self) -> &Timings {
+        &self.timings
+    }
+
+    /// The trash the files edited by the commands are copied to
+    pub fn trash(&self) -> Trash {
+        let root = self.path.canonicalize().unwrap_or_else(|_| self.path());
+        Trash::new(root, self.config.trash_retention)
+    }
+
+    /// The directories holding the templates of the vault
+    pub fn template_dirs(&self) -> Vec<PathBuf> {
+        self.config
+            .kinds
+            .get(&Kind::Template)
+            .into_iter()
+            .flatten()
+            .map(|folder| self.path.join(folder))
+            .collect()
     }
 
     #[inline]
     pub fn get_document(&self, path: &MarkdownPath) -> Option<&Document> {
-        self.documents.get(path)
+        self.id(path).map(|id| self.document(id))
     }
     pub fn new(base_path: PathBuf) -> Result<Self, VaultInitialisationError> {
-        let documents: HashMap<MarkdownPath, Document> = base_path
-            .read_dir()
-            .map_err(|reason| VaultInitialisationError::ReadDirFailed {
-                path: base_path.clone(),
-                reason: reason.to_string(),
-            })?
-            .par_bridge()
-            .filter_map(|path| match path {
+        let config = Config::load(&base_path)?;
+        let root = base_path
+            .canonicalize()
+            .unwrap_or_else(|_| base_path.clone());
+
+        let mut timings = Timings::default();
+
+        let paths = timings.time("scan", || Self::walk(&base_path))?;
+        let mut documents: Vec<Document> = timings.time("parse", || {
+            paths
+                .into_par_iter()
                 // TODO: Log this error. We don't want one broken file to block the initialisation
                 // process, but we also might want to optionally know which file failed.
-                Ok(file) => Document::new(base_path.clone(), file.path().clone()).ok(),
-                // TODO: This one, too.
-                Err(_) => None,
-            })
-            .map(|document| (document.path(), document))
+                .filter_map(|path| Document::new(base_path.clone(), path).ok())
+                .map(|mut document| {
+                    document.set_kind(Kind::infer(&document, &root, &config));
+                    document.normalise_tags(&config.tags);
+                    document
+                })
+                .collect()
+        });
+        // Sort the documents so that their IDs don't change from one run to the next
+        documents.sort_unstable_by_key(Document::path);
+
+        let ids: HashMap<MarkdownPath, DocId> = documents
+            .iter()
+            .zip(0..)
+            .map(|(doc, id)| (doc.path(), DocId(id)))
             .collect();
 
-        let corpus = Corpus::new(
-            documents
+        // Resolve every link once, relative to the directory of the document it's in
+        let (outbound, inbound) = timings.time("links", || {
+            let outbound: Vec<Vec<DocId>> = documents
                 .par_iter()
-                .map(|(_, doc)| doc.stripped().unwrap())
-                .collect(),
-        );
+                .map(|doc| {
+                    let base_path = doc.path().dir();
+                    doc.links()
+                        .into_iter()
+                        .filter_map(|link| link.to_markdown_path(base_path.clone()))
+                        .filter_map(|target| ids.get(&target).copied())
+                        .collect()
+                })
+                .collect();
+            let mut inbound = vec![Vec::new(); documents.len()];
+            for (targets, src) in outbound.iter().zip(0..) {
+                for target in targets {
+                    inbound[target.index()].push(DocId(src));
+                }
+            }
+            (outbound, inbound)
+        });
+
+        let corpus = timings.time("corpus", || {
+            Corpus::new(
+                documents
+                    .par_iter()
+                    .map(|doc| doc.stripped().unwrap())
+                    .collect(),
+                config.tokenizer.get(),
+            )
+        });
 
         Ok(Vault {
             path: base_path,
             documents,
+            ids,
+            outbound,
+            inbound,
             corpus,
+            config,
+            include_drafts: false,
+            timings,
         })
     }
 
-    pub fn search(&self, query: String) -> HashMap<Document, f32> {
-        let documents = &self.documents;
-        documents
-            .par_iter()
-            .map(|(_, doc)| {
-                (
-                    doc,
-                    self.corpus
-                        .score(query.as_str(), doc.stripped().unwrap().as_str()),
-                )
+    /// Recursively list the files inside of `dir`, skipping hidden files and directories such as
+    /// `.git` or `.n`.
+    pub fn walk(dir: &Path) -> Result<Vec<PathBuf>, VaultInitialisationError> {
+        let entries = dir
+            .read_dir()
+            .map_err(|reason| VaultInitialisationError::ReadDirFailed {
+                path: dir.to_path_buf(),
+                reason: reason.to_string(),
+            })?;
+
+        let mut files = Vec::new();
+        // TODO: Log the entries which can't be read, like the files which fail to parse in `new`
+        for entry in entries.filter_map(Result::ok) {
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            let path = entry.path();
+            if file_type.is_dir() {
+                // An unreadable subdirectory shouldn't prevent the rest of the vault from loading
+                files.extend(Self::walk(&path).unwrap_or_default());
+            } else if file_type.is_symlink() && path.is_dir() {
+                // Symlinked directories are skipped, as they may point back into the vault and
+                // never end
+                continue;
+            } else {
+                files.push(path);
+            }
+        }
+        Ok(files)
+    }
+
+    /// Score every visible document against the query, in the order of their IDs. The documents
+    /// themselves can be looked up with [`Vault::document`].
+    pub fn search(&self, query: &str) -> Vec<(DocId, f32)> {
+        self.ids()
+            .into_par_iter()
+            .map(|id| (id, self.corpus.score(query, id.index())))
+            .collect()
+    }
+    /// Score every section of the visible documents against the query, i.e. the text before their
+    /// first heading, and every heading along with the text up to the next one. The statistics
+    /// are computed over the sections rather than over whole documents.
+    pub fn search_sections(&self, query: &str) -> Vec<(DocId, Option<Heading>, f32)> {
+        let sections: Vec<(DocId, Option<Heading>, String)> = self
+            .ids()
+            .into_par_iter()
+            .flat_map_iter(|id| {
+                let sections = self.document(id).stripped_sections().unwrap_or_default();
+                sections
+                    .into_iter()
+                    .map(move |(heading, text)| (id, heading, text))
             })
-            .map(|(k, v)| (k.to_owned(), v))
+            .collect();
+        let corpus = Corpus::new(
+            sections.iter().map(|(_, _, text)| text.clone()).collect(),
+            self.config.tokenizer.get(),
+        );
+        sections
+            .into_par_iter()
+            .enumerate()
+            .map(|(i, (id, heading, _))| (id, heading, corpus.score(query, i)))
             .collect()
     }
+
     /// Get the list of documents which references the given document
     pub fn find_backlinks(&self, path: &MarkdownPath) -> Vec<MarkdownPath> {
         self.documents
             .par_iter()
-            .filter_map(|(_, document)| {
+            .filter(|document| self.is_visible(document))
+            .filter_map(|document| {
                 if document.has_link_to(path) {
                     return Some(document.path());
                 }
@@ -112,10 +370,1045 @@ impl Vault {
     }
 
     pub fn query(&self, query: Query) -> Vec<&Document> {
+        let hierarchy = Hierarchy::new(self);
         self.documents()
             .par_iter()
-            .filter(|doc| query.matches(doc))
+            .filter(|doc| query.matches(self, &hierarchy, doc))
             .map(|doc| doc.to_owned())
             .collect()
     }
+
+    /// Evaluate several queries in a single pass over the documents, giving the matching
+    /// documents of each query in the same order as the queries
+    pub fn query_many(&self, queries: &[Query]) -> Vec<Vec<&Document>> {
+        let documents = self.documents();
+        let hierarchy = Hierarchy::new(self);
+        let matches: Vec<Vec<bool>> = documents
+            .par_iter()
+            .map(|doc| {
+                queries
+                    .iter()
+                    .map(|query| query.matches(self, &hierarchy, doc))
+                    .collect()
+            })
+            .collect();
+        (0..queries.len())
+            .map(|i| {
+                documents
+                    .iter()
+                    .zip(&matches)
+                    .filter(|(_, matches)| matches[i])
+                    .map(|(doc, _)| *doc)
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Plan the edits needed to rename the heading `old` of the document at `path` to `new`, and to
+    /// point every link to its anchor, across the whole vault, to the new one.
+    pub fn rename_heading(
+        &self,
+        path: &MarkdownPath,
+        old: &str,
+        new: &str,
+    ) -> Result<Vec<FileEdit>, EditError> {
+        let document = self
+            .get_document(path)
+            .ok_or_else(|| EditError::UnknownDocument { path: path.path() })?;
+        let unknown_heading = || EditError::UnknownHeading {
+            path: path.path(),
+            heading: old.to_string(),
+        };
+        let (old_slug, new_slug) = (slugify(old), slugify(new));
+
+        let heading = document
+            .headings()
+            .into_iter()
+            .find(|heading| heading.text == old || heading.slug() == old_slug)
+            .ok_or_else(unknown_heading)?;
+        let mut edits = vec![FileEdit {
+            path: path.path(),
+            edits: vec![TextEdit {
+                range: heading.content.clone(),
+                replacement: new.to_string(),
+            }],
+            digest: document.digest(),
+            rename: None,
+        }];
+
+        // Drafts are edited as well, so that their links don't break once they're published
+        for doc in self.documents.iter() {
+            let links: Vec<_> = doc
+                .links()
+                .into_iter()
+                .filter(|link| link.anchor() == Some(old_slug.as_str()))
+                // Links to an anchor in the same document have no target
+                .filter(|link| {
+                    (link.target().is_empty() && &doc.path() == path)
+                        || link.points_to(doc.path().dir(), path)
+                })
+                .collect();
+            if links.is_empty() {
+                continue;
+            }
+
+            let contents = doc.contents()?;
+            let link_edits = links.into_iter().filter_map(|link| {
+                let anchor = format!("#{old_slug}");
+                let offset = contents[link.range.clone()].rfind(&anchor)? + 1;
+                let start = link.range.start + offset;
+                Some(TextEdit {
+                    range: start..start + old_slug.len(),
+                    replacement: new_slug.clone(),
+                })
+            });
+
+            match edits.iter_mut().find(|edit| edit.path == doc.path().path()) {
+                Some(edit) => edit.edits.extend(link_edits),
+                None => edits.push(FileEdit {
+                    path: doc.path().path(),
+                    edits: link_edits.collect(),
+                    digest: doc.digest(),
+                    rename: None,
+                }),
+            }
+        }
+
+        Ok(edits)
+    }
+
+    /// Plan the edit appending a `## Backlinks` section to the document, listing the visible notes
+    /// linking to it, or refreshing the section if it's already there. Links inside of the
+    /// backlinks sections of other notes don't count, so that refreshing every note is stable.
+    pub fn backlinks_section(&self, doc: &Document) -> Result<FileEdit, EditError> {
+        const HEADING: &str = "Backlinks";
+        let generated = |doc: &Document, contents: &str| {
+            Section::from_headings(&doc.headings(), contents.len())
+                .into_iter()
+                .find(|section| section.heading.level == 2 && section.heading.is(HEADING))
+                .map(|section| section.range)
+        };
+
+        let path = doc.path();
+        let mut backlinks = Vec::new();
+        for other in self.documents() {
+            if other.path() == path || !other.has_link_to(&path) {
+                continue;
+            }
+            let contents = other.contents()?;
+            let section = generated(other, &contents);
+            let links_here = other.links().into_iter().any(|link| {
+                link.points_to(other.path().dir(), &path)
+                    && !section
+                        .as_ref()
+                        .is_some_and(|range| range.contains(&link.range.start))
+            });
+            if links_here {
+                backlinks.push(format!(
+                    "- [{}]({})\n",
+                    other.title(),
+                    other.path().link_from(&path.dir())
+                ));
+            }
+        }
+
+        let contents = doc.contents()?;
+        let mut replacement = String::new();
+        if !backlinks.is_empty() {
+            replacement = format!("## {HEADING}\n\n{}", backlinks.concat());
+        }
+        let edit = match generated(doc, &contents) {
+            // The section goes away along with the last backlink
+            Some(range) if contents[range.clone()] != replacement => {
+                Some(TextEdit { range, replacement })
+            }
+            Some(_) => None,
+            None if backlinks.is_empty() => None,
+            None => {
+                let separator = match contents.ends_with("\n\n") || contents.is_empty() {
+                    true => "",
+                    false if contents.ends_with('\n') => "\n",
+                    false => "\n\n",
+                };
+                Some(TextEdit {
+                    range: contents.len()..contents.len(),
+                    replacement: format!("{separator}{replacement}"),
+                })
+            }
+        };
+
+        Ok(FileEdit {
+            path: path.path(),
+            edits: edit.into_iter().collect(),
+            digest: doc.digest(),
+            rename: None,
+        })
+    }
+
+    /// Plan writing the table of contents of a document under its `<!-- toc -->` line, up to the
+    /// `<!-- /toc -->` line closing it, which is added the first time
+    pub fn toc_section(&self, doc: &Document) -> Result<FileEdit, EditError> {
+        let contents = doc.contents()?;
+        let mut offset = 0;
+        let mut start = None;
+        let mut end = None;
+        for line in contents.split_inclusive('\n') {
+            match (line.trim(), start) {
+                (heading::TOC_START, None) => start = Some(offset + line.len()),
+                (heading::TOC_END, Some(_)) => {
+                    end = Some(offset);
+                    break;
+                }
+                _ => {}
+            }
+            offset += line.len();
+        }
+        let start = start.ok_or_else(|| EditError::NoTocMarker {
+            path: doc.path().path(),
+        })?;
+
+        let toc = heading::table_of_contents(&doc.headings());
+        // The marker may be the last line, without a line break
+        let separator = if contents[..start].ends_with('\n') {
+            ""
+        } else {
+            "\n"
+        };
+        let edit = match end {
+            Some(end) if contents[start..end] == toc => None,
+            Some(end) => Some(TextEdit {
+                range: start..end,
+                replacement: toc,
+            }),
+            None => Some(TextEdit {
+                range: start..start,
+                replacement: format!("{separator}{toc}{}\n", heading::TOC_END),
+            }),
+        };
+
+        Ok(FileEdit {
+            path: doc.path().path(),
+            edits: edit.into_iter().collect(),
+            digest: doc.digest(),
+            rename: None,
+        })
+    }
+
+    /// Plan moving the given document to the file `to`, rewriting both its own relative links and
+    /// the links of every other document to it, drafts included, so that they all still resolve.
+    pub fn move_document(
+        &self,
+        path: &MarkdownPath,
+        to: PathBuf,
+    ) -> Result<Vec<FileEdit>, EditError> {
+        let document = self
+            .get_document(path)
+            .ok_or_else(|| EditError::UnknownDocument { path: path.path() })?;
+        let to = path::normalise(&to);
+        if to.exists() {
+            return Err(EditError::AlreadyExists { path: to });
+        }
+        let (old_dir, new_dir) = (path.dir(), to.parent().unwrap_or(&to).to_path_buf());
+
+        let contents = document.contents()?;
+        let own_edits = document
+            .links()
+            .into_iter()
+            .filter(|link| !link.is_external())
+            .filter_map(|link| {
+                let range = link.target_range(&contents)?;
+                // Links to the document itself follow it
+                let target = if link.points_to(old_dir.clone(), path) {
+                    to.clone()
+                } else {
+                    let decoded = percent_decode_str(link.target()).decode_utf8_lossy();
+                    old_dir.join(decoded.as_ref())
+                };
+                Some(TextEdit {
+                    range,
+                    replacement: path::relative_link(&target, &new_dir),
+                })
+            })
+            .collect();
+        let mut edits = vec![FileEdit {
+            path: path.path(),
+            edits: own_edits,
+            digest: document.digest(),
+            rename: Some(to.clone()),
+        }];
+
+        for doc in self.documents.iter().filter(|doc| &doc.path() != path) {
+            let base_path = doc.path().dir();
+            let links: Vec<_> = doc
+                .links()
+                .into_iter()
+                .filter(|link| link.points_to(base_path.clone(), path))
+                .collect();
+            if links.is_empty() {
+                continue;
+            }
+            let contents = doc.contents()?;
+            let replacement = path::relative_link(&to, &base_path);
+            edits.push(FileEdit {
+                path: doc.path().path(),
+                edits: links
+                    .iter()
+                    .filter_map(|link| link.target_range(&contents))
+                    .map(|range| TextEdit {
+                        range,
+                        replacement: replacement.clone(),
+                    })
+                    .collect(),
+                digest: doc.digest(),
+                rename: None,
+            });
+        }
+
+        Ok(edits)
+    }
+
+    /// Plan renaming the given document to `title`. Its file is renamed after the title, in the same
+    /// folder, and its `title` attribute is updated if it has one. The links of other documents
+    /// to it are rewritten, along with their text if it was the old title, and so are the `parent`
+    /// attributes of the notes nested under it.
+    pub fn rename_document(
+        &self,
+        path: &MarkdownPath,
+        title: &str,
+    ) -> Result<Vec<FileEdit>, EditError> {
+        let document = self
+            .get_document(path)
+            .ok_or_else(|| EditError::UnknownDocument { path: path.path() })?;
+        // Characters which aren't allowed in file names on some systems
+        let stem: String = title
+            .chars()
+            .map(|c| match c {
+                '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '-',
+                c => c,
+            })
+            .collect();
+        let stem = stem.trim();
+        if stem.is_empty() || stem.starts_with('.') {
+            return Err(EditError::InvalidName {
+                name: title.to_string(),
+            });
+        }
+
+        let to = path.dir().join(format!("{stem}.md"));
+        let mut edits = if to == path.path() {
+            vec![FileEdit {
+                path: path.path(),
+                edits: Vec::new(),
+                digest: document.digest(),
+                rename: None,
+            }]
+        } else {
+            self.move_document(path, to.clone())?
+        };
+
+        if let Some(range) = frontmatter_value(document, "title")? {
+            edits[0].edits.push(TextEdit {
+                range,
+                replacement: format!(" {}", yaml_scalar(title)),
+            });
+        }
+
+        // Links showing the old title now show the new one
+        let old_title = document.title();
+        for doc in &self.documents {
+            let base_path = doc.path().dir();
+            let links: Vec<_> = doc
+                .links()
+                .into_iter()
+                .filter(|link| link.text == old_title && link.points_to(base_path.clone(), path))
+                .collect();
+            if links.is_empty() {
+                continue;
+            }
+            let contents = doc.contents()?;
+            let text_edits = links.iter().filter_map(|link| {
+                let start = link.range.start + 1;
+                let end = start + link.text.len();
+                (contents.get(link.range.start..end + 1)? == format!("[{old_title}]")).then(|| {
+                    TextEdit {
+                        range: start..end,
+                        replacement: title.to_string(),
+                    }
+                })
+            });
+            match edits.iter_mut().find(|edit| edit.path == doc.path().path()) {
+                Some(edit) => edit.edits.extend(text_edits),
+                None => edits.push(FileEdit {
+                    path: doc.path().path(),
+                    edits: text_edits.collect(),
+                    digest: doc.digest(),
+                    rename: None,
+                }),
+            }
+        }
+
+        // Parents are given by title or by path, like in the hierarchy, and keep their form
+        for doc in &self.documents {
+            let Some(Value::String(parent)) = doc.get_metadata(&"parent".to_string()) else {
+                continue;
+            };
+            let wiki = parent.starts_with("[[") && parent.ends_with("]]");
+            let name = parent.trim_start_matches("[[").trim_end_matches("]]");
+            let by_path = MarkdownPath::new(doc.path().dir(), PathBuf::from(name))
+                .is_ok_and(|parent| &parent == path);
+            let new_name = match by_path {
+                true => percent_decode_str(&path::relative_link(&to, &doc.path().dir()))
+                    .decode_utf8_lossy()
+                    .to_string(),
+                false if name == old_title => title.to_string(),
+                false => continue,
+            };
+            let new_name = match wiki {
+                true => format!("[[{new_name}]]"),
+                false => new_name,
+            };
+            let Some(range) = frontmatter_value(doc, "parent")? else {
+                continue;
+            };
+            let edit = TextEdit {
+                range,
+                replacement: format!(" {}", yaml_scalar(&new_name)),
+            };
+            match edits.iter_mut().find(|edit| edit.path == doc.path().path()) {
+                Some(file_edit) => file_edit.edits.push(edit),
+                None => edits.push(FileEdit {
+                    path: doc.path().path(),
+                    edits: vec![edit],
+                    digest: doc.digest(),
+                    rename: None,
+                }),
+            }
+        }
+
+        edits.retain(|edit| !edit.edits.is_empty() || edit.rename.is_some());
+        Ok(edits)
+    }
+
+    /// Plan the edits renaming the tag `old`, along with its nested tags, to `new` in every note,
+    /// both in their `tags` attribute and in their body. Unless `merge` is set, `new` must not be
+    /// in use yet.
+    pub fn rename_tag(
+        &self,
+        old: &str,
+        new: &str,
+        merge: bool,
+    ) -> Result<Vec<FileEdit>, EditError> {
+        let rules = &self.config.tags;
+        let (old, new) = (
+            &rules.normalise(old.trim_start_matches('#')),
+            &rules.normalise(new.trim_start_matches('#')),
+        );
+        let tagged =
+            |doc: &&Document, name: &str| doc.tags().iter().any(|t| tag::is_within(t, name));
+
+        if !merge && self.documents.iter().any(|doc| tagged(&doc, new)) {
+            return Err(EditError::TagExists {
+                tag: new.to_string(),
+            });
+        }
+        let documents: Vec<&Document> = self
+            .documents
+            .iter()
+            .filter(|doc| tagged(doc, old))
+            .collect();
+        if documents.is_empty() {
+            return Err(EditError::UnknownTag {
+                tag: old.to_string(),
+            });
+        }
+
+        documents
+            .into_iter()
+            .map(|doc| self.retag(doc, old, new, merge))
+            .filter(|edit| !matches!(edit, Ok(edit) if edit.edits.is_empty()))
+            .collect()
+    }
+
+    /// Plan the edits renaming the tag `old`, along with its nested tags, to `new` in a single
+    /// document. If `merge` is set, `old` is dropped from its `tags` attribute when `new` is
+    /// already in it. Both tags must be canonical.
+    pub fn retag(
+        &self,
+        doc: &Document,
+        old: &str,
+        new: &str,
+        merge: bool,
+    ) -> Result<FileEdit, EditError> {
+        let rules = &self.config.tags;
+        let contents = doc.contents()?;
+        let mut edits = Vec::new();
+
+        if let Some(range) = doc.frontmatter() {
+            let has_new = doc
+                .get_metadata(&"tags".to_string())
+                .is_some_and(|tags| tag::from_metadata(tags).contains(new));
+            edits.extend(tag::frontmatter_edits(
+                &contents[range.clone()],
+                range.start,
+                old,
+                new,
+                merge && has_new,
+                rules,
+            ));
+        }
+
+        // Keep the `#`, and any nested part after the renamed prefix
+        edits.extend(
+            doc.inline_tags()
+                .into_iter()
+                .filter(|inline| tag::is_within(&inline.name, old))
+                .map(|inline| {
+                    let start = inline.range.start + 1;
+                    let raw = &contents[start..inline.range.end];
+                    TextEdit {
+                        range: start..start + rules.source_len(raw, old.len()),
+                        replacement: new.to_string(),
+                    }
+                }),
+        );
+
+        Ok(FileEdit {
+            path: doc.path().path(),
+            edits,
+            digest: doc.digest(),
+            rename: None,
+        })
+    }
+
+    /// Plan the edits removing the canonical tag `name` from a single document, leaving its nested
+    /// tags
+    pub fn untag(&self, doc: &Document, name: &str) -> Result<FileEdit, EditError> {
+        let contents = doc.contents()?;
+        let mut edits = Vec::new();
+
+        if let Some(range) = doc.frontmatter() {
+            // Nested tags are "renamed" to themselves, which is left out
+            edits.extend(
+                tag::frontmatter_edits(
+                    &contents[range.clone()],
+                    range.start,
+                    name,
+                    name,
+                    true,
+                    &self.config.tags,
+                )
+                .into_iter()
+                .filter(|edit| edit.replacement.is_empty()),
+            );
+        }
+        edits.extend(
+            doc.inline_tags()
+                .into_iter()
+                .filter(|inline| inline.name == name)
+                .map(|inline| TextEdit {
+                    range: inline.range,
+                    replacement: String::new(),
+                }),
+        );
+
+        Ok(FileEdit {
+            path: doc.path().path(),
+            edits,
+            digest: doc.digest(),
+            rename: None,
+        })
+    }
+
+    /// Plan the edits rewriting the tags of a single document, in its `tags` attribute and in its
+    /// body, into their canonical form
+    pub fn canonicalise_tags(&self, doc: &Document) -> Result<FileEdit, EditError> {
+        let contents = doc.contents()?;
+        let mut edits = Vec::new();
+
+        if let Some(range) = doc.frontmatter() {
+            edits.extend(tag::canonical_edits(
+                &contents[range.clone()],
+                range.start,
+                &self.config.tags,
+            ));
+        }
+        // The names of inline tags are canonical already, unlike their source
+        edits.extend(doc.inline_tags().into_iter().filter_map(|inline| {
+            let source = inline.range.start + 1..inline.range.end;
+            (contents[source.clone()] != inline.name).then_some(TextEdit {
+                range: source,
+                replacement: inline.name,
+            })
+        }));
+
+        Ok(FileEdit {
+            path: doc.path().path(),
+            edits,
+            digest: doc.digest(),
+            rename: None,
+        })
+    }
+
+    /// The daily note of the given date, if the vault has one, either where `n journal` puts it or
+    /// named after the date in a folder of daily notes
+    pub fn daily_note(&self, date: Date) -> Option<&Document> {
+        let root = self.path.canonicalize().unwrap_or_else(|_| self.path());
+        let journal = PathBuf::from(date.format(&self.config.journal.path));
+        let folders = self.config.kinds.get(&Kind::Daily).into_iter().flatten();
+        std::iter::once(MarkdownPath::new(root.clone(), journal).ok())
+            .chain(folders.map(|folder| {
+                MarkdownPath::new(root.join(folder), PathBuf::from(format!("{date}.md"))).ok()
+            }))
+            .flatten()
+            .find_map(|path| self.get_document(&path))
+    }
+
+    /// Plan the edits turning the dates mentioned in the given document, or in every visible one,
+    /// into links to their daily note, for the dates which have one.
+    pub fn link_dates(&self, path: Option<&MarkdownPath>) -> Result<Vec<FileEdit>, EditError> {
+        let documents = match path {
+            Some(path) => vec![
+                self.get_document(path)
+                    .ok_or_else(|| EditError::UnknownDocument { path: path.path() })?,
+            ],
+            None => self.documents(),
+        };
+
+        documents
+            .into_par_iter()
+            .map(|doc| {
+                let edits = doc
+                    .date_mentions(&self.config.dates)?
+                    .into_iter()
+                    .filter_map(|mention| {
+                        let daily = self.daily_note(mention.date)?;
+                        // A daily note doesn't need to link to itself
+                        (daily.path() != doc.path()).then(|| TextEdit {
+                            replacement: format!(
+                                "[{}]({})",
+                                mention.text,
+                                daily.path().link_from(&doc.path().dir())
+                            ),
+                            range: mention.range,
+                        })
+                    })
+                    .collect();
+                Ok(FileEdit {
+                    path: doc.path().path(),
+                    edits,
+                    digest: doc.digest(),
+                    rename: None,
+                })
+            })
+            .filter(|edit| !matches!(edit, Ok(edit) if edit.edits.is_empty()))
+            .collect()
+    }
+}
+
+/// Write a string as a YAML scalar, quoting it unless it's plain text which YAML would read as is
+/// The byte range of the value of the top-level `key` in the frontmatter of the document, from
+/// right after its colon to the end of its line
+fn frontmatter_value(document: &Document, key: &str) -> Result<Option<Range<usize>>, ParseError> {
+    let Some(range) = document.frontmatter() else {
+        return Ok(None);
+    };
+    let contents = document.contents()?;
+    let mut offset = range.start;
+    for line in contents[range].split_inclusive('\n') {
+        let value = line
+            .strip_prefix(key)
+            .and_then(|rest| rest.trim_start().strip_prefix(':'));
+        if let Some(value) = value {
+            let start = offset + line.len() - value.len();
+            return Ok(Some(start..offset + line.trim_end().len()));
+        }
+        offset += line.len();
+    }
+    Ok(None)
+}
+
+fn yaml_scalar(value: &str) -> String {
+    let plain = value.starts_with(|c: char| c.is_alphabetic())
+        && value
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, ' ' | '-' | '_' | '.' | ',' | '(' | ')'))
+        && !value.ends_with(' ')
+        && !matches!(
+            value.to_ascii_lowercase().as_str(),
+            "true" | "false" | "yes" | "no" | "on" | "off" | "null"
+        );
+    match plain {
+        true => value.to_string(),
+        // JSON strings are valid double-quoted YAML scalars
+        false => serde_json::to_string(value).unwrap(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{edit, testing::TempDir};
+
+    fn path(vault: &Vault, file: &str) -> MarkdownPath {
+        MarkdownPath::new(vault.path(), PathBuf::from(file)).unwrap()
+    }
+
+    /// Write the edits planned on the vault
+    fn commit(vault: &Vault, edits: Vec<FileEdit>) {
+        edit::commit(&edits, &vault.trash()).unwrap();
+    }
+
+    #[test]
+    fn yaml_scalar_quoting() {
+        assert_eq!(yaml_scalar("Plain title, v1.0"), "Plain title, v1.0");
+        assert_eq!(yaml_scalar("Rust: the book"), r#""Rust: the book""#);
+        assert_eq!(yaml_scalar("#hashtag"), r##""#hashtag""##);
+        assert_eq!(yaml_scalar("[[Beta]]"), r#""[[Beta]]""#);
+        assert_eq!(yaml_scalar("Yes"), r#""Yes""#);
+        assert_eq!(yaml_scalar("2024"), r#""2024""#);
+        assert_eq!(yaml_scalar(r#"Say "hi""#), r#""Say \"hi\"""#);
+    }
+
+    #[test]
+    /// Renaming a note renames its file and title, and rewrites the links to it from every folder
+    fn rename_document_links() {
+        let dir = TempDir::new(&[
+            ("a.md", "[Beta](b.md) and [the other one](b.md#intro)\n"),
+            ("b.md", "---\ntitle: Beta\n---\n\n# Intro\n"),
+            ("sub/c.md", "[Beta](../b.md)\n"),
+        ]);
+        let vault = dir.vault();
+
+        commit(
+            &vault,
+            vault
+                .rename_document(&path(&vault, "b.md"), "Bravo Two")
+                .unwrap(),
+        );
+        assert!(!dir.join("b.md").exists());
+        assert_eq!(
+            dir.read("Bravo Two.md"),
+            "---\ntitle: Bravo Two\n---\n\n# Intro\n"
+        );
+        assert_eq!(
+            dir.read("a.md"),
+            "[Bravo Two](Bravo%20Two.md) and [the other one](Bravo%20Two.md#intro)\n"
+        );
+        assert_eq!(dir.read("sub/c.md"), "[Bravo Two](../Bravo%20Two.md)\n");
+    }
+
+    #[test]
+    /// Titles which aren't plain YAML scalars are quoted, so that they read back as they were
+    fn rename_document_quotes_title() {
+        let dir = TempDir::new(&[("b.md", "---\ntitle: Beta\ntags: [x]\n---\n")]);
+        let vault = dir.vault();
+
+        commit(
+            &vault,
+            vault
+                .rename_document(&path(&vault, "b.md"), "Rust: yes")
+                .unwrap(),
+        );
+        assert_eq!(
+            dir.read("Rust- yes.md"),
+            "---\ntitle: \"Rust: yes\"\ntags: [x]\n---\n"
+        );
+        let vault = dir.vault();
+        assert_eq!(
+            vault
+                .get_document(&path(&vault, "Rust- yes.md"))
+                .unwrap()
+                .title(),
+            "Rust: yes"
+        );
+    }
+
+    #[test]
+    /// A moved note keeps its links working, to other notes, to itself and to attachments
+    fn move_document_own_links() {
+        let dir = TempDir::new(&[
+            (
+                "notes/a.md",
+                "[B](b.md), [C](../c.md), [self](a.md#top), [img](img/x.png), [web](https://x.org)\n",
+            ),
+            ("notes/b.md", "# B\n"),
+            ("c.md", "# C\n"),
+        ]);
+        let vault = dir.vault();
+
+        let to = dir.join("archive/2024/a.md");
+        commit(
+            &vault,
+            vault
+                .move_document(&path(&vault, "notes/a.md"), to)
+                .unwrap(),
+        );
+        assert!(!dir.join("notes/a.md").exists());
+        assert_eq!(
+            dir.read("archive/2024/a.md"),
+            "[B](../../notes/b.md), [C](../../c.md), [self](a.md#top), \
+             [img](../../notes/img/x.png), [web](https://x.org)\n"
+        );
+    }
+
+    #[test]
+    /// The links to a moved note are rewritten relative to the folder of each linking note
+    fn move_document_inbound_links() {
+        let dir = TempDir::new(&[
+            ("a.md", "[B](notes/b.md#intro)\n"),
+            ("notes/c.md", "[B](b.md) and [B again](./b.md)\n"),
+            ("other/d.md", "[B](../notes/b.md)\n"),
+            ("notes/b.md", "# Intro\n"),
+        ]);
+        let vault = dir.vault();
+
+        let to = dir.join("other/b.md");
+        commit(
+            &vault,
+            vault
+                .move_document(&path(&vault, "notes/b.md"), to)
+                .unwrap(),
+        );
+        assert_eq!(dir.read("a.md"), "[B](other/b.md#intro)\n");
+        assert_eq!(
+            dir.read("notes/c.md"),
+            "[B](../other/b.md) and [B again](../other/b.md)\n"
+        );
+        assert_eq!(dir.read("other/d.md"), "[B](b.md)\n");
+        assert_eq!(dir.read("other/b.md"), "# Intro\n");
+    }
+
+    #[test]
+    fn move_document_over_existing() {
+        let dir = TempDir::new(&[("a.md", "A\n"), ("b.md", "B\n")]);
+        let vault = dir.vault();
+
+        let error = vault.move_document(&path(&vault, "a.md"), dir.join("b.md"));
+        assert!(matches!(error, Err(EditError::AlreadyExists { .. })));
+    }
+
+    #[test]
+    /// Renaming a heading rewrites the links to its anchor, from the note itself and from others
+    fn rename_heading() {
+        let dir = TempDir::new(&[
+            (
+                "a.md",
+                "# Plan\n\n## Details\n\nSee [below](#details) and [b](b.md#details).\n",
+            ),
+            ("b.md", "## Details\n"),
+            (
+                "sub/c.md",
+                "[a](../a.md#details), [b](../b.md#details), [a](../a.md#plan)\n",
+            ),
+        ]);
+        let vault = dir.vault();
+
+        let edits = vault
+            .rename_heading(&path(&vault, "a.md"), "details", "More details")
+            .unwrap();
+        commit(&vault, edits);
+        assert_eq!(
+            dir.read("a.md"),
+            "# Plan\n\n## More details\n\nSee [below](#more-details) and [b](b.md#details).\n"
+        );
+        assert_eq!(dir.read("b.md"), "## Details\n");
+        assert_eq!(
+            dir.read("sub/c.md"),
+            "[a](../a.md#more-details), [b](../b.md#details), [a](../a.md#plan)\n"
+        );
+    }
+
+    #[test]
+    /// Headings with inline markup are found by their text, and replaced along with the markup
+    fn rename_heading_markup() {
+        let dir = TempDir::new(&[
+            ("a.md", "## The *real* `plan` \n\n[here](#the-real-plan)\n"),
+            ("b.md", "[there](a.md#the-real-plan)\n"),
+        ]);
+        let vault = dir.vault();
+
+        let edits = vault
+            .rename_heading(&path(&vault, "a.md"), "The real plan", "New plan")
+            .unwrap();
+        commit(&vault, edits);
+        assert_eq!(dir.read("a.md"), "## New plan \n\n[here](#new-plan)\n");
+        assert_eq!(dir.read("b.md"), "[there](a.md#new-plan)\n");
+        assert!(matches!(
+            vault.rename_heading(&path(&vault, "a.md"), "Nowhere", "x"),
+            Err(EditError::UnknownHeading { .. })
+        ));
+    }
+
+    /// Commit the planned tag edits, and read the tags of every note back
+    fn commit_tags(dir: &TempDir, vault: &Vault, edits: Vec<FileEdit>) -> Vec<Vec<String>> {
+        commit(vault, edits);
+        let vault = dir.vault();
+        vault
+            .documents()
+            .iter()
+            .map(|doc| doc.tags().into_iter().collect())
+            .collect()
+    }
+
+    #[test]
+    /// Renaming a tag renames it, and its nested tags, in frontmatter lists and in the body
+    fn rename_tag() {
+        let dir = TempDir::new(&[
+            (
+                "a.md",
+                "---\ntags: [rust, rust/async, notes]\n---\n\nOn #rust and #rust/async.\n",
+            ),
+            ("b.md", "---\ntags:\n  - notes\n  - rust\n---\n\n#notes\n"),
+        ]);
+        let vault = dir.vault();
+
+        let tags = commit_tags(
+            &dir,
+            &vault,
+            vault.rename_tag("rust", "lang/rust", false).unwrap(),
+        );
+        assert_eq!(
+            dir.read("a.md"),
+            "---\ntags: [lang/rust, lang/rust/async, notes]\n---\n\n\
+             On #lang/rust and #lang/rust/async.\n"
+        );
+        assert_eq!(
+            dir.read("b.md"),
+            "---\ntags:\n  - notes\n  - lang/rust\n---\n\n#notes\n"
+        );
+        assert_eq!(
+            tags,
+            [
+                vec!["lang/rust", "lang/rust/async", "notes"],
+                vec!["lang/rust", "notes"]
+            ]
+        );
+        assert!(matches!(
+            vault.rename_tag("missing", "other", false),
+            Err(EditError::UnknownTag { .. })
+        ));
+    }
+
+    #[test]
+    /// Merging a tag into one a note already carries leaves a single entry in its frontmatter
+    fn merge_tag() {
+        let dir = TempDir::new(&[
+            ("a.md", "---\ntags: [rust, programming]\n---\n\n#rust\n"),
+            ("b.md", "---\ntags:\n  - programming\n  - rust\n---\n"),
+            ("c.md", "---\ntags: rust\n---\n"),
+        ]);
+        let vault = dir.vault();
+
+        assert!(matches!(
+            vault.rename_tag("rust", "programming", false),
+            Err(EditError::TagExists { .. })
+        ));
+        let edits = vault.rename_tag("#rust", "#programming", true).unwrap();
+        let tags = commit_tags(&dir, &vault, edits);
+        assert_eq!(
+            dir.read("a.md"),
+            "---\ntags: [programming]\n---\n\n#programming\n"
+        );
+        assert_eq!(dir.read("b.md"), "---\ntags:\n  - programming\n---\n");
+        assert_eq!(dir.read("c.md"), "---\ntags: programming\n---\n");
+        assert!(tags.iter().all(|tags| tags == &["programming"]));
+    }
+
+    #[test]
+    /// A tag which is a prefix of another one, without being its parent, is renamed on its own
+    fn rename_tag_prefix() {
+        let dir = TempDir::new(&[(
+            "a.md",
+            "---\ntags: [foo, foobar, foo/x]\n---\n\n#foo #foobar #foo/x #foo-bar\n",
+        )]);
+        let vault = dir.vault();
+
+        commit(&vault, vault.rename_tag("foo", "baz", false).unwrap());
+        assert_eq!(
+            dir.read("a.md"),
+            "---\ntags: [baz, foobar, baz/x]\n---\n\n#baz #foobar #baz/x #foo-bar\n"
+        );
+    }
+
+    #[test]
+    /// Removing a tag leaves its nested tags
+    fn untag() {
+        let dir = TempDir::new(&[(
+            "a.md",
+            "---\ntags: [inbox, inbox/later, x]\n---\n\nTo do #inbox #inbox/later\n",
+        )]);
+        let vault = dir.vault();
+
+        let doc = vault.get_document(&path(&vault, "a.md")).unwrap();
+        commit(&vault, vec![vault.untag(doc, "inbox").unwrap()]);
+        assert_eq!(
+            dir.read("a.md"),
+            "---\ntags: [inbox/later, x]\n---\n\nTo do  #inbox/later\n"
+        );
+    }
+
+    #[test]
+    /// Tags are rewritten into the canonical form given by the configuration of the vault
+    fn canonicalise_tags() {
+        let dir = TempDir::new(&[
+            (
+                ".n/config.yaml",
+                "tags:\n  case-fold: true\n  word-separator: \"-\"\n  hierarchy-separators: [\".\"]\n",
+            ),
+            (
+                "a.md",
+                "---\ntags: [Machine_Learning, lang.Rust]\n---\n\n#Machine_Learning #ok\n",
+            ),
+        ]);
+        let vault = dir.vault();
+
+        let doc = vault.get_document(&path(&vault, "a.md")).unwrap();
+        commit(&vault, vec![vault.canonicalise_tags(doc).unwrap()]);
+        assert_eq!(
+            dir.read("a.md"),
+            "---\ntags: [machine-learning, lang/rust]\n---\n\n#machine-learning #ok\n"
+        );
+    }
+
+    #[test]
+    /// Notes nested under a renamed note, by its title or by its path, stay nested under it
+    fn rename_document_parents() {
+        let dir = TempDir::new(&[
+            ("b.md", "---\ntitle: Beta\n---\n"),
+            ("c.md", "---\ntitle: Gamma\nparent: Beta\n---\n"),
+            (
+                "sub/d.md",
+                "---\ntitle: Delta\nparent: \"[[../b.md]]\"\n---\n",
+            ),
+            ("e.md", "---\ntitle: Epsilon\nparent: Gamma\n---\n"),
+        ]);
+        let vault = dir.vault();
+
+        commit(
+            &vault,
+            vault
+                .rename_document(&path(&vault, "b.md"), "Bravo")
+                .unwrap(),
+        );
+        assert_eq!(dir.read("c.md"), "---\ntitle: Gamma\nparent: Bravo\n---\n");
+        assert_eq!(
+            dir.read("sub/d.md"),
+            "---\ntitle: Delta\nparent: \"[[../Bravo.md]]\"\n---\n"
+        );
+        assert_eq!(
+            dir.read("e.md"),
+            "---\ntitle: Epsilon\nparent: Gamma\n---\n"
+        );
+
+        let vault = dir.vault();
+        let hierarchy = Hierarchy::new(&vault);
+        let bravo = path(&vault, "Bravo.md");
+        for child in ["c.md", "sub/d.md"] {
+            let child = vault.get_document(&path(&vault, child)).unwrap();
+            assert_eq!(
+                hierarchy.parent(child).map(Document::path),
+                Some(bravo.clone())
+            );
+        }
+    }
 }
@@ -0,0 +1,145 @@
+use std::{
+    collections::BTreeSet,
+    io,
+    path::{Path, PathBuf},
+    process::Command,
+    thread,
+    time::Duration,
+};
+
+use owo_colors::OwoColorize;
+use thiserror::Error;
+
+use crate::{
+    index::{IndexError, TitleIndex},
+    kind::Kind,
+    query::Query,
+    vault::{Vault, VaultInitialisationError},
+};
+
+#[derive(Debug, Error)]
+pub enum WatchError {
+    #[error(transparent)]
+    Index(#[from] IndexError),
+    #[error(transparent)]
+    Vault(#[from] VaultInitialisationError),
+}
+
+/// The notes followed by `n watch --query`, which the query is evaluated over again whenever
+/// notes change
+pub struct Followed<'a> {
+    pub query: &'a Query,
+    /// The kinds of notes the results are restricted to
+    pub kinds: &'a [Kind],
+    pub include_drafts: bool,
+}
+
+impl Followed<'_> {
+    /// The paths of the notes of the vault at `base_path` currently matching the query
+    fn matching(&self, base_path: &Path) -> Result<BTreeSet<PathBuf>, WatchError> {
+        let mut vault = Vault::new(base_path.to_path_buf())?;
+        vault.set_include_drafts(self.include_drafts);
+        Ok(vault
+            .query_many(std::slice::from_ref(self.query))
+            .into_iter()
+            .flatten()
+            .filter(|doc| self.kinds.contains(&doc.kind()))
+            .map(|doc| canonical(&doc.path().path()))
+            .collect())
+    }
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Watch the notes of the vault at `base_path`, checking every `interval` for notes which were
+/// created or modified. The title index is brought up to date first, and saved if `persist` is
+/// set, so only the notes which changed are parsed again. Then `exec` is run for each changed
+/// note, or its path is printed if there's no command.
+///
+/// If a query is `followed`, only the changed notes matching it count. Without a command, the
+/// whole list of matching notes is printed instead, at first and again whenever notes change,
+/// separated by a blank line. This only returns if the vault can't be read anymore.
+pub fn watch(
+    base_path: &Path,
+    persist: bool,
+    interval: Duration,
+    exec: Option<&str>,
+    followed: Option<&Followed>,
+) -> Result<(), WatchError> {
+    let mut index = TitleIndex::load(base_path, persist)?;
+    if let (Some(followed), None) = (followed, exec) {
+        print_paths(&followed.matching(base_path)?);
+    }
+    loop {
+        thread::sleep(interval);
+        let current = TitleIndex::load(base_path, persist)?;
+        let mut changed: Vec<&PathBuf> = current
+            .entries()
+            .iter()
+            .filter(|(path, entry)| {
+                index
+                    .entries()
+                    .get(*path)
+                    .is_none_or(|old| old.modified() != entry.modified())
+            })
+            .map(|(path, _)| path)
+            .collect();
+        // A removed note can only change the results of the query
+        let removed = index
+            .entries()
+            .keys()
+            .any(|path| !current.entries().contains_key(path));
+
+        if let Some(followed) = followed
+            && (!changed.is_empty() || removed)
+        {
+            let matching = followed.matching(base_path)?;
+            if exec.is_none() {
+                println!();
+                print_paths(&matching);
+                changed.clear();
+            }
+            changed.retain(|path| matching.contains(&canonical(path)));
+        }
+
+        for path in changed {
+            match exec {
+                // A failing command shouldn't stop the watch
+                Some(command) => match run(command, path) {
+                    Ok(true) => {}
+                    Ok(false) => eprintln!(
+                        "{}",
+                        format!("`{command}` failed for {}", path.to_string_lossy()).bright_red()
+                    ),
+                    Err(e) => eprintln!(
+                        "{}",
+                        format!("failed to run `{command}` because {e}").bright_red()
+                    ),
+                },
+                None => println!("{}", path.to_string_lossy()),
+            }
+        }
+        index = current;
+    }
+}
+
+fn print_paths(paths: &BTreeSet<PathBuf>) {
+    paths
+        .iter()
+        .for_each(|path| println!("{}", path.to_string_lossy()));
+}
+
+/// Run the command through the shell, with every `{}` replaced by the quoted path of the note. If
+/// it has no `{}`, the path is passed as its last argument instead. Returns whether it succeeded.
+fn run(command: &str, path: &Path) -> io::Result<bool> {
+    let quoted = format!("'{}'", path.to_string_lossy().replace('\'', r"'\''"));
+    let command = if command.contains("{}") {
+        command.replace("{}", &quoted)
+    } else {
+        format!("{command} {quoted}")
+    };
+    let status = Command::new("sh").arg("-c").arg(command).status()?;
+    Ok(status.success())
+}
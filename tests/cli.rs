@@ -0,0 +1,124 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// A vault in a temporary directory, removed once the value is dropped
+struct TempVault {
+    path: PathBuf,
+}
+
+impl TempVault {
+    fn new(files: &[(&str, &str)]) -> Self {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "n-cli-{}-{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        for (file, contents) in files {
+            let file = path.join(file);
+            fs::create_dir_all(file.parent().unwrap()).unwrap();
+            fs::write(file, contents).unwrap();
+        }
+        Self { path }
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Run `n` on the vault with the given arguments
+    fn run(&self, args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_n"))
+            .arg("--vault-dir")
+            .arg(self.path())
+            .args(args)
+            .env("NO_COLOR", "1")
+            .output()
+            .unwrap()
+    }
+}
+
+impl Drop for TempVault {
+    fn drop(&mut self) {
+        _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+const NOTE: &str = "---\ntitle: Alpha\n---\n\n# Alpha\n\n## Details\n\nSome details.\n";
+
+#[test]
+/// Asking for a section of a note which it doesn't have is an error, not a crash
+fn missing_section() {
+    let vault = TempVault::new(&[("a.md", NOTE)]);
+
+    let output = vault.run(&["inspect", "a.md", "--section", "Details"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Some details."));
+
+    let output = vault.run(&["inspect", "a.md", "--section", "Nowhere"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("no section `Nowhere`"));
+    assert!(!stderr(&output).contains("panicked"));
+}
+
+#[test]
+/// Malformed queries are reported, along with where they went wrong, by every subcommand taking one
+fn invalid_query() {
+    let vault = TempVault::new(&[
+        ("a.md", NOTE),
+        ("queries.txt", "(contains title Alpha)\n(bogus x)\n"),
+    ]);
+    let queries = vault.path().join("queries.txt");
+
+    let output = vault.run(&["query", "(contains title Alpha)"]);
+    assert!(output.status.success());
+
+    for args in [
+        &["query", "(contains title Alpha) extra"][..],
+        &["query", "--queries-file", queries.to_str().unwrap()],
+        &["tasks", "--query", "(contains title"],
+        &["random", "--query", "(not)"],
+        &["publish-check", "(and (contains title Alpha))"],
+        &["watch", "--exec", "true", "--query", "(orphan"],
+    ] {
+        let output = vault.run(args);
+        assert_eq!(output.status.code(), Some(1), "{args:?}");
+        assert!(stderr(&output).contains("error: invalid query"), "{args:?}");
+        assert!(!stderr(&output).contains("panicked"), "{args:?}");
+    }
+
+    let output = vault.run(&["query", "(contains title Alpha) extra"]);
+    assert!(stderr(&output).contains("near `extra`"));
+}
+
+#[test]
+/// With `--dry-run`, the edits renaming a heading are only listed
+fn rename_heading_dry_run() {
+    let vault = TempVault::new(&[("a.md", NOTE), ("b.md", "[details](a.md#details)\n")]);
+
+    let output = vault.run(&["rename-heading", "a.md", "Details", "More", "--dry-run"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("a.md") && stdout.contains("b.md"));
+    assert_eq!(fs::read_to_string(vault.path().join("a.md")).unwrap(), NOTE);
+    assert_eq!(
+        fs::read_to_string(vault.path().join("b.md")).unwrap(),
+        "[details](a.md#details)\n"
+    );
+    assert!(!vault.path().join(".trash").exists());
+
+    let output = vault.run(&["rename-heading", "a.md", "Details", "More"]);
+    assert!(output.status.success());
+    assert_eq!(
+        fs::read_to_string(vault.path().join("b.md")).unwrap(),
+        "[details](a.md#more)\n"
+    );
+}